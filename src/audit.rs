@@ -0,0 +1,666 @@
+use crate::connection_identity::ConnectionIdSource;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use kodegen_config::KodegenConfig;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+/// `prev_hash` of the first entry in an audit log
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Bytes read per step when scanning back from the end of the log for its last entry
+const TAIL_CHUNK_BYTES: u64 = 8 * 1024;
+
+/// Default audit log location for a category: `<log dir>/audit_<category>.jsonl`
+///
+/// Unlike tool history the file is shared by every instance of the category, so
+/// the chain continues across restarts and a removed run shows up as a gap.
+/// Instances running at the same time append under an exclusive file lock, each
+/// continuing from the last entry on disk.
+pub fn default_path(category: &str) -> PathBuf {
+    KodegenConfig::log_dir()
+        .unwrap_or_else(|_| PathBuf::from("logs"))
+        .join(format!("audit_{category}.jsonl"))
+}
+
+/// How a tool call ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    /// The tool ran but returned a result with `is_error: true`
+    ToolError,
+    /// The call failed with a JSON-RPC error (including rejected calls)
+    Error,
+}
+
+/// One line of the audit log
+///
+/// `hash` is the SHA-256 of the line's JSON without the `hash` field, which
+/// includes `prev_hash` - editing, inserting or removing an entry breaks the
+/// chain from that point on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Position in the chain, starting at 0
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub category: String,
+    pub instance_id: String,
+    pub connection_id: Option<String>,
+    pub connection_id_source: Option<ConnectionIdSource>,
+    pub request_id: Option<String>,
+    pub tool_name: String,
+    /// SHA-256 of the arguments JSON (the arguments themselves are not logged)
+    pub args_sha256: String,
+    pub outcome: AuditOutcome,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+/// A finished tool call to record
+#[derive(Debug, Clone, Copy)]
+pub struct AuditEvent<'a> {
+    pub connection_id: Option<&'a str>,
+    pub connection_id_source: Option<ConnectionIdSource>,
+    pub request_id: Option<&'a str>,
+    pub tool_name: &'a str,
+    pub arguments: &'a serde_json::Value,
+    pub outcome: AuditOutcome,
+    pub error: Option<&'a str>,
+    pub duration_ms: u64,
+}
+
+/// Append-only, hash-chained log of tool invocations
+///
+/// `record` hands entries to a dedicated writer thread, so tool calls never wait
+/// on the file. The writer appends whatever is queued in one write followed by
+/// `sync_data`, holding an exclusive lock on the file while it does. It keeps
+/// the end of the chain in memory and only reads the file's tail again when
+/// another process appended since its last write. Write failures are logged,
+/// never surfaced to the client; they show up as a chain error on verification.
+pub struct AuditLog {
+    path: PathBuf,
+    sender: Option<mpsc::Sender<WriterMessage>>,
+    writer: Option<std::thread::JoinHandle<()>>,
+}
+
+impl AuditLog {
+    /// Open (or create) the audit log at `path` and continue its chain
+    pub fn open(path: impl Into<PathBuf>, category: &str, instance_id: &str) -> Result<Self> {
+        let path = path.into();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create audit log directory {}", dir.display()))?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open audit log {}", path.display()))?;
+
+        let head = ChainHead::read(&mut file, &path)
+            .with_context(|| format!("Failed to read audit log {}", path.display()))?;
+        log::info!("Audit log: {} (next entry {})", path.display(), head.next_seq);
+
+        let (sender, receiver) = mpsc::channel();
+        let mut writer = AuditWriter {
+            path: path.clone(),
+            category: category.to_string(),
+            instance_id: instance_id.to_string(),
+            file,
+            head: Some(head),
+        };
+        let writer = std::thread::Builder::new()
+            .name("audit-log".to_string())
+            .spawn(move || writer.run(receiver))
+            .context("Failed to start audit log writer")?;
+
+        Ok(Self {
+            path,
+            sender: Some(sender),
+            writer: Some(writer),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Queue an entry for a finished tool call
+    pub fn record(&self, event: AuditEvent<'_>) {
+        let entry = PendingEntry {
+            timestamp: Utc::now(),
+            connection_id: event.connection_id.map(str::to_string),
+            connection_id_source: event.connection_id_source,
+            request_id: event.request_id.map(str::to_string),
+            tool_name: event.tool_name.to_string(),
+            args_sha256: hex_digest(event.arguments.to_string().as_bytes()),
+            outcome: event.outcome,
+            error: event.error.map(str::to_string),
+            duration_ms: event.duration_ms,
+        };
+        if self.send(WriterMessage::Record(Box::new(entry))).is_err() {
+            log::error!("Audit log writer for {} has stopped; dropped entry for {}", self.path.display(), event.tool_name);
+        }
+    }
+
+    /// Block until every entry recorded so far has been written
+    pub fn flush(&self) {
+        let (done, written) = mpsc::channel();
+        if self.send(WriterMessage::Flush(done)).is_ok() {
+            let _ = written.recv();
+        }
+    }
+
+    fn send(&self, message: WriterMessage) -> Result<(), mpsc::SendError<WriterMessage>> {
+        match self.sender {
+            Some(ref sender) => sender.send(message),
+            None => Err(mpsc::SendError(message)),
+        }
+    }
+}
+
+impl Drop for AuditLog {
+    /// Write out queued entries before the log goes away
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        if let Some(writer) = self.writer.take()
+            && writer.join().is_err()
+        {
+            log::error!("Audit log writer for {} panicked", self.path.display());
+        }
+    }
+}
+
+/// An entry waiting for the writer to link it into the chain
+struct PendingEntry {
+    timestamp: DateTime<Utc>,
+    connection_id: Option<String>,
+    connection_id_source: Option<ConnectionIdSource>,
+    request_id: Option<String>,
+    tool_name: String,
+    args_sha256: String,
+    outcome: AuditOutcome,
+    error: Option<String>,
+    duration_ms: u64,
+}
+
+enum WriterMessage {
+    Record(Box<PendingEntry>),
+    /// Answered once everything queued before it has been written
+    Flush(mpsc::Sender<()>),
+}
+
+/// Where the next entry links into the chain
+struct ChainHead {
+    next_seq: u64,
+    prev_hash: String,
+    /// File length the head was taken at; any other length means another process appended
+    len: u64,
+    /// The file ends with a torn line, so the next write starts on a new line
+    needs_newline: bool,
+}
+
+impl ChainHead {
+    /// Read the head from the end of the file
+    fn read(file: &mut File, path: &Path) -> std::io::Result<Self> {
+        let (next_seq, prev_hash) = match last_entry(file, path)? {
+            Some(entry) => (entry.seq + 1, entry.hash),
+            None => (0, GENESIS_HASH.to_string()),
+        };
+        Ok(Self {
+            next_seq,
+            prev_hash,
+            len: file.seek(SeekFrom::End(0))?,
+            needs_newline: !ends_with_newline(file)?,
+        })
+    }
+}
+
+/// Owns the file on the writer thread
+struct AuditWriter {
+    path: PathBuf,
+    category: String,
+    instance_id: String,
+    file: File,
+    /// None after a failed write, so the next one starts from what is on disk
+    head: Option<ChainHead>,
+}
+
+impl AuditWriter {
+    fn run(&mut self, receiver: mpsc::Receiver<WriterMessage>) {
+        while let Ok(message) = receiver.recv() {
+            // Write everything queued so far in one go
+            let mut entries = Vec::new();
+            let mut flushes = Vec::new();
+            for message in std::iter::once(message).chain(receiver.try_iter()) {
+                match message {
+                    WriterMessage::Record(entry) => entries.push(*entry),
+                    WriterMessage::Flush(done) => flushes.push(done),
+                }
+            }
+
+            if !entries.is_empty() {
+                // Other instances may append to the same file - hold the lock while linking and writing
+                let result = self.file.lock().map_err(anyhow::Error::from).and_then(|()| {
+                    let result = self.append(&entries);
+                    self.file.unlock()?;
+                    result
+                });
+                if let Err(e) = result {
+                    self.head = None;
+                    log::error!("Failed to write {} entries to audit log {}: {:#}", entries.len(), self.path.display(), e);
+                }
+            }
+            for done in flushes {
+                let _ = done.send(());
+            }
+        }
+    }
+
+    /// Link `entries` after the last entry in the file and write them (the caller holds the file lock)
+    fn append(&mut self, entries: &[PendingEntry]) -> Result<()> {
+        let len = self.file.seek(SeekFrom::End(0))?;
+        let mut head = match self.head.take() {
+            Some(head) if head.len == len => head,
+            _ => ChainHead::read(&mut self.file, &self.path)?,
+        };
+
+        let mut lines = if head.needs_newline { "\n".to_string() } else { String::new() };
+        for pending in entries {
+            let entry = AuditEntry {
+                seq: head.next_seq,
+                timestamp: pending.timestamp,
+                category: self.category.clone(),
+                instance_id: self.instance_id.clone(),
+                connection_id: pending.connection_id.clone(),
+                connection_id_source: pending.connection_id_source,
+                request_id: pending.request_id.clone(),
+                tool_name: pending.tool_name.clone(),
+                args_sha256: pending.args_sha256.clone(),
+                outcome: pending.outcome,
+                error: pending.error.clone(),
+                duration_ms: pending.duration_ms,
+                prev_hash: std::mem::take(&mut head.prev_hash),
+                hash: String::new(),
+            };
+
+            let mut value = serde_json::to_value(&entry).context("Failed to serialize audit entry")?;
+            let hash = entry_hash(&value);
+            value["hash"] = hash.clone().into();
+            lines.push_str(&format!("{}\n", value));
+
+            head.next_seq += 1;
+            head.prev_hash = hash;
+        }
+
+        self.file.write_all(lines.as_bytes())?;
+        self.file.sync_data()?;
+        head.len = len + lines.len() as u64;
+        head.needs_newline = false;
+        self.head = Some(head);
+        Ok(())
+    }
+}
+
+/// What is wrong with an audit log line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditProblemKind {
+    /// Not a valid audit entry
+    Malformed,
+    /// The entry was edited after it was written
+    HashMismatch,
+    /// `prev_hash` does not match the previous entry
+    ChainBroken,
+    /// Sequence numbers skip, repeat or do not start at 0
+    SequenceGap,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditProblem {
+    /// 1-based line number
+    pub line: usize,
+    pub kind: AuditProblemKind,
+    pub detail: String,
+}
+
+/// Result of [`verify_audit_log`]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AuditVerification {
+    pub entries: u64,
+    pub last_seq: Option<u64>,
+    pub last_hash: Option<String>,
+    pub problems: Vec<AuditProblem>,
+}
+
+impl AuditVerification {
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Check every entry's hash, the chain between entries and the sequence numbers
+///
+/// Reports every problem rather than stopping at the first. After a broken
+/// link the chain is followed from the entry as found, so one edit is reported
+/// once. Entries removed from the end cannot be detected from the file alone;
+/// compare `last_seq`/`last_hash` with a copy kept elsewhere.
+pub fn verify_audit_log(path: &Path) -> Result<AuditVerification> {
+    let file = File::open(path).with_context(|| format!("Failed to open audit log {}", path.display()))?;
+
+    let mut report = AuditVerification::default();
+    let mut expected_seq = 0;
+    let mut expected_prev = GENESIS_HASH.to_string();
+
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line_no = index + 1;
+        let line = line.with_context(|| format!("Failed to read audit log {}", path.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut problem = |kind, detail: String| {
+            report.problems.push(AuditProblem {
+                line: line_no,
+                kind,
+                detail,
+            })
+        };
+
+        let value: serde_json::Value = match serde_json::from_str(&line) {
+            Ok(value) => value,
+            Err(e) => {
+                problem(AuditProblemKind::Malformed, e.to_string());
+                continue;
+            }
+        };
+        let entry: AuditEntry = match serde_json::from_value(value.clone()) {
+            Ok(entry) => entry,
+            Err(e) => {
+                problem(AuditProblemKind::Malformed, e.to_string());
+                continue;
+            }
+        };
+
+        let hash = entry_hash(&value);
+        if hash != entry.hash {
+            problem(
+                AuditProblemKind::HashMismatch,
+                format!("entry {} hashes to {} but records {}", entry.seq, hash, entry.hash),
+            );
+        }
+        if entry.seq != expected_seq {
+            problem(
+                AuditProblemKind::SequenceGap,
+                format!("expected entry {} but found {}", expected_seq, entry.seq),
+            );
+        }
+        if entry.prev_hash != expected_prev {
+            problem(
+                AuditProblemKind::ChainBroken,
+                format!("entry {} links to {} but the previous entry is {}", entry.seq, entry.prev_hash, expected_prev),
+            );
+        }
+
+        report.entries += 1;
+        expected_seq = entry.seq + 1;
+        expected_prev = entry.hash.clone();
+        report.last_seq = Some(entry.seq);
+        report.last_hash = Some(entry.hash);
+    }
+
+    Ok(report)
+}
+
+/// Verify an audit log and print the result (the `verify-audit-log` command)
+///
+/// Returns an error if the log has any problems, so the process exits non-zero.
+pub fn run_verify_command(path: &Path) -> Result<()> {
+    let report = verify_audit_log(path)?;
+
+    for problem in &report.problems {
+        println!("line {}: {:?}: {}", problem.line, problem.kind, problem.detail);
+    }
+    println!(
+        "{}: {} entries, last entry {}, last hash {}",
+        path.display(),
+        report.entries,
+        report.last_seq.map_or_else(|| "-".to_string(), |seq| seq.to_string()),
+        report.last_hash.as_deref().unwrap_or("-"),
+    );
+
+    if report.is_valid() {
+        println!("OK: hash chain intact");
+        Ok(())
+    } else {
+        anyhow::bail!("audit log {} failed verification ({} problems)", path.display(), report.problems.len())
+    }
+}
+
+/// Last parseable entry of the log, to continue its chain
+///
+/// Reads backwards from the end in chunks, so only the tail of the file is read.
+fn last_entry(file: &mut File, path: &Path) -> std::io::Result<Option<AuditEntry>> {
+    let mut end = file.seek(SeekFrom::End(0))?;
+    // Start of a line whose beginning lies in a chunk not read yet
+    let mut carry = Vec::new();
+    let mut torn = false;
+
+    while end > 0 {
+        let start = end.saturating_sub(TAIL_CHUNK_BYTES);
+        let mut region = vec![0; (end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut region)?;
+        region.extend_from_slice(&carry);
+        end = start;
+
+        // Unless this chunk starts the file, its first line may begin further back
+        let complete_from = if start == 0 {
+            0
+        } else {
+            match region.iter().position(|&b| b == b'\n') {
+                Some(newline) => newline + 1,
+                None => {
+                    carry = region;
+                    continue;
+                }
+            }
+        };
+
+        for line in region[complete_from..].rsplit(|&b| b == b'\n') {
+            if line.trim_ascii().is_empty() {
+                continue;
+            }
+            match serde_json::from_slice(line) {
+                Ok(entry) => {
+                    if torn {
+                        // A torn final line (e.g. after a crash) - chain from the last good
+                        // entry; verification still reports the bad line
+                        log::warn!("Audit log {} ends with a malformed entry", path.display());
+                    }
+                    return Ok(Some(entry));
+                }
+                Err(_) => torn = true,
+            }
+        }
+        carry = region[..complete_from].to_vec();
+    }
+
+    Ok(None)
+}
+
+/// Whether the log is empty or its last byte is a newline
+fn ends_with_newline(file: &mut File) -> std::io::Result<bool> {
+    let len = file.seek(SeekFrom::End(0))?;
+    if len == 0 {
+        return Ok(true);
+    }
+    let mut last = [0; 1];
+    file.seek(SeekFrom::Start(len - 1))?;
+    file.read_exact(&mut last)?;
+    Ok(last[0] == b'\n')
+}
+
+/// Hash of an entry's JSON with the `hash` field removed
+fn entry_hash(value: &serde_json::Value) -> String {
+    let mut value = value.clone();
+    if let Some(object) = value.as_object_mut() {
+        object.remove("hash");
+    }
+    hex_digest(value.to_string().as_bytes())
+}
+
+fn hex_digest(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh audit log path, unique per test
+    fn log_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kodegen-audit-test-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.join("audit_test.jsonl")
+    }
+
+    fn record(log: &AuditLog, tool_name: &str) {
+        log.record(AuditEvent {
+            connection_id: Some("conn-1"),
+            connection_id_source: Some(ConnectionIdSource::Header),
+            request_id: None,
+            tool_name,
+            arguments: &serde_json::json!({ "path": "/tmp/file" }),
+            outcome: AuditOutcome::Success,
+            error: None,
+            duration_ms: 5,
+        });
+    }
+
+    fn problem_kinds(path: &Path) -> Vec<(usize, AuditProblemKind)> {
+        verify_audit_log(path)
+            .unwrap()
+            .problems
+            .iter()
+            .map(|problem| (problem.line, problem.kind))
+            .collect()
+    }
+
+    #[test]
+    fn chain_verifies_and_continues_across_reopen() {
+        let path = log_path("reopen");
+        let log = AuditLog::open(&path, "test", "one").unwrap();
+        for _ in 0..3 {
+            record(&log, "fs_read_file");
+        }
+        drop(log);
+        record(&AuditLog::open(&path, "test", "two").unwrap(), "fs_write_file");
+
+        let report = verify_audit_log(&path).unwrap();
+        assert!(report.is_valid(), "{:?}", report.problems);
+        assert_eq!((report.entries, report.last_seq), (4, Some(3)));
+    }
+
+    #[test]
+    fn concurrent_instances_share_one_chain() {
+        let path = log_path("concurrent");
+        let one = AuditLog::open(&path, "test", "one").unwrap();
+        let two = AuditLog::open(&path, "test", "two").unwrap();
+        for _ in 0..3 {
+            record(&one, "fs_read_file");
+            record(&two, "fs_read_file");
+        }
+        one.flush();
+        two.flush();
+
+        let report = verify_audit_log(&path).unwrap();
+        assert!(report.is_valid(), "{:?}", report.problems);
+        assert_eq!(report.last_seq, Some(5));
+    }
+
+    #[test]
+    fn writer_rereads_the_tail_after_another_instance_appends() {
+        let path = log_path("interleaved");
+        let one = AuditLog::open(&path, "test", "one").unwrap();
+        let two = AuditLog::open(&path, "test", "two").unwrap();
+        for log in [&one, &two, &one, &one, &two] {
+            record(log, "fs_read_file");
+            log.flush();
+        }
+
+        let report = verify_audit_log(&path).unwrap();
+        assert!(report.is_valid(), "{:?}", report.problems);
+        assert_eq!(report.last_seq, Some(4));
+    }
+
+    #[test]
+    fn detects_edited_removed_and_malformed_entries() {
+        let path = log_path("tamper");
+        let log = AuditLog::open(&path, "test", "one").unwrap();
+        for _ in 0..4 {
+            record(&log, "fs_read_file");
+        }
+        drop(log);
+        let lines: Vec<String> = std::fs::read_to_string(&path).unwrap().lines().map(str::to_string).collect();
+
+        // Edited entry: its own hash no longer matches
+        let edited = lines[1].replace("fs_read_file", "fs_delete_file");
+        std::fs::write(&path, [&lines[0], &edited, &lines[2], &lines[3]].map(|l| format!("{l}\n")).concat()).unwrap();
+        assert_eq!(problem_kinds(&path), [(2, AuditProblemKind::HashMismatch)]);
+
+        // Removed entry: the next one skips a sequence number and links to a missing hash
+        std::fs::write(&path, [&lines[0], &lines[2], &lines[3]].map(|l| format!("{l}\n")).concat()).unwrap();
+        assert_eq!(
+            problem_kinds(&path),
+            [(2, AuditProblemKind::SequenceGap), (2, AuditProblemKind::ChainBroken)]
+        );
+
+        // Garbage line
+        std::fs::write(&path, [&lines[0], "not json", &lines[1]].map(|l| format!("{l}\n")).concat()).unwrap();
+        assert_eq!(problem_kinds(&path), [(2, AuditProblemKind::Malformed)]);
+    }
+
+    #[test]
+    fn continues_after_a_torn_final_line() {
+        let path = log_path("torn");
+        let log = AuditLog::open(&path, "test", "one").unwrap();
+        record(&log, "fs_read_file");
+        drop(log);
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"seq":1,"timest"#).unwrap();
+        drop(file);
+
+        record(&AuditLog::open(&path, "test", "two").unwrap(), "fs_read_file");
+
+        // Only the torn line is reported; the new entry links to the last good one
+        assert_eq!(problem_kinds(&path), [(2, AuditProblemKind::Malformed)]);
+        assert_eq!(verify_audit_log(&path).unwrap().last_seq, Some(1));
+    }
+
+    #[test]
+    fn finds_the_last_entry_beyond_one_tail_chunk() {
+        let path = log_path("tail");
+        let log = AuditLog::open(&path, "test", "one").unwrap();
+        let long_tool = "t".repeat(TAIL_CHUNK_BYTES as usize);
+        for _ in 0..3 {
+            record(&log, &long_tool);
+        }
+        log.flush();
+
+        let mut file = File::open(&path).unwrap();
+        let last = last_entry(&mut file, &path).unwrap().unwrap();
+        assert_eq!(last.seq, 2);
+        assert!(verify_audit_log(&path).unwrap().is_valid());
+    }
+}
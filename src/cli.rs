@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::time::Duration;
@@ -7,6 +7,13 @@ use anyhow::{Result, Context};
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
+    /// Path to TOML config file (overrides KODEGEN_CONFIG)
+    ///
+    /// Settings are applied in order: defaults, config file, KODEGEN_*
+    /// environment variables, then CLI flags. See `ServerConfig`.
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
     /// HTTP server bind address (e.g., 127.0.0.1:8080)
     #[arg(long, value_name = "ADDRESS")]
     pub http: Option<SocketAddr>,
@@ -19,6 +26,13 @@ pub struct Cli {
     #[arg(long, value_name = "PATH", requires = "tls_cert")]
     pub tls_key: Option<PathBuf>,
 
+    /// CA bundle for verifying optional TLS client certificates
+    ///
+    /// Clients that present a certificate signed by one of these CAs get a
+    /// connection id derived from it; clients without a certificate are still accepted.
+    #[arg(long, value_name = "PATH", requires = "tls_cert")]
+    pub tls_client_ca: Option<PathBuf>,
+
     /// Graceful shutdown timeout in seconds
     ///
     /// Timeout budget allocation:
@@ -35,8 +49,8 @@ pub struct Cli {
     ///
     /// Note: Request drain and manager timeouts are independent but must
     /// fit within the cleanup buffer. See server.rs for details.
-    #[arg(long, value_name = "SECONDS", default_value = "30")]
-    pub shutdown_timeout_secs: u64,
+    #[arg(long, value_name = "SECONDS")]
+    pub shutdown_timeout_secs: Option<u64>,
 
     /// Session keep-alive timeout in seconds (0 or omit = infinite, default: infinite)
     ///
@@ -52,6 +66,100 @@ pub struct Cli {
     /// when clients disconnect or the server restarts.
     #[arg(long, value_name = "SECONDS")]
     pub keep_alive: Option<u64>,
+
+    /// Per-session message channel capacity (default: 16)
+    #[arg(long, value_name = "MESSAGES")]
+    pub channel_capacity: Option<usize>,
+
+    /// SSE keep-alive ping interval in seconds, 0 disables pings (default: 15)
+    #[arg(long, value_name = "SECONDS")]
+    pub sse_keep_alive: Option<u64>,
+
+    /// Time allowed for in-flight tool calls to finish during shutdown (default: 30)
+    #[arg(long, value_name = "SECONDS")]
+    pub drain_timeout: Option<u64>,
+
+    /// TCP listen backlog when binding the server socket (default: 1024)
+    #[arg(long, value_name = "CONNECTIONS")]
+    pub listen_backlog: Option<u32>,
+
+    /// Maximum concurrent sessions (default: unlimited)
+    #[arg(long, value_name = "SESSIONS")]
+    pub max_sessions: Option<usize>,
+
+    /// What to do when --max-sessions is reached (default: reject)
+    #[arg(long, value_enum, value_name = "POLICY")]
+    pub session_limit_policy: Option<crate::session::SessionLimitPolicy>,
+
+    /// Release a connection's resources after this many seconds without tool calls or open sessions, 0 = never (default: 0)
+    #[arg(long, value_name = "SECONDS")]
+    pub connection_idle_timeout: Option<u64>,
+
+    /// Export OpenTelemetry traces to this OTLP/HTTP collector (e.g. http://localhost:4318)
+    #[arg(long, value_name = "URL")]
+    pub otlp_endpoint: Option<String>,
+
+    /// Log output format (default: text)
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    pub log_format: Option<crate::logging::LogFormat>,
+
+    /// Set the log level of one module, e.g. --log-filter tantivy=warn (repeatable)
+    #[arg(long = "log-filter", value_name = "MODULE=LEVEL", value_parser = crate::logging::parse_log_filter)]
+    pub log_filters: Vec<(String, String)>,
+
+    /// Memory monitor sampling interval in seconds (default: 30)
+    #[arg(long, value_name = "SECONDS")]
+    pub memory_check_interval: Option<u64>,
+
+    /// Warn when RSS grows by this many MB between samples, 0 = never (default: 100)
+    #[arg(long, value_name = "MB")]
+    pub memory_growth_warning_mb: Option<u64>,
+
+    /// Reject new tool calls with a retryable error while RSS is above this many MB (default: no limit)
+    #[arg(long, value_name = "MB")]
+    pub memory_limit_mb: Option<u64>,
+
+    /// Log and flag tool calls taking at least this many ms, 0 = never (default: 30000)
+    #[arg(long, value_name = "MS")]
+    pub slow_call_threshold_ms: Option<u64>,
+
+    /// Slow-call threshold for one tool, e.g. --slow-call-threshold fs_search=120000 (repeatable)
+    #[arg(long = "slow-call-threshold", value_name = "TOOL=MS", value_parser = crate::slow_calls::parse_slow_call_threshold)]
+    pub slow_call_thresholds: Vec<(String, u64)>,
+
+    /// Record every tool call in a hash-chained audit log
+    #[arg(long)]
+    pub audit_log: bool,
+
+    /// Audit log file (default: <log dir>/audit_<category>.jsonl)
+    #[arg(long, value_name = "PATH")]
+    pub audit_log_path: Option<PathBuf>,
+
+    /// Hourly usage buckets kept for /mcp/stats, 0 = none (default: 168)
+    #[arg(long, value_name = "HOURS")]
+    pub stats_hourly_retention_hours: Option<u64>,
+
+    /// Daily usage buckets kept for /mcp/stats, 0 = none (default: 90)
+    #[arg(long, value_name = "DAYS")]
+    pub stats_daily_retention_days: Option<u64>,
+
+    /// Where usage stats and tool history are persisted (default: json)
+    #[arg(long, value_enum, value_name = "BACKEND")]
+    pub storage: Option<crate::storage::StorageBackend>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Commands run instead of starting the server
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Check an audit log's hash chain for edits and gaps, then exit
+    VerifyAuditLog {
+        /// Audit log file (default: the configured audit log)
+        #[arg(value_name = "PATH")]
+        path: Option<PathBuf>,
+    },
 }
 
 impl Cli {
@@ -104,7 +212,9 @@ impl Cli {
 
     /// Get shutdown timeout duration
     pub fn shutdown_timeout(&self) -> Duration {
-        Duration::from_secs(self.shutdown_timeout_secs)
+        self.shutdown_timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| crate::ServerConfig::default().shutdown_timeout())
     }
 
     /// Convert CLI keep-alive argument to SessionConfig duration
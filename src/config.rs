@@ -0,0 +1,744 @@
+use crate::cli::Cli;
+use crate::logging::{self, LogFormat};
+use crate::slow_calls;
+use crate::storage::StorageBackend;
+use crate::time_buckets::BucketRetention;
+use crate::session::{SessionLimit, SessionLimitPolicy};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Environment variable naming the TOML config file (overridden by `--config`)
+pub const CONFIG_FILE_ENV: &str = "KODEGEN_CONFIG";
+
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_CHANNEL_CAPACITY: usize = 16;
+const DEFAULT_SSE_KEEP_ALIVE_SECS: u64 = 15;
+const DEFAULT_EMBEDDED_KEEP_ALIVE_SECS: u64 = 60 * 60;
+const DEFAULT_DRAIN_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_LISTEN_BACKLOG: u32 = 1024;
+const DEFAULT_CONNECTION_IDLE_TIMEOUT_SECS: u64 = 0;
+const DEFAULT_MEMORY_CHECK_INTERVAL_SECS: u64 = 30;
+const DEFAULT_MEMORY_GROWTH_WARNING_MB: u64 = 100;
+const DEFAULT_SLOW_CALL_THRESHOLD_MS: u64 = 30_000;
+const DEFAULT_SLOW_CALL_HISTORY: usize = 100;
+const DEFAULT_STATS_HOURLY_RETENTION_HOURS: u64 = 7 * 24;
+const DEFAULT_STATS_DAILY_RETENTION_DAYS: u64 = 90;
+
+/// Runtime configuration shared by `ServerBuilder::run()` and `ServerBuilder::serve()`
+///
+/// Sources are applied in increasing order of precedence:
+/// 1. Built-in defaults
+/// 2. TOML file given by `--config` or `KODEGEN_CONFIG`
+/// 3. `KODEGEN_*` environment variables (e.g. `KODEGEN_SHUTDOWN_TIMEOUT_SECS`)
+/// 4. CLI flags (`run()` only - embedded servers do not own the command line)
+///
+/// The file uses the field names below:
+/// ```toml
+/// http = "127.0.0.1:30437"
+/// shutdown_timeout_secs = 30
+/// keep_alive_secs = 3600
+/// channel_capacity = 16
+/// log_format = "json"
+///
+/// [log_filters]
+/// "tantivy" = "warn"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// HTTP server bind address (`KODEGEN_HTTP`)
+    pub http: Option<SocketAddr>,
+    /// TLS certificate file, enables HTTPS together with `tls_key` (`KODEGEN_TLS_CERT`)
+    pub tls_cert: Option<PathBuf>,
+    /// TLS private key file (`KODEGEN_TLS_KEY`)
+    pub tls_key: Option<PathBuf>,
+    /// CA bundle for optional TLS client certificates, used to derive connection ids (`KODEGEN_TLS_CLIENT_CA`)
+    pub tls_client_ca: Option<PathBuf>,
+    /// Graceful shutdown budget: 70% HTTP drain, 30% cleanup (`KODEGEN_SHUTDOWN_TIMEOUT_SECS`)
+    pub shutdown_timeout_secs: u64,
+    /// Idle session expiry, 0 = never; unset = never for `.run()`, 1 hour for `.serve()` (`KODEGEN_KEEP_ALIVE_SECS`)
+    pub keep_alive_secs: Option<u64>,
+    /// Per-session message channel capacity (`KODEGEN_CHANNEL_CAPACITY`)
+    pub channel_capacity: usize,
+    /// SSE keep-alive ping interval, 0 = disabled (`KODEGEN_SSE_KEEP_ALIVE_SECS`)
+    pub sse_keep_alive_secs: u64,
+    /// Time allowed for in-flight tool calls to finish before manager shutdown (`KODEGEN_DRAIN_TIMEOUT_SECS`)
+    pub drain_timeout_secs: u64,
+    /// TCP listen backlog when the server binds its own socket (`KODEGEN_LISTEN_BACKLOG`)
+    pub listen_backlog: u32,
+    /// Maximum concurrent sessions, unset = unlimited (`KODEGEN_MAX_SESSIONS`)
+    pub max_sessions: Option<usize>,
+    /// What to do when `max_sessions` is reached: `reject` or `evict_idle` (`KODEGEN_SESSION_LIMIT_POLICY`)
+    pub session_limit_policy: SessionLimitPolicy,
+    /// Release a connection's resources after this long without tool calls or open sessions, 0 = never (`KODEGEN_CONNECTION_IDLE_TIMEOUT_SECS`)
+    pub connection_idle_timeout_secs: u64,
+    /// OTLP/HTTP collector for trace export, e.g. `http://localhost:4318`, unset = no tracing (`KODEGEN_OTLP_ENDPOINT`)
+    pub otlp_endpoint: Option<String>,
+    /// Log output: `text` or `json`, one object per line (`KODEGEN_LOG_FORMAT`)
+    pub log_format: LogFormat,
+    /// Per-module log levels applied on top of `RUST_LOG`
+    ///
+    /// Setting this table in the config file replaces the built-in chromiumoxide/tantivy
+    /// filters; `KODEGEN_LOG_FILTERS` (`module=level,...`) and `--log-filter` add to it.
+    pub log_filters: BTreeMap<String, String>,
+    /// How often the memory monitor samples RSS (`KODEGEN_MEMORY_CHECK_INTERVAL_SECS`)
+    pub memory_check_interval_secs: u64,
+    /// Warn when RSS grows by this much between samples, 0 = never (`KODEGEN_MEMORY_GROWTH_WARNING_MB`)
+    pub memory_growth_warning_mb: u64,
+    /// Reject new tool calls while RSS is above this limit, unset = no limit (`KODEGEN_MEMORY_LIMIT_MB`)
+    pub memory_limit_mb: Option<u64>,
+    /// Tool calls taking at least this long are logged and flagged as slow, 0 = never (`KODEGEN_SLOW_CALL_THRESHOLD_MS`)
+    pub slow_call_threshold_ms: u64,
+    /// Per-tool overrides of `slow_call_threshold_ms`; `KODEGEN_SLOW_CALL_THRESHOLDS` (`tool=ms,...`) and `--slow-call-threshold` add to it
+    pub slow_call_thresholds: BTreeMap<String, u64>,
+    /// Number of recent slow calls kept for `/mcp/slow-calls` (`KODEGEN_SLOW_CALL_HISTORY`)
+    pub slow_call_history: usize,
+    /// Record every tool call in a hash-chained audit log (`KODEGEN_AUDIT_LOG`)
+    pub audit_log: bool,
+    /// Audit log file, unset = `<log dir>/audit_<category>.jsonl` (`KODEGEN_AUDIT_LOG_PATH`)
+    pub audit_log_path: Option<PathBuf>,
+    /// Hourly usage buckets kept in the stats file (`KODEGEN_STATS_HOURLY_RETENTION_HOURS`)
+    pub stats_hourly_retention_hours: u64,
+    /// Daily usage buckets kept in the stats file (`KODEGEN_STATS_DAILY_RETENTION_DAYS`)
+    pub stats_daily_retention_days: u64,
+    /// Where usage stats and tool history are persisted (`KODEGEN_STORAGE`)
+    pub storage: StorageBackend,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            http: None,
+            tls_cert: None,
+            tls_key: None,
+            tls_client_ca: None,
+            shutdown_timeout_secs: DEFAULT_SHUTDOWN_TIMEOUT_SECS,
+            keep_alive_secs: None,
+            channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+            sse_keep_alive_secs: DEFAULT_SSE_KEEP_ALIVE_SECS,
+            drain_timeout_secs: DEFAULT_DRAIN_TIMEOUT_SECS,
+            listen_backlog: DEFAULT_LISTEN_BACKLOG,
+            max_sessions: None,
+            session_limit_policy: SessionLimitPolicy::default(),
+            connection_idle_timeout_secs: DEFAULT_CONNECTION_IDLE_TIMEOUT_SECS,
+            otlp_endpoint: None,
+            log_format: LogFormat::default(),
+            log_filters: logging::default_log_filters(),
+            memory_check_interval_secs: DEFAULT_MEMORY_CHECK_INTERVAL_SECS,
+            memory_growth_warning_mb: DEFAULT_MEMORY_GROWTH_WARNING_MB,
+            memory_limit_mb: None,
+            slow_call_threshold_ms: DEFAULT_SLOW_CALL_THRESHOLD_MS,
+            slow_call_thresholds: BTreeMap::new(),
+            slow_call_history: DEFAULT_SLOW_CALL_HISTORY,
+            audit_log: false,
+            audit_log_path: None,
+            stats_hourly_retention_hours: DEFAULT_STATS_HOURLY_RETENTION_HOURS,
+            stats_daily_retention_days: DEFAULT_STATS_DAILY_RETENTION_DAYS,
+            storage: StorageBackend::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Load configuration from defaults, config file and environment, then apply CLI flags
+    ///
+    /// Pass `None` for embedded servers. The result is validated.
+    pub fn load(cli: Option<&Cli>) -> Result<Self> {
+        let config_file = cli
+            .and_then(|cli| cli.config.clone())
+            .or_else(|| std::env::var_os(CONFIG_FILE_ENV).map(PathBuf::from));
+
+        let mut config = match config_file {
+            Some(path) => Self::from_file(&path)?,
+            None => Self::default(),
+        };
+
+        config.apply_env()?;
+
+        if let Some(cli) = cli {
+            config.apply_cli(cli);
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Read a TOML config file (unset fields keep their defaults)
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let config = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+        log::info!("Loaded server config from {}", path.display());
+        Ok(config)
+    }
+
+    /// Override fields from `KODEGEN_*` environment variables
+    pub fn apply_env(&mut self) -> Result<()> {
+        if let Some(http) = env_var("KODEGEN_HTTP")? {
+            self.http = Some(http);
+        }
+        if let Some(tls_cert) = env_var("KODEGEN_TLS_CERT")? {
+            self.tls_cert = Some(tls_cert);
+        }
+        if let Some(tls_key) = env_var("KODEGEN_TLS_KEY")? {
+            self.tls_key = Some(tls_key);
+        }
+        if let Some(tls_client_ca) = env_var("KODEGEN_TLS_CLIENT_CA")? {
+            self.tls_client_ca = Some(tls_client_ca);
+        }
+        if let Some(secs) = env_var("KODEGEN_SHUTDOWN_TIMEOUT_SECS")? {
+            self.shutdown_timeout_secs = secs;
+        }
+        if let Some(secs) = env_var("KODEGEN_KEEP_ALIVE_SECS")? {
+            self.keep_alive_secs = Some(secs);
+        }
+        if let Some(capacity) = env_var("KODEGEN_CHANNEL_CAPACITY")? {
+            self.channel_capacity = capacity;
+        }
+        if let Some(secs) = env_var("KODEGEN_SSE_KEEP_ALIVE_SECS")? {
+            self.sse_keep_alive_secs = secs;
+        }
+        if let Some(secs) = env_var("KODEGEN_DRAIN_TIMEOUT_SECS")? {
+            self.drain_timeout_secs = secs;
+        }
+        if let Some(backlog) = env_var("KODEGEN_LISTEN_BACKLOG")? {
+            self.listen_backlog = backlog;
+        }
+        if let Some(max_sessions) = env_var("KODEGEN_MAX_SESSIONS")? {
+            self.max_sessions = Some(max_sessions);
+        }
+        if let Some(policy) = env_var("KODEGEN_SESSION_LIMIT_POLICY")? {
+            self.session_limit_policy = policy;
+        }
+        if let Some(secs) = env_var("KODEGEN_CONNECTION_IDLE_TIMEOUT_SECS")? {
+            self.connection_idle_timeout_secs = secs;
+        }
+        if let Some(endpoint) = env_var("KODEGEN_OTLP_ENDPOINT")? {
+            self.otlp_endpoint = Some(endpoint);
+        }
+        if let Some(format) = env_var("KODEGEN_LOG_FORMAT")? {
+            self.log_format = format;
+        }
+        if let Some(filters) = env_var::<String>("KODEGEN_LOG_FILTERS")? {
+            let filters = logging::parse_log_filters(&filters)
+                .map_err(|e| anyhow::anyhow!("Invalid value for KODEGEN_LOG_FILTERS: {}", e))?;
+            self.log_filters.extend(filters);
+        }
+        if let Some(secs) = env_var("KODEGEN_MEMORY_CHECK_INTERVAL_SECS")? {
+            self.memory_check_interval_secs = secs;
+        }
+        if let Some(mb) = env_var("KODEGEN_MEMORY_GROWTH_WARNING_MB")? {
+            self.memory_growth_warning_mb = mb;
+        }
+        if let Some(mb) = env_var("KODEGEN_MEMORY_LIMIT_MB")? {
+            self.memory_limit_mb = Some(mb);
+        }
+        if let Some(ms) = env_var("KODEGEN_SLOW_CALL_THRESHOLD_MS")? {
+            self.slow_call_threshold_ms = ms;
+        }
+        if let Some(thresholds) = env_var::<String>("KODEGEN_SLOW_CALL_THRESHOLDS")? {
+            let thresholds = slow_calls::parse_slow_call_thresholds(&thresholds)
+                .map_err(|e| anyhow::anyhow!("Invalid value for KODEGEN_SLOW_CALL_THRESHOLDS: {}", e))?;
+            self.slow_call_thresholds.extend(thresholds);
+        }
+        if let Some(capacity) = env_var("KODEGEN_SLOW_CALL_HISTORY")? {
+            self.slow_call_history = capacity;
+        }
+        if let Some(enabled) = env_var("KODEGEN_AUDIT_LOG")? {
+            self.audit_log = enabled;
+        }
+        if let Some(path) = env_var("KODEGEN_AUDIT_LOG_PATH")? {
+            self.audit_log_path = Some(path);
+        }
+        if let Some(hours) = env_var("KODEGEN_STATS_HOURLY_RETENTION_HOURS")? {
+            self.stats_hourly_retention_hours = hours;
+        }
+        if let Some(days) = env_var("KODEGEN_STATS_DAILY_RETENTION_DAYS")? {
+            self.stats_daily_retention_days = days;
+        }
+        if let Some(storage) = env_var("KODEGEN_STORAGE")? {
+            self.storage = storage;
+        }
+        Ok(())
+    }
+
+    /// Override fields from CLI flags that were given
+    pub fn apply_cli(&mut self, cli: &Cli) {
+        if let Some(http) = cli.http {
+            self.http = Some(http);
+        }
+        if let Some((tls_cert, tls_key)) = cli.tls_config() {
+            self.tls_cert = Some(tls_cert);
+            self.tls_key = Some(tls_key);
+        }
+        if let Some(ref tls_client_ca) = cli.tls_client_ca {
+            self.tls_client_ca = Some(tls_client_ca.clone());
+        }
+        if let Some(secs) = cli.shutdown_timeout_secs {
+            self.shutdown_timeout_secs = secs;
+        }
+        if let Some(secs) = cli.keep_alive {
+            self.keep_alive_secs = Some(secs);
+        }
+        if let Some(capacity) = cli.channel_capacity {
+            self.channel_capacity = capacity;
+        }
+        if let Some(secs) = cli.sse_keep_alive {
+            self.sse_keep_alive_secs = secs;
+        }
+        if let Some(secs) = cli.drain_timeout {
+            self.drain_timeout_secs = secs;
+        }
+        if let Some(backlog) = cli.listen_backlog {
+            self.listen_backlog = backlog;
+        }
+        if let Some(max_sessions) = cli.max_sessions {
+            self.max_sessions = Some(max_sessions);
+        }
+        if let Some(policy) = cli.session_limit_policy {
+            self.session_limit_policy = policy;
+        }
+        if let Some(secs) = cli.connection_idle_timeout {
+            self.connection_idle_timeout_secs = secs;
+        }
+        if let Some(ref endpoint) = cli.otlp_endpoint {
+            self.otlp_endpoint = Some(endpoint.clone());
+        }
+        if let Some(format) = cli.log_format {
+            self.log_format = format;
+        }
+        self.log_filters.extend(cli.log_filters.iter().cloned());
+        if let Some(secs) = cli.memory_check_interval {
+            self.memory_check_interval_secs = secs;
+        }
+        if let Some(mb) = cli.memory_growth_warning_mb {
+            self.memory_growth_warning_mb = mb;
+        }
+        if let Some(mb) = cli.memory_limit_mb {
+            self.memory_limit_mb = Some(mb);
+        }
+        if let Some(ms) = cli.slow_call_threshold_ms {
+            self.slow_call_threshold_ms = ms;
+        }
+        self.slow_call_thresholds.extend(cli.slow_call_thresholds.iter().cloned());
+        if cli.audit_log {
+            self.audit_log = true;
+        }
+        if let Some(ref path) = cli.audit_log_path {
+            self.audit_log_path = Some(path.clone());
+        }
+        if let Some(hours) = cli.stats_hourly_retention_hours {
+            self.stats_hourly_retention_hours = hours;
+        }
+        if let Some(days) = cli.stats_daily_retention_days {
+            self.stats_daily_retention_days = days;
+        }
+        if let Some(storage) = cli.storage {
+            self.storage = storage;
+        }
+    }
+
+    /// Check that the configuration is usable
+    pub fn validate(&self) -> Result<()> {
+        if let Some(addr) = self.http {
+            // Validate privileged ports
+            if addr.port() < 1024 && addr.port() != 0 {
+                anyhow::bail!(
+                    "Port {} requires elevated privileges (root/sudo).\n\
+                     Use ports >= 1024 for unprivileged operation, e.g., --http {}:30437",
+                    addr.port(),
+                    addr.ip()
+                );
+            }
+
+            // Validate port 0 (OS-assigned ports break MCP client config)
+            if addr.port() == 0 {
+                anyhow::bail!(
+                    "Port 0 is not allowed (OS-assigned ports not supported).\n\
+                     Specify an explicit port, e.g., --http {}:30437",
+                    addr.ip()
+                );
+            }
+        }
+
+        match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => {
+                anyhow::ensure!(cert.is_file(), "TLS certificate not found: {}", cert.display());
+                anyhow::ensure!(key.is_file(), "TLS private key not found: {}", key.display());
+            }
+            (Some(_), None) => anyhow::bail!("tls_cert is set but tls_key is missing"),
+            (None, Some(_)) => anyhow::bail!("tls_key is set but tls_cert is missing"),
+            (None, None) => {}
+        }
+        if let Some(ref ca) = self.tls_client_ca {
+            anyhow::ensure!(ca.is_file(), "TLS client CA bundle not found: {}", ca.display());
+        }
+
+        anyhow::ensure!(self.shutdown_timeout_secs > 0, "shutdown_timeout_secs must be greater than 0");
+        anyhow::ensure!(self.channel_capacity > 0, "channel_capacity must be greater than 0");
+        anyhow::ensure!(self.drain_timeout_secs > 0, "drain_timeout_secs must be greater than 0");
+        anyhow::ensure!(self.listen_backlog > 0, "listen_backlog must be greater than 0");
+        anyhow::ensure!(self.memory_check_interval_secs > 0, "memory_check_interval_secs must be greater than 0");
+        anyhow::ensure!(self.memory_limit_mb != Some(0), "memory_limit_mb must be greater than 0 (omit it for no limit)");
+        if let Some(ref endpoint) = self.otlp_endpoint {
+            anyhow::ensure!(
+                endpoint.starts_with("http://") || endpoint.starts_with("https://"),
+                "otlp_endpoint must be an http:// or https:// URL, got {:?}",
+                endpoint
+            );
+        }
+        anyhow::ensure!(self.max_sessions != Some(0), "max_sessions must be greater than 0 (omit it for unlimited)");
+        for (module, level) in &self.log_filters {
+            anyhow::ensure!(
+                log::LevelFilter::from_str(level).is_ok(),
+                "log_filters: invalid level {:?} for module {}",
+                level,
+                module
+            );
+        }
+        anyhow::ensure!(
+            self.storage.is_available(),
+            "storage = \"sqlite\" requires kodegen_server_http to be built with the `sqlite` feature"
+        );
+
+        Ok(())
+    }
+
+    /// Get HTTP address, warning about wildcard binds
+    pub fn http_address(&self) -> Result<SocketAddr> {
+        let addr = self.http
+            .context("HTTP address is required - pass --http, set KODEGEN_HTTP or `http` in the config file")?;
+
+        // Warn about wildcard binding security implications
+        if addr.ip().is_unspecified() {
+            log::warn!(
+                "Binding to {} exposes server on all network interfaces.",
+                addr.ip()
+            );
+            log::warn!(
+                "For local-only access, use: --http 127.0.0.1:{}",
+                addr.port()
+            );
+        }
+
+        Ok(addr)
+    }
+
+    /// Get TLS configuration if both cert and key provided
+    pub fn tls_config(&self) -> Option<(PathBuf, PathBuf)> {
+        match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => Some((cert.clone(), key.clone())),
+            _ => None,
+        }
+    }
+
+    /// Get shutdown timeout duration
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_secs)
+    }
+
+    /// Session keep-alive (None = infinite)
+    pub fn session_keep_alive(&self) -> Option<Duration> {
+        match self.keep_alive_secs {
+            None | Some(0) => None,
+            Some(n) => Some(Duration::from_secs(n)),
+        }
+    }
+
+    /// Session keep-alive for embedded servers started with `.serve()` (None = infinite)
+    ///
+    /// Embedded servers have always expired idle sessions after an hour, so that
+    /// stays the default when `keep_alive_secs` is unset.
+    pub fn embedded_session_keep_alive(&self) -> Option<Duration> {
+        match self.keep_alive_secs {
+            None => Some(Duration::from_secs(DEFAULT_EMBEDDED_KEEP_ALIVE_SECS)),
+            Some(_) => self.session_keep_alive(),
+        }
+    }
+
+    /// SSE keep-alive interval (None = disabled)
+    pub fn sse_keep_alive(&self) -> Option<Duration> {
+        match self.sse_keep_alive_secs {
+            0 => None,
+            n => Some(Duration::from_secs(n)),
+        }
+    }
+
+    /// Session cap enforced on new sessions
+    pub fn session_limit(&self) -> SessionLimit {
+        SessionLimit {
+            max_sessions: self.max_sessions,
+            policy: self.session_limit_policy,
+        }
+    }
+
+    /// Idle time after which a connection's resources are cleaned up (None = never)
+    pub fn connection_idle_timeout(&self) -> Option<Duration> {
+        match self.connection_idle_timeout_secs {
+            0 => None,
+            n => Some(Duration::from_secs(n)),
+        }
+    }
+
+    /// Module log filters as parsed levels (invalid entries are rejected by `validate`)
+    pub fn log_filters(&self) -> Vec<(String, log::LevelFilter)> {
+        self.log_filters
+            .iter()
+            .filter_map(|(module, level)| {
+                log::LevelFilter::from_str(level).ok().map(|level| (module.clone(), level))
+            })
+            .collect()
+    }
+
+    /// Memory monitor sampling interval
+    pub fn memory_check_interval(&self) -> Duration {
+        Duration::from_secs(self.memory_check_interval_secs)
+    }
+
+    /// Hard RSS limit in bytes (None = no limit)
+    pub fn memory_limit(&self) -> Option<u64> {
+        self.memory_limit_mb.map(|mb| mb.saturating_mul(1024 * 1024))
+    }
+
+    /// Get in-flight request drain timeout
+    pub fn drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.drain_timeout_secs)
+    }
+    /// Audit log file for `category` (whether or not `audit_log` is enabled)
+    pub fn audit_log_file(&self, category: &str) -> PathBuf {
+        self.audit_log_path
+            .clone()
+            .unwrap_or_else(|| crate::audit::default_path(category))
+    }
+
+    /// How long hourly and daily usage buckets are kept
+    pub fn bucket_retention(&self) -> BucketRetention {
+        BucketRetention {
+            hours: self.stats_hourly_retention_hours,
+            days: self.stats_daily_retention_days,
+        }
+    }
+}
+
+/// Parse an environment variable, treating unset and empty as absent
+fn env_var<T>(name: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    match std::env::var(name) {
+        Ok(value) if value.trim().is_empty() => Ok(None),
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("Invalid value for {}: {:?} ({})", name, value, e)),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(e) => Err(anyhow::anyhow!("Invalid value for {}: {}", name, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::sync::Mutex;
+
+    /// Serialises tests that touch `KODEGEN_*` variables, which are process-wide
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("kodegen-config-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn cli(args: &[&str]) -> Cli {
+        Cli::try_parse_from(std::iter::once("kodegen").chain(args.iter().copied())).unwrap()
+    }
+
+    /// Run `f` with `vars` set, removing them afterwards
+    fn with_env<R>(vars: &[(&str, &str)], f: impl FnOnce() -> R) -> R {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        // SAFETY: every test that reads or writes these variables holds ENV_LOCK
+        unsafe {
+            for (name, value) in vars {
+                std::env::set_var(name, value);
+            }
+        }
+        let result = f();
+        unsafe {
+            for (name, _) in vars {
+                std::env::remove_var(name);
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn file_overrides_defaults_and_keeps_unset_fields() {
+        let path = temp_file("file.toml", "shutdown_timeout_secs = 5\nhttp = \"127.0.0.1:4000\"\n");
+        let config = with_env(&[], || {
+            ServerConfig::load(Some(&cli(&["--config", path.to_str().unwrap()]))).unwrap()
+        });
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(config.shutdown_timeout_secs, 5);
+        assert_eq!(config.http, Some("127.0.0.1:4000".parse().unwrap()));
+        assert_eq!(config.channel_capacity, DEFAULT_CHANNEL_CAPACITY);
+    }
+
+    #[test]
+    fn env_overrides_file() {
+        let path = temp_file("env.toml", "shutdown_timeout_secs = 5\nchannel_capacity = 8\n");
+        let config = with_env(
+            &[(CONFIG_FILE_ENV, path.to_str().unwrap()), ("KODEGEN_SHUTDOWN_TIMEOUT_SECS", "7")],
+            || ServerConfig::load(None).unwrap(),
+        );
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(config.shutdown_timeout_secs, 7);
+        assert_eq!(config.channel_capacity, 8);
+    }
+
+    #[test]
+    fn cli_overrides_env_and_file() {
+        let path = temp_file("cli.toml", "shutdown_timeout_secs = 5\nchannel_capacity = 8\ndrain_timeout_secs = 4\n");
+        let config = with_env(
+            &[("KODEGEN_SHUTDOWN_TIMEOUT_SECS", "7"), ("KODEGEN_CHANNEL_CAPACITY", "9")],
+            || {
+                ServerConfig::load(Some(&cli(&[
+                    "--config",
+                    path.to_str().unwrap(),
+                    "--shutdown-timeout-secs",
+                    "11",
+                    "--drain-timeout",
+                    "12",
+                ])))
+                .unwrap()
+            },
+        );
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(config.shutdown_timeout_secs, 11, "CLI beats env");
+        assert_eq!(config.drain_timeout_secs, 12, "CLI beats file");
+        assert_eq!(config.channel_capacity, 9, "env still beats file when no flag is given");
+    }
+
+    #[test]
+    fn cli_config_path_overrides_env_config_path() {
+        let from_env = temp_file("path-env.toml", "shutdown_timeout_secs = 5\n");
+        let from_cli = temp_file("path-cli.toml", "shutdown_timeout_secs = 6\n");
+        let config = with_env(&[(CONFIG_FILE_ENV, from_env.to_str().unwrap())], || {
+            ServerConfig::load(Some(&cli(&["--config", from_cli.to_str().unwrap()]))).unwrap()
+        });
+        std::fs::remove_file(&from_env).ok();
+        std::fs::remove_file(&from_cli).ok();
+
+        assert_eq!(config.shutdown_timeout_secs, 6);
+    }
+
+    #[test]
+    fn map_fields_merge_across_layers() {
+        let path = temp_file("filters.toml", "[log_filters]\n\"a\" = \"warn\"\n\"b\" = \"warn\"\n");
+        let config = with_env(&[("KODEGEN_LOG_FILTERS", "b=info,c=info")], || {
+            ServerConfig::load(Some(&cli(&["--config", path.to_str().unwrap(), "--log-filter", "c=debug"]))).unwrap()
+        });
+        std::fs::remove_file(&path).ok();
+
+        let filters: Vec<_> = config.log_filters.iter().map(|(m, l)| (m.as_str(), l.as_str())).collect();
+        assert_eq!(filters, [("a", "warn"), ("b", "info"), ("c", "debug")]);
+    }
+
+    #[test]
+    fn invalid_env_value_is_rejected() {
+        let err = with_env(&[("KODEGEN_CHANNEL_CAPACITY", "lots")], || ServerConfig::load(None).unwrap_err());
+        assert!(err.to_string().contains("KODEGEN_CHANNEL_CAPACITY"), "{err:#}");
+    }
+
+    #[test]
+    fn empty_env_value_is_ignored() {
+        let config = with_env(&[("KODEGEN_SHUTDOWN_TIMEOUT_SECS", "  ")], || ServerConfig::load(None).unwrap());
+        assert_eq!(config.shutdown_timeout_secs, DEFAULT_SHUTDOWN_TIMEOUT_SECS);
+    }
+
+    #[test]
+    fn missing_config_file_is_an_error() {
+        let path = std::env::temp_dir().join(format!("kodegen-config-{}-missing.toml", std::process::id()));
+        let err = ServerConfig::from_file(&path).unwrap_err();
+        assert!(format!("{err:#}").contains("Failed to read config file"), "{err:#}");
+    }
+
+    #[test]
+    fn malformed_config_file_is_an_error() {
+        let path = temp_file("malformed.toml", "shutdown_timeout_secs = \"soon\"\n");
+        let err = ServerConfig::from_file(&path).unwrap_err();
+        std::fs::remove_file(&path).ok();
+        assert!(format!("{err:#}").contains("Failed to parse config file"), "{err:#}");
+
+        let path = temp_file("unknown.toml", "no_such_field = 1\n");
+        let err = ServerConfig::from_file(&path).unwrap_err();
+        std::fs::remove_file(&path).ok();
+        assert!(format!("{err:#}").contains("no_such_field"), "{err:#}");
+    }
+
+    #[test]
+    fn validate_rejects_reserved_ports() {
+        for addr in ["127.0.0.1:80", "127.0.0.1:0"] {
+            let config = ServerConfig { http: Some(addr.parse().unwrap()), ..ServerConfig::default() };
+            assert!(config.validate().is_err(), "{addr} should be rejected");
+        }
+        let config = ServerConfig { http: Some("127.0.0.1:30437".parse().unwrap()), ..ServerConfig::default() };
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn validate_requires_tls_cert_and_key_together() {
+        let cert = temp_file("cert.pem", "");
+        let key = temp_file("key.pem", "");
+
+        let config = ServerConfig { tls_cert: Some(cert.clone()), ..ServerConfig::default() };
+        assert!(config.validate().unwrap_err().to_string().contains("tls_key is missing"));
+
+        let config = ServerConfig { tls_key: Some(key.clone()), ..ServerConfig::default() };
+        assert!(config.validate().unwrap_err().to_string().contains("tls_cert is missing"));
+
+        let config = ServerConfig { tls_cert: Some(cert.clone()), tls_key: Some(key.clone()), ..ServerConfig::default() };
+        config.validate().unwrap();
+
+        let config = ServerConfig {
+            tls_cert: Some(cert.clone()),
+            tls_key: Some(cert.with_extension("absent")),
+            ..ServerConfig::default()
+        };
+        assert!(config.validate().unwrap_err().to_string().contains("TLS private key not found"));
+
+        std::fs::remove_file(&cert).ok();
+        std::fs::remove_file(&key).ok();
+    }
+
+    #[test]
+    fn validate_rejects_zero_and_malformed_values() {
+        let invalid = [
+            ServerConfig { shutdown_timeout_secs: 0, ..ServerConfig::default() },
+            ServerConfig { channel_capacity: 0, ..ServerConfig::default() },
+            ServerConfig { max_sessions: Some(0), ..ServerConfig::default() },
+            ServerConfig { memory_limit_mb: Some(0), ..ServerConfig::default() },
+            ServerConfig { otlp_endpoint: Some("localhost:4318".to_string()), ..ServerConfig::default() },
+            ServerConfig {
+                log_filters: BTreeMap::from([("tantivy".to_string(), "loud".to_string())]),
+                ..ServerConfig::default()
+            },
+        ];
+        for config in invalid {
+            assert!(config.validate().is_err(), "{config:?} should be rejected");
+        }
+        ServerConfig::default().validate().unwrap();
+    }
+}
@@ -0,0 +1,218 @@
+use axum::http::request::Parts;
+use rmcp::transport::common::http_header::HEADER_SESSION_ID;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Number of hex characters kept from SHA-256 digests in derived connection ids
+const DERIVED_ID_HEX_LEN: usize = 16;
+
+/// Where a tool call's connection id came from
+///
+/// Listed in order of precedence: an explicit `X-Kodegen-Connection-Id` header wins,
+/// then the client certificate, then the bearer token, then the MCP session id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionIdSource {
+    /// `X-Kodegen-Connection-Id` header (sent by kodegend)
+    Header,
+    /// SHA-256 fingerprint of the TLS client certificate (requires `tls_client_ca`)
+    ClientCertificate,
+    /// SHA-256 hash of the `Authorization: Bearer` token
+    Token,
+    /// MCP session id (`Mcp-Session-Id` header)
+    Session,
+}
+
+impl ConnectionIdSource {
+    /// Name as serialized in `/mcp/stats` and `/mcp/history`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Header => "header",
+            Self::ClientCertificate => "client_certificate",
+            Self::Token => "token",
+            Self::Session => "session",
+        }
+    }
+}
+
+/// Connection id for a tool call together with its source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionIdentity {
+    pub id: String,
+    pub source: ConnectionIdSource,
+}
+
+impl ConnectionIdentity {
+    /// Resolve the connection identity of a tool call
+    ///
+    /// `header_id` is the `X-Kodegen-Connection-Id` value forwarded in request meta;
+    /// `parts` is the underlying HTTP request, used for the header itself and for the
+    /// derived fallbacks. Derived ids are prefixed with their source so they never
+    /// collide with ids chosen by kodegend.
+    pub fn resolve(header_id: Option<String>, parts: Option<&Parts>) -> Option<Self> {
+        let header_id = header_id.or_else(|| {
+            parts
+                .and_then(|parts| parts.headers.get(kodegen_config::X_KODEGEN_CONNECTION_ID))
+                .and_then(|v| v.to_str().ok())
+                .map(|s| s.to_string())
+        });
+        if let Some(id) = header_id {
+            return Some(Self {
+                id,
+                source: ConnectionIdSource::Header,
+            });
+        }
+
+        let parts = parts?;
+
+        if let Some(ClientCertificateFingerprint(fingerprint)) = parts.extensions.get() {
+            return Some(Self {
+                id: format!("cert-{}", fingerprint),
+                source: ConnectionIdSource::ClientCertificate,
+            });
+        }
+
+        let token = parts
+            .headers
+            .get(axum::http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|token| !token.is_empty());
+        if let Some(token) = token {
+            return Some(Self {
+                id: format!("token-{}", short_digest(token.as_bytes())),
+                source: ConnectionIdSource::Token,
+            });
+        }
+
+        parts
+            .headers
+            .get(HEADER_SESSION_ID)
+            .and_then(|v| v.to_str().ok())
+            .map(|session_id| Self {
+                id: format!("session-{}", session_id),
+                source: ConnectionIdSource::Session,
+            })
+    }
+}
+
+/// Fingerprint of the TLS client certificate, attached to requests as an extension
+///
+/// Inserted by the TLS accept loop when the client presented a certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCertificateFingerprint(pub String);
+
+impl ClientCertificateFingerprint {
+    /// Fingerprint a DER-encoded certificate
+    pub fn from_der(der: &[u8]) -> Self {
+        Self(short_digest(der))
+    }
+}
+
+/// Truncated hex SHA-256 digest
+fn short_digest(bytes: &[u8]) -> String {
+    let mut hex = hex::encode(Sha256::digest(bytes));
+    hex.truncate(DERIVED_ID_HEX_LEN);
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+
+    const CERT_FINGERPRINT: &str = "00112233aabbccdd";
+
+    /// Request parts carrying each of the given identity sources
+    fn parts(header: bool, cert: bool, token: Option<&str>, session: bool) -> Parts {
+        let mut builder = Request::builder();
+        if header {
+            builder = builder.header(kodegen_config::X_KODEGEN_CONNECTION_ID, "from-header");
+        }
+        if let Some(token) = token {
+            builder = builder.header(axum::http::header::AUTHORIZATION, token);
+        }
+        if session {
+            builder = builder.header(HEADER_SESSION_ID, "abc");
+        }
+        let (mut parts, ()) = builder.body(()).unwrap().into_parts();
+        if cert {
+            parts.extensions.insert(ClientCertificateFingerprint(CERT_FINGERPRINT.to_string()));
+        }
+        parts
+    }
+
+    fn resolve(header_id: Option<&str>, parts: Option<&Parts>) -> Option<(String, ConnectionIdSource)> {
+        ConnectionIdentity::resolve(header_id.map(str::to_string), parts).map(|identity| (identity.id, identity.source))
+    }
+
+    #[test]
+    fn meta_header_id_wins_over_everything() {
+        let all = parts(true, true, Some("Bearer secret"), true);
+        assert_eq!(
+            resolve(Some("from-meta"), Some(&all)),
+            Some(("from-meta".to_string(), ConnectionIdSource::Header))
+        );
+        assert_eq!(
+            resolve(Some("from-meta"), None),
+            Some(("from-meta".to_string(), ConnectionIdSource::Header))
+        );
+    }
+
+    #[test]
+    fn http_header_wins_over_certificate() {
+        let parts = parts(true, true, Some("Bearer secret"), true);
+        assert_eq!(resolve(None, Some(&parts)), Some(("from-header".to_string(), ConnectionIdSource::Header)));
+    }
+
+    #[test]
+    fn certificate_wins_over_token() {
+        let parts = parts(false, true, Some("Bearer secret"), true);
+        assert_eq!(
+            resolve(None, Some(&parts)),
+            Some((format!("cert-{}", CERT_FINGERPRINT), ConnectionIdSource::ClientCertificate))
+        );
+    }
+
+    #[test]
+    fn token_wins_over_session() {
+        let parts = parts(false, false, Some("Bearer secret"), true);
+        let (id, source) = resolve(None, Some(&parts)).unwrap();
+        assert_eq!(source, ConnectionIdSource::Token);
+        assert_eq!(id, format!("token-{}", short_digest(b"secret")));
+        assert!(!id.contains("secret"));
+    }
+
+    #[test]
+    fn session_is_the_last_resort() {
+        let parts = parts(false, false, None, true);
+        assert_eq!(resolve(None, Some(&parts)), Some(("session-abc".to_string(), ConnectionIdSource::Session)));
+    }
+
+    #[test]
+    fn unusable_tokens_fall_through_to_session() {
+        for token in ["Basic dXNlcjpwYXNz", "Bearer ", "Bearer    "] {
+            let parts = parts(false, false, Some(token), true);
+            assert_eq!(
+                resolve(None, Some(&parts)).map(|(_, source)| source),
+                Some(ConnectionIdSource::Session),
+                "{token:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn no_sources_means_no_identity() {
+        assert_eq!(resolve(None, None), None);
+        assert_eq!(resolve(None, Some(&parts(false, false, None, false))), None);
+    }
+
+    #[test]
+    fn fingerprints_are_stable_and_truncated() {
+        let fingerprint = ClientCertificateFingerprint::from_der(b"certificate");
+        assert_eq!(fingerprint, ClientCertificateFingerprint::from_der(b"certificate"));
+        assert_ne!(fingerprint, ClientCertificateFingerprint::from_der(b"other"));
+        assert_eq!(fingerprint.0.len(), DERIVED_ID_HEX_LEN);
+    }
+}
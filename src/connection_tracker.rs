@@ -0,0 +1,82 @@
+use crate::connection_identity::{ConnectionIdSource, ConnectionIdentity};
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// What the server knows about one connection id
+struct ConnectionEntry {
+    source: ConnectionIdSource,
+    last_active: Instant,
+    in_flight_calls: usize,
+}
+
+/// Activity of each connection id seen by this server
+///
+/// Reports how a connection was identified (for `/mcp/stats` and `/mcp/history`)
+/// and finds connections idle long enough to have their resources cleaned up.
+#[derive(Default)]
+pub struct ConnectionTracker {
+    connections: DashMap<String, ConnectionEntry>,
+}
+
+impl ConnectionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a tool call on a connection; the connection stays active until the guard is dropped
+    pub fn begin_call(self: &Arc<Self>, identity: &ConnectionIdentity) -> ConnectionCallGuard {
+        let mut entry = self
+            .connections
+            .entry(identity.id.clone())
+            .or_insert_with(|| ConnectionEntry {
+                source: identity.source,
+                last_active: Instant::now(),
+                in_flight_calls: 0,
+            });
+        entry.source = identity.source;
+        entry.last_active = Instant::now();
+        entry.in_flight_calls += 1;
+
+        ConnectionCallGuard {
+            tracker: self.clone(),
+            connection_id: identity.id.clone(),
+        }
+    }
+
+    /// Get the source of a connection id
+    pub fn source(&self, connection_id: &str) -> Option<ConnectionIdSource> {
+        self.connections.get(connection_id).map(|entry| entry.source)
+    }
+
+    /// Connections with no call in flight and no activity for at least `idle_timeout`
+    pub fn idle_connections(&self, idle_timeout: Duration) -> Vec<String> {
+        self.connections
+            .iter()
+            .filter(|entry| entry.in_flight_calls == 0 && entry.last_active.elapsed() >= idle_timeout)
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// Forget a connection id (called on connection cleanup)
+    pub fn remove(&self, connection_id: &str) {
+        self.connections.remove(connection_id);
+    }
+}
+
+/// RAII guard for an in-flight tool call on a connection
+///
+/// Marks the connection active again when the call finishes, even if it panics.
+pub struct ConnectionCallGuard {
+    tracker: Arc<ConnectionTracker>,
+    connection_id: String,
+}
+
+impl Drop for ConnectionCallGuard {
+    fn drop(&mut self) {
+        if let Some(mut entry) = self.tracker.connections.get_mut(&self.connection_id) {
+            entry.in_flight_calls = entry.in_flight_calls.saturating_sub(1);
+            entry.last_active = Instant::now();
+        }
+    }
+}
@@ -0,0 +1,369 @@
+use crate::connection_identity::ConnectionIdSource;
+use crate::latency;
+use crate::tool_history::HistoryRecord;
+use crate::usage_tracker::UsageStats;
+use chrono::{DateTime, Utc};
+use rmcp::model::Tool;
+use std::fmt::Write;
+
+/// Most recent history records shown per connection
+pub const DASHBOARD_HISTORY_LIMIT: usize = 20;
+
+/// Inline stylesheet - the page must not load anything from the network
+const STYLE: &str = "
+body { font: 14px/1.4 system-ui, sans-serif; margin: 1.5em; color: #222; background: #fafafa; }
+h1 { font-size: 1.5em; margin-bottom: 0.2em; }
+h2 { font-size: 1.2em; border-bottom: 1px solid #ccc; margin-top: 1.5em; }
+h3 { font-size: 1em; margin-bottom: 0.3em; }
+table { border-collapse: collapse; margin: 0.5em 0; background: #fff; }
+th, td { border: 1px solid #ddd; padding: 3px 8px; text-align: left; vertical-align: top; }
+th { background: #f0f0f0; }
+td.num { text-align: right; font-variant-numeric: tabular-nums; }
+pre { margin: 0.3em 0; padding: 0.5em; background: #f4f4f4; overflow-x: auto; max-height: 30em; }
+.muted { color: #777; }
+.slow { color: #b36b00; font-weight: bold; }
+.HEALTHY { color: #1a7f37; } .DEGRADED { color: #b36b00; } .UNHEALTHY { color: #cf222e; }
+";
+
+/// Per-connection section of the dashboard
+pub struct ConnectionSection {
+    pub connection_id: String,
+    pub source: Option<ConnectionIdSource>,
+    pub stats: UsageStats,
+    /// Newest first, at most `DASHBOARD_HISTORY_LIMIT`
+    pub recent: Vec<HistoryRecord>,
+}
+
+/// Everything shown on `/mcp/dashboard`, rendered to a single self-contained HTML page
+pub struct Dashboard {
+    pub category: String,
+    pub instance_id: String,
+    pub port: u16,
+    pub generated_at: DateTime<Utc>,
+    /// Reload the page every N seconds (from `?refresh=N`)
+    pub refresh_secs: Option<u64>,
+    /// `/mcp/health` response
+    pub health: serde_json::Value,
+    pub connections: Vec<ConnectionSection>,
+    pub tools: Vec<Tool>,
+}
+
+impl Dashboard {
+    pub fn render(&self) -> String {
+        let mut html = String::with_capacity(16 * 1024);
+        // Writing to a String cannot fail
+        let _ = self.write_page(&mut html);
+        html
+    }
+
+    fn write_page(&self, out: &mut String) -> std::fmt::Result {
+        writeln!(out, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">")?;
+        if let Some(secs) = self.refresh_secs {
+            writeln!(out, "<meta http-equiv=\"refresh\" content=\"{}\">", secs)?;
+        }
+        writeln!(out, "<title>{} - kodegen dashboard</title>", escape(&self.category))?;
+        writeln!(out, "<style>{}</style>\n</head>\n<body>", STYLE)?;
+
+        let status = self.health.get("status").and_then(|v| v.as_str()).unwrap_or("UNKNOWN");
+        writeln!(
+            out,
+            "<h1>{} <span class=\"{}\">{}</span></h1>",
+            escape(&self.category),
+            escape(status),
+            escape(status)
+        )?;
+        writeln!(
+            out,
+            "<p class=\"muted\">instance {} &middot; port {} &middot; generated {} &middot; \
+             <a href=\"?refresh=10\">auto-refresh</a> &middot; <a href=\"?\">static</a></p>",
+            escape(&self.instance_id),
+            self.port,
+            self.generated_at.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        )?;
+
+        writeln!(out, "<h2>Health</h2>")?;
+        write_json_table(out, &self.health)?;
+
+        writeln!(out, "<h2>Connections ({})</h2>", self.connections.len())?;
+        if self.connections.is_empty() {
+            writeln!(out, "<p class=\"muted\">No connections with tracked calls.</p>")?;
+        }
+        for connection in &self.connections {
+            write_connection(out, connection)?;
+        }
+
+        writeln!(out, "<h2>Tools ({})</h2>", self.tools.len())?;
+        for tool in &self.tools {
+            write_tool(out, tool)?;
+        }
+
+        writeln!(out, "</body>\n</html>")
+    }
+}
+
+fn write_connection(out: &mut String, connection: &ConnectionSection) -> std::fmt::Result {
+    let stats = &connection.stats;
+    writeln!(
+        out,
+        "<h3>{} <span class=\"muted\">({})</span></h3>",
+        escape(&connection.connection_id),
+        connection.source.map_or("unknown", ConnectionIdSource::as_str)
+    )?;
+    writeln!(
+        out,
+        "<p>{} calls &middot; {} ok &middot; {} failed &middot; {} sessions &middot; last used {}</p>",
+        stats.total_tool_calls,
+        stats.successful_calls,
+        stats.failed_calls,
+        stats.total_sessions,
+        format_unix(stats.last_used)
+    )?;
+
+    let latency = latency::summarize(&stats.tool_latency);
+    let mut tools: Vec<_> = stats.tool_counts.iter().collect();
+    tools.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    writeln!(
+        out,
+        "<table>\n<tr><th>Tool</th><th>Calls</th><th>p50 ms</th><th>p95 ms</th><th>p99 ms</th><th>max ms</th></tr>"
+    )?;
+    for (tool, count) in tools {
+        write!(out, "<tr><td>{}</td><td class=\"num\">{}</td>", escape(tool), count)?;
+        match latency.get(tool) {
+            Some(summary) => writeln!(
+                out,
+                "<td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td></tr>",
+                summary.p50_ms, summary.p95_ms, summary.p99_ms, summary.max_ms
+            )?,
+            None => writeln!(out, "<td colspan=\"4\" class=\"muted\">-</td></tr>")?,
+        }
+    }
+    writeln!(out, "</table>")?;
+
+    if connection.recent.is_empty() {
+        return Ok(());
+    }
+    writeln!(
+        out,
+        "<details><summary>Recent calls ({})</summary>\n<table>\n\
+         <tr><th>Time</th><th>Tool</th><th>Duration ms</th><th>Request id</th><th>Arguments</th></tr>",
+        connection.recent.len()
+    )?;
+    for entry in &connection.recent {
+        let record = &entry.record;
+        writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td><td class=\"num{}\">{}</td><td>{}</td><td><code>{}</code></td></tr>",
+            escape(&record.timestamp),
+            escape(&record.tool_name),
+            if entry.annotations.slow { " slow" } else { "" },
+            record.duration_ms.map_or_else(|| "-".to_string(), |ms| ms.to_string()),
+            escape(entry.annotations.request_id.as_deref().unwrap_or("-")),
+            escape(&truncate(&record.args_json, 200))
+        )?;
+    }
+    writeln!(out, "</table>\n</details>")
+}
+
+fn write_tool(out: &mut String, tool: &Tool) -> std::fmt::Result {
+    writeln!(out, "<details><summary><b>{}</b>", escape(&tool.name))?;
+    if let Some(ref description) = tool.description {
+        let first_line = description.lines().next().unwrap_or_default();
+        write!(out, " &mdash; <span class=\"muted\">{}</span>", escape(first_line))?;
+    }
+    writeln!(out, "</summary>")?;
+    if let Some(ref description) = tool.description {
+        writeln!(out, "<pre>{}</pre>", escape(description))?;
+    }
+    let input_schema = serde_json::to_string_pretty(&*tool.input_schema).unwrap_or_default();
+    writeln!(out, "<p>Input schema</p>\n<pre>{}</pre>", escape(&input_schema))?;
+    if let Some(ref output_schema) = tool.output_schema {
+        let output_schema = serde_json::to_string_pretty(&**output_schema).unwrap_or_default();
+        writeln!(out, "<p>Output schema</p>\n<pre>{}</pre>", escape(&output_schema))?;
+    }
+    writeln!(out, "</details>")
+}
+
+/// Render a JSON object as a key/value table, nesting objects as sub-tables
+fn write_json_table(out: &mut String, value: &serde_json::Value) -> std::fmt::Result {
+    let serde_json::Value::Object(map) = value else {
+        return write!(out, "{}", escape(&value.to_string()));
+    };
+    writeln!(out, "<table>")?;
+    for (key, value) in map {
+        write!(out, "<tr><th>{}</th><td>", escape(key))?;
+        match value {
+            serde_json::Value::Object(_) => write_json_table(out, value)?,
+            serde_json::Value::Array(items) => {
+                let json = serde_json::to_string_pretty(items).unwrap_or_default();
+                write!(
+                    out,
+                    "<details><summary>{} items</summary><pre>{}</pre></details>",
+                    items.len(),
+                    escape(&json)
+                )?;
+            }
+            serde_json::Value::String(s) => write!(out, "{}", escape(s))?,
+            serde_json::Value::Null => write!(out, "<span class=\"muted\">-</span>")?,
+            other => write!(out, "{}", other)?,
+        }
+        writeln!(out, "</td></tr>")?;
+    }
+    writeln!(out, "</table>")
+}
+
+fn format_unix(timestamp: i64) -> String {
+    DateTime::<Utc>::from_timestamp(timestamp, 0)
+        .map_or_else(|| timestamp.to_string(), |t| t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+}
+
+fn truncate(s: &str, max_chars: usize) -> String {
+    match s.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}...", &s[..end]),
+        None => s.to_string(),
+    }
+}
+
+/// Escape text for HTML element content and attribute values
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool_history::CallAnnotations;
+    use kodegen_mcp_schema::tool::tool_history::ToolCallRecord;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn object(value: serde_json::Value) -> Arc<serde_json::Map<String, serde_json::Value>> {
+        let serde_json::Value::Object(map) = value else { unreachable!() };
+        Arc::new(map)
+    }
+
+    fn history(tool_name: &str, duration_ms: u64, slow: bool, args_json: &str) -> HistoryRecord {
+        HistoryRecord {
+            record: ToolCallRecord {
+                timestamp: "2026-01-01T00:00:00Z".to_string(),
+                tool_name: tool_name.to_string(),
+                args_json: args_json.to_string(),
+                output_json: "{}".to_string(),
+                duration_ms: Some(duration_ms),
+            },
+            annotations: CallAnnotations {
+                slow,
+                request_id: Some("req-1".to_string()),
+            },
+        }
+    }
+
+    fn dashboard() -> Dashboard {
+        let mut stats = UsageStats {
+            total_tool_calls: 3,
+            successful_calls: 2,
+            failed_calls: 1,
+            ..UsageStats::default()
+        };
+        stats.tool_counts.insert("read".to_string(), 2);
+        stats.tool_counts.insert("write".to_string(), 1);
+        stats.tool_latency.entry("read".to_string()).or_default().record(40);
+
+        let mut tool = Tool::new("read", "Read a file\nLonger explanation", object(json!({ "type": "object" })));
+        tool.output_schema = Some(object(json!({ "type": "string" })));
+
+        Dashboard {
+            category: "filesystem".to_string(),
+            instance_id: "instance-1".to_string(),
+            port: 30437,
+            generated_at: Utc::now(),
+            refresh_secs: None,
+            health: json!({ "status": "HEALTHY", "memory": { "rss_bytes": 1024 }, "samples": [1, 2] }),
+            connections: vec![ConnectionSection {
+                connection_id: "conn-1".to_string(),
+                source: Some(ConnectionIdSource::Header),
+                stats,
+                recent: vec![history("read", 40, false, "{}"), history("write", 9000, true, &"x".repeat(300))],
+            }],
+            tools: vec![tool],
+        }
+    }
+
+    #[test]
+    fn renders_health_connections_and_tools() {
+        let html = dashboard().render();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.trim_end().ends_with("</html>"));
+        assert!(html.contains("<h1>filesystem <span class=\"HEALTHY\">HEALTHY</span></h1>"));
+        assert!(html.contains("<th>rss_bytes</th><td>1024</td>"), "nested health objects become sub-tables");
+        assert!(html.contains("<summary>2 items</summary>"));
+        assert!(html.contains("<h3>conn-1 <span class=\"muted\">(header)</span></h3>"));
+        assert!(html.contains("3 calls &middot; 2 ok &middot; 1 failed"));
+        assert!(html.contains("<h2>Tools (1)</h2>"));
+        assert!(html.contains("<p>Output schema</p>"));
+        assert!(html.contains("<span class=\"muted\">Read a file</span>"), "summary shows the first description line");
+    }
+
+    #[test]
+    fn tools_are_listed_by_call_count_with_latency_when_known() {
+        let html = dashboard().render();
+
+        let read = html.find("<tr><td>read</td>").unwrap();
+        let write = html.find("<tr><td>write</td>").unwrap();
+        assert!(read < write);
+        assert!(html.contains("<tr><td>write</td><td class=\"num\">1</td><td colspan=\"4\" class=\"muted\">-</td></tr>"));
+    }
+
+    #[test]
+    fn recent_calls_flag_slow_calls_and_truncate_arguments() {
+        let html = dashboard().render();
+
+        assert!(html.contains("<summary>Recent calls (2)</summary>"));
+        assert!(html.contains("<td class=\"num slow\">9000</td>"));
+        assert!(html.contains("<td class=\"num\">40</td><td>req-1</td>"));
+        assert!(html.contains(&format!("<code>{}...</code>", "x".repeat(200))));
+        assert!(!html.contains(&"x".repeat(201)));
+    }
+
+    #[test]
+    fn refresh_is_opt_in() {
+        let html = dashboard().render();
+        assert!(!html.contains("http-equiv=\"refresh\""));
+
+        let html = Dashboard { refresh_secs: Some(10), ..dashboard() }.render();
+        assert!(html.contains("<meta http-equiv=\"refresh\" content=\"10\">"));
+    }
+
+    #[test]
+    fn untrusted_text_is_escaped() {
+        let mut dashboard = dashboard();
+        dashboard.connections[0].connection_id = "<script>alert('x')</script>".to_string();
+        dashboard.connections[0].recent = vec![history("read", 1, false, r#"{"path":"a&b"}"#)];
+
+        let html = dashboard.render();
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;"));
+        assert!(html.contains("{&quot;path&quot;:&quot;a&amp;b&quot;}"));
+    }
+
+    #[test]
+    fn page_is_self_contained() {
+        let html = Dashboard { connections: Vec::new(), ..dashboard() }.render();
+
+        assert!(html.contains("No connections with tracked calls."));
+        for external in ["<script", "<link", "src=", "http://", "https://"] {
+            assert!(!html.contains(external), "page references {external}");
+        }
+    }
+}
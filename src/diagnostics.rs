@@ -0,0 +1,126 @@
+use serde::Serialize;
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+/// Resource usage of this process, reported by `/mcp/health`
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ProcessStats {
+    /// CPU usage since the previous sample, in percent of one core (can exceed 100)
+    pub cpu_usage_percent: f32,
+    pub rss_bytes: u64,
+    pub virtual_memory_bytes: u64,
+    /// Open file descriptors (None where the platform does not report them)
+    pub open_fds: Option<usize>,
+    /// OS threads (Linux only)
+    pub threads: Option<usize>,
+}
+
+/// Tokio runtime counters, reported by `/mcp/health`
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct RuntimeStats {
+    pub workers: usize,
+    pub alive_tasks: usize,
+}
+
+/// Samples this process's resource usage
+///
+/// Keeps one `System` between samples so CPU usage is measured over the time
+/// since the previous probe, and refreshes only this process, which keeps a
+/// sample cheap enough to take on every health check.
+pub struct ProcessSampler {
+    pid: Pid,
+    system: parking_lot::Mutex<System>,
+}
+
+impl ProcessSampler {
+    pub fn new() -> Self {
+        let sampler = Self {
+            pid: Pid::from_u32(std::process::id()),
+            system: parking_lot::Mutex::new(System::new()),
+        };
+        // Prime the CPU counters so the first probe has a baseline
+        sampler.sample();
+        sampler
+    }
+
+    /// Current resource usage (None if the process could not be read)
+    pub fn sample(&self) -> Option<ProcessStats> {
+        let mut system = self.system.lock();
+        system.refresh_processes_specifics(
+            ProcessesToUpdate::Some(&[self.pid]),
+            true,
+            ProcessRefreshKind::nothing().with_cpu().with_memory().with_tasks(),
+        );
+
+        let process = system.process(self.pid)?;
+        Some(ProcessStats {
+            cpu_usage_percent: process.cpu_usage(),
+            rss_bytes: process.memory(),
+            virtual_memory_bytes: process.virtual_memory(),
+            open_fds: process.open_files(),
+            // sysinfo lists every thread except the main one
+            threads: process.tasks().map(|tasks| tasks.len() + 1),
+        })
+    }
+}
+
+impl Default for ProcessSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counters of the current tokio runtime (None outside a runtime)
+pub fn runtime_stats() -> Option<RuntimeStats> {
+    let metrics = tokio::runtime::Handle::try_current().ok()?.metrics();
+    Some(RuntimeStats {
+        workers: metrics.num_workers(),
+        alive_tasks: metrics.num_alive_tasks(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_this_process() {
+        let stats = ProcessSampler::new().sample().unwrap();
+        assert!(stats.rss_bytes > 0);
+        assert!(stats.virtual_memory_bytes >= stats.rss_bytes);
+        assert!(stats.cpu_usage_percent >= 0.0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn counts_threads_and_open_files_on_linux() {
+        let sampler = ProcessSampler::new();
+        let (stop, stopped) = std::sync::mpsc::channel::<()>();
+        let thread = std::thread::spawn(move || stopped.recv().ok());
+
+        let stats = sampler.sample().unwrap();
+        // The test thread and the one just spawned, at least
+        assert!(stats.threads.is_some_and(|threads| threads >= 2), "{:?}", stats.threads);
+        assert!(stats.open_fds.is_some_and(|fds| fds > 0), "{:?}", stats.open_fds);
+
+        stop.send(()).unwrap();
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn runtime_stats_need_a_runtime() {
+        assert!(runtime_stats().is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn runtime_stats_count_workers_and_tasks() {
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(released);
+
+        let stats = runtime_stats().unwrap();
+        assert_eq!(stats.workers, 2);
+        assert!(stats.alive_tasks >= 1);
+
+        release.send(()).unwrap();
+        task.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,299 @@
+use axum::response::sse::Event;
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use rmcp::model::CallToolResult;
+use rmcp::ErrorData as McpError;
+use serde::Serialize;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+/// Events buffered per subscriber before the oldest are dropped
+const EVENT_BUFFER: usize = 1024;
+
+/// How a tool call ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallStatus {
+    Success,
+    /// The tool ran but returned a result with `is_error: true`
+    ToolError,
+    /// The call failed with a JSON-RPC error
+    Error,
+}
+
+impl CallStatus {
+    pub fn from_result(result: &Result<CallToolResult, McpError>) -> Self {
+        match result {
+            Ok(call_result) if call_result.is_error == Some(true) => Self::ToolError,
+            Ok(_) => Self::Success,
+            Err(_) => Self::Error,
+        }
+    }
+}
+
+/// Tool activity pushed to `/mcp/events` subscribers
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolEvent {
+    CallStarted {
+        timestamp: DateTime<Utc>,
+        tool_name: String,
+        connection_id: Option<String>,
+        request_id: String,
+    },
+    CallFinished {
+        timestamp: DateTime<Utc>,
+        tool_name: String,
+        connection_id: Option<String>,
+        request_id: String,
+        duration_ms: u64,
+        status: CallStatus,
+        slow: bool,
+    },
+}
+
+impl ToolEvent {
+    /// SSE event name (`call_started` or `call_finished`)
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::CallStarted { .. } => "call_started",
+            Self::CallFinished { .. } => "call_finished",
+        }
+    }
+
+    pub fn tool_name(&self) -> &str {
+        match self {
+            Self::CallStarted { tool_name, .. } | Self::CallFinished { tool_name, .. } => tool_name,
+        }
+    }
+
+    pub fn connection_id(&self) -> Option<&str> {
+        match self {
+            Self::CallStarted { connection_id, .. } | Self::CallFinished { connection_id, .. } => {
+                connection_id.as_deref()
+            }
+        }
+    }
+}
+
+/// Subscriber filter from the `/mcp/events` query string
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    pub connection_id: Option<String>,
+    pub tool_name: Option<String>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &ToolEvent) -> bool {
+        self.connection_id
+            .as_deref()
+            .is_none_or(|id| event.connection_id() == Some(id))
+            && self.tool_name.as_deref().is_none_or(|name| event.tool_name() == name)
+    }
+}
+
+/// Fan-out of tool events to live subscribers
+///
+/// Publishing never waits: each subscriber has a bounded buffer, and one that
+/// falls behind loses its oldest events (and is told how many) instead of
+/// slowing down `call_tool`.
+pub struct EventBus {
+    sender: broadcast::Sender<Arc<ToolEvent>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER);
+        Self { sender }
+    }
+
+    /// Send an event to every subscriber (no-op without subscribers)
+    pub fn publish(&self, event: impl FnOnce() -> ToolEvent) {
+        if self.sender.receiver_count() > 0 {
+            let _ = self.sender.send(Arc::new(event()));
+        }
+    }
+
+    /// SSE stream of events matching `filter`, ending when `ct` is cancelled
+    pub fn sse_stream(
+        &self,
+        filter: EventFilter,
+        ct: CancellationToken,
+    ) -> impl Stream<Item = Result<Event, Infallible>> + Send + 'static {
+        let receiver = self.sender.subscribe();
+
+        futures::stream::unfold(receiver, move |mut receiver| {
+            let filter = filter.clone();
+            async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(event) if filter.matches(&event) => {
+                            let sse = Event::default()
+                                .event(event.name())
+                                .json_data(&*event)
+                                .unwrap_or_else(|e| Event::default().event("error").data(e.to_string()));
+                            return Some((Ok(sse), receiver));
+                        }
+                        Ok(_) => continue,
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            log::debug!("Event subscriber lagged, {} events dropped", skipped);
+                            let sse = Event::default()
+                                .event("lagged")
+                                .data(format!("{{\"skipped\":{}}}", skipped));
+                            return Some((Ok(sse), receiver));
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            }
+        })
+        .take_until(ct.cancelled_owned())
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
+    use axum::response::sse::Sse;
+    use futures::stream::BoxStream;
+    use rmcp::model::Content;
+
+    fn started(tool_name: &str, connection_id: &str) -> ToolEvent {
+        ToolEvent::CallStarted {
+            timestamp: Utc::now(),
+            tool_name: tool_name.to_string(),
+            connection_id: Some(connection_id.to_string()),
+            request_id: "req".to_string(),
+        }
+    }
+
+    /// SSE frames of a subscription, as `(event name, data)`
+    struct Frames {
+        body: BoxStream<'static, Result<axum::body::Bytes, axum::Error>>,
+        buffer: String,
+    }
+
+    impl Frames {
+        fn subscribe(bus: &EventBus, filter: EventFilter, ct: CancellationToken) -> Self {
+            let body = Sse::new(bus.sse_stream(filter, ct)).into_response().into_body();
+            Self {
+                body: body.into_data_stream().boxed(),
+                buffer: String::new(),
+            }
+        }
+
+        /// Next frame, or None once the stream has ended
+        async fn next(&mut self) -> Option<(String, String)> {
+            loop {
+                if let Some(end) = self.buffer.find("\n\n") {
+                    let frame: String = self.buffer.drain(..end + 2).collect();
+                    let field = |name: &str| {
+                        frame
+                            .lines()
+                            .find_map(|line| line.strip_prefix(name))
+                            .unwrap_or_default()
+                            .to_string()
+                    };
+                    return Some((field("event: "), field("data: ")));
+                }
+                let chunk = self.body.next().await?.unwrap();
+                self.buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn call_status_distinguishes_tool_errors_from_failures() {
+        let success = Ok(CallToolResult::success(vec![Content::text("ok")]));
+        let tool_error = Ok(CallToolResult::error(vec![Content::text("bad input")]));
+        let failure = Err(McpError::internal_error("boom", None));
+
+        assert_eq!(CallStatus::from_result(&success), CallStatus::Success);
+        assert_eq!(CallStatus::from_result(&tool_error), CallStatus::ToolError);
+        assert_eq!(CallStatus::from_result(&failure), CallStatus::Error);
+    }
+
+    #[test]
+    fn publish_without_subscribers_does_not_build_the_event() {
+        EventBus::new().publish(|| panic!("event built with no subscribers"));
+    }
+
+    #[test]
+    fn filter_matches_connection_and_tool() {
+        let event = started("read", "c1");
+        assert!(EventFilter::default().matches(&event));
+        assert!(EventFilter { connection_id: Some("c1".to_string()), tool_name: Some("read".to_string()) }.matches(&event));
+        assert!(!EventFilter { connection_id: Some("c2".to_string()), ..EventFilter::default() }.matches(&event));
+        assert!(!EventFilter { tool_name: Some("write".to_string()), ..EventFilter::default() }.matches(&event));
+    }
+
+    #[tokio::test]
+    async fn events_fan_out_to_every_matching_subscriber() {
+        let bus = EventBus::new();
+        let ct = CancellationToken::new();
+        let mut all = Frames::subscribe(&bus, EventFilter::default(), ct.clone());
+        let mut reads = Frames::subscribe(
+            &bus,
+            EventFilter { tool_name: Some("read".to_string()), ..EventFilter::default() },
+            ct.clone(),
+        );
+
+        bus.publish(|| started("write", "c1"));
+        bus.publish(|| started("read", "c1"));
+
+        let (name, data) = all.next().await.unwrap();
+        assert_eq!(name, "call_started");
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&data).unwrap()["tool_name"], "write");
+        let (_, data) = all.next().await.unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&data).unwrap()["tool_name"], "read");
+
+        // The filtered subscriber skips the write and only sees the read
+        let (_, data) = reads.next().await.unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&data).unwrap()["tool_name"], "read");
+
+        ct.cancel();
+        assert!(all.next().await.is_none());
+        assert!(reads.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn lagging_subscriber_is_told_how_many_events_it_missed() {
+        let bus = EventBus::new();
+        let ct = CancellationToken::new();
+        let mut frames = Frames::subscribe(&bus, EventFilter::default(), ct.clone());
+
+        let overflow = 5;
+        for i in 0..EVENT_BUFFER + overflow {
+            bus.publish(|| started(&format!("tool-{}", i), "c1"));
+        }
+
+        let (name, data) = frames.next().await.unwrap();
+        assert_eq!(name, "lagged");
+        assert_eq!(data, format!("{{\"skipped\":{}}}", overflow));
+
+        // Delivery resumes with the oldest event still buffered
+        let (name, data) = frames.next().await.unwrap();
+        assert_eq!(name, "call_started");
+        let event: serde_json::Value = serde_json::from_str(&data).unwrap();
+        assert_eq!(event["tool_name"], format!("tool-{}", overflow));
+
+        ct.cancel();
+    }
+
+    #[tokio::test]
+    async fn stream_ends_when_the_bus_is_dropped() {
+        let bus = EventBus::new();
+        let mut frames = Frames::subscribe(&bus, EventFilter::default(), CancellationToken::new());
+        drop(bus);
+        assert!(frames.next().await.is_none());
+    }
+}
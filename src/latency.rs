@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Upper bounds (inclusive, in ms) of the latency buckets; slower calls land in an overflow bucket
+///
+/// Roughly logarithmic, so percentiles stay within ~50% of the true value from
+/// sub-millisecond file reads up to multi-minute builds.
+const BUCKET_BOUNDS_MS: &[u64] = &[
+    1, 2, 3, 5, 7, 10, 15, 20, 30, 50, 75, 100, 150, 200, 300, 500, 750, 1_000, 1_500, 2_000, 3_000,
+    5_000, 7_500, 10_000, 15_000, 20_000, 30_000, 60_000, 120_000, 300_000, 600_000,
+];
+
+/// Fixed-bucket histogram of tool call durations
+///
+/// Serialized sparsely as `{"buckets": {"<upper bound ms>": count}}`, with the
+/// overflow bucket keyed by `"inf"`, so it stays small in the stats file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LatencyHistogram {
+    pub count: u64,
+    pub sum_ms: u64,
+    pub min_ms: u64,
+    pub max_ms: u64,
+    #[serde(with = "sparse_buckets")]
+    buckets: Vec<u64>,
+}
+
+/// Latency summary reported by `/mcp/stats`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub min_ms: u64,
+    pub mean_ms: f64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+}
+
+impl LatencyHistogram {
+    /// Record one call duration
+    pub fn record(&mut self, duration_ms: u64) {
+        if self.buckets.len() != BUCKET_BOUNDS_MS.len() + 1 {
+            self.buckets.resize(BUCKET_BOUNDS_MS.len() + 1, 0);
+        }
+
+        let bucket = BUCKET_BOUNDS_MS.partition_point(|&bound| bound < duration_ms);
+        self.buckets[bucket] += 1;
+
+        self.min_ms = if self.count == 0 { duration_ms } else { self.min_ms.min(duration_ms) };
+        self.max_ms = self.max_ms.max(duration_ms);
+        self.count += 1;
+        self.sum_ms = self.sum_ms.saturating_add(duration_ms);
+    }
+
+    /// Add another histogram's samples to this one
+    pub fn merge(&mut self, other: &LatencyHistogram) {
+        if other.count == 0 {
+            return;
+        }
+        if self.buckets.len() != BUCKET_BOUNDS_MS.len() + 1 {
+            self.buckets.resize(BUCKET_BOUNDS_MS.len() + 1, 0);
+        }
+        for (bucket, count) in self.buckets.iter_mut().zip(&other.buckets) {
+            *bucket += count;
+        }
+
+        self.min_ms = if self.count == 0 { other.min_ms } else { self.min_ms.min(other.min_ms) };
+        self.max_ms = self.max_ms.max(other.max_ms);
+        self.count += other.count;
+        self.sum_ms = self.sum_ms.saturating_add(other.sum_ms);
+    }
+
+    /// Estimate the `q` quantile (0.0..=1.0), interpolating within the bucket
+    pub fn quantile(&self, q: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+
+        let rank = (q.clamp(0.0, 1.0) * self.count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (i, &count) in self.buckets.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            if seen + count >= rank {
+                let lower = if i == 0 { 0 } else { BUCKET_BOUNDS_MS[i - 1] };
+                let upper = BUCKET_BOUNDS_MS.get(i).copied().unwrap_or(self.max_ms);
+                let fraction = (rank - seen) as f64 / count as f64;
+                let estimate = lower as f64 + (upper.saturating_sub(lower)) as f64 * fraction;
+                return (estimate.round() as u64).clamp(self.min_ms, self.max_ms);
+            }
+            seen += count;
+        }
+        self.max_ms
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        LatencySummary {
+            count: self.count,
+            min_ms: self.min_ms,
+            mean_ms: if self.count == 0 { 0.0 } else { self.sum_ms as f64 / self.count as f64 },
+            p50_ms: self.quantile(0.50),
+            p95_ms: self.quantile(0.95),
+            p99_ms: self.quantile(0.99),
+            max_ms: self.max_ms,
+        }
+    }
+}
+
+/// Summaries for a map of per-tool histograms
+pub fn summarize<'a, I>(histograms: I) -> BTreeMap<String, LatencySummary>
+where
+    I: IntoIterator<Item = (&'a String, &'a LatencyHistogram)>,
+{
+    histograms
+        .into_iter()
+        .map(|(tool, histogram)| (tool.clone(), histogram.summary()))
+        .collect()
+}
+
+/// (De)serialize bucket counts as a map of non-empty buckets keyed by upper bound
+mod sparse_buckets {
+    use super::BUCKET_BOUNDS_MS;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    const OVERFLOW_KEY: &str = "inf";
+
+    pub fn serialize<S: Serializer>(buckets: &[u64], serializer: S) -> Result<S::Ok, S::Error> {
+        let entries = buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(i, count)| {
+                let key = match BUCKET_BOUNDS_MS.get(i) {
+                    Some(bound) => bound.to_string(),
+                    None => OVERFLOW_KEY.to_string(),
+                };
+                (key, *count)
+            });
+
+        serializer.collect_map(entries)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u64>, D::Error> {
+        let sparse = BTreeMap::<String, u64>::deserialize(deserializer)?;
+        let mut buckets = vec![0; BUCKET_BOUNDS_MS.len() + 1];
+
+        for (key, count) in sparse {
+            let index = if key == OVERFLOW_KEY {
+                BUCKET_BOUNDS_MS.len()
+            } else {
+                let bound: u64 = key
+                    .parse()
+                    .map_err(|_| serde::de::Error::custom(format!("invalid latency bucket {key:?}")))?;
+                // Unknown bounds (from a different bucket layout) go to the next bucket up
+                BUCKET_BOUNDS_MS.partition_point(|&b| b < bound)
+            };
+            buckets[index] += count;
+        }
+
+        Ok(buckets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn histogram(durations: impl IntoIterator<Item = u64>) -> LatencyHistogram {
+        let mut histogram = LatencyHistogram::default();
+        for duration_ms in durations {
+            histogram.record(duration_ms);
+        }
+        histogram
+    }
+
+    #[test]
+    fn empty_histogram_reports_zero() {
+        let histogram = LatencyHistogram::default();
+        assert_eq!(histogram.quantile(0.5), 0);
+        assert_eq!(histogram.summary().mean_ms, 0.0);
+    }
+
+    #[test]
+    fn quantiles_interpolate_within_buckets() {
+        let histogram = histogram(1..=100);
+
+        assert_eq!(histogram.quantile(0.0), 1);
+        assert_eq!(histogram.quantile(0.50), 50);
+        assert_eq!(histogram.quantile(0.95), 95);
+        assert_eq!(histogram.quantile(0.99), 99);
+        assert_eq!(histogram.quantile(1.0), 100);
+
+        let summary = histogram.summary();
+        assert_eq!((summary.count, summary.min_ms, summary.max_ms), (100, 1, 100));
+        assert_eq!(summary.mean_ms, 50.5);
+    }
+
+    #[test]
+    fn quantiles_stay_within_observed_range() {
+        let single = histogram([42]);
+        assert_eq!((single.quantile(0.01), single.quantile(0.99)), (42, 42));
+
+        // The overflow bucket is bounded by the largest observed duration
+        let slow = histogram([700_000, 900_000]);
+        assert_eq!(slow.quantile(0.5), 750_000);
+        assert_eq!(slow.quantile(1.0), 900_000);
+    }
+
+    #[test]
+    fn merge_matches_recording_every_sample() {
+        let mut merged = histogram(1..=50);
+        merged.merge(&histogram(51..=100));
+        merged.merge(&LatencyHistogram::default());
+        assert_eq!(merged, histogram(1..=100));
+
+        let mut empty = LatencyHistogram::default();
+        empty.merge(&histogram([7, 9]));
+        assert_eq!((empty.min_ms, empty.max_ms, empty.count), (7, 9, 2));
+    }
+
+    #[test]
+    fn sparse_serialization_round_trips() {
+        let histogram = histogram([3, 3, 40, 1_000_000]);
+        let json = serde_json::to_value(&histogram).unwrap();
+        assert_eq!(json["buckets"], serde_json::json!({ "3": 2, "50": 1, "inf": 1 }));
+
+        let restored: LatencyHistogram = serde_json::from_value(json).unwrap();
+        assert_eq!(restored, histogram);
+    }
+}
@@ -2,24 +2,49 @@ use anyhow::Result;
 use clap::Parser;
 use kodegen_config_manager::ConfigManager;
 use rmcp::handler::server::router::{prompt::PromptRouter, tool::ToolRouter};
+use rmcp::transport::streamable_http_server::SessionManager;
 use rmcp::transport::streamable_http_server::session::local::{LocalSessionManager, SessionConfig};
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
 
+pub mod audit;
 pub mod cli;
+pub mod config;
+pub mod connection_identity;
+pub mod connection_tracker;
+pub mod dashboard;
+pub mod diagnostics;
+pub mod events;
+pub mod latency;
+pub mod logging;
 pub mod managers;
 pub mod memory;
+pub mod metrics;
 pub mod monitor;
+pub mod output_schema;
 pub mod registration;
+pub mod request_id;
 pub mod server;
+pub mod session;
+pub mod slow_calls;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_storage;
+pub mod storage;
+pub mod telemetry;
+pub mod time_buckets;
 pub mod tool_history;
 pub mod usage_tracker;
 
 pub use cli::Cli;
-pub use managers::{Managers, ShutdownHook};
-pub use registration::{register_tool, register_tool_arc};
+pub use config::ServerConfig;
+pub use connection_identity::ConnectionIdSource;
+pub use logging::LogFormat;
+pub use managers::{HealthStatus, ManagerHealth, Managers, ShutdownHook};
+pub use output_schema::OutputSchemaValidation;
+pub use registration::{register_tool, register_tool_arc, register_tool_with_output_schema};
 pub use server::{HttpServer, ServerHandle, ShutdownError};
+pub use session::{EventStoreConfig, PersistentSessionManager, SessionLimitPolicy};
 pub use tool_history::ToolHistory;
 pub use usage_tracker::{UsageTracker, UsageStats};
 
@@ -33,6 +58,16 @@ pub type ConnectionCleanupFn = Arc<
     + Sync
 >;
 
+/// Type alias for async memory pressure callback
+///
+/// Called by the memory monitor on every check while RSS is above the configured
+/// limit, e.g. to clear caches. The server rejects new tool calls in the meantime.
+pub type MemoryPressureFn = Arc<
+    dyn Fn(monitor::MemoryPressure) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>>
+    + Send
+    + Sync
+>;
+
 /// Container for routers and managers
 ///
 /// Category servers build this and pass to run_http_server().
@@ -45,6 +80,8 @@ where
     pub managers: Managers,
     /// Optional async cleanup callback invoked when connection drops
     pub connection_cleanup: Option<ConnectionCleanupFn>,
+    /// Optional async callback invoked while memory is over the limit
+    pub memory_pressure: Option<MemoryPressureFn>,
 }
 
 impl<S> RouterSet<S>
@@ -61,16 +98,20 @@ where
             prompt_router,
             managers,
             connection_cleanup: None,
+            memory_pressure: None,
         }
     }
 }
 
 /// Type alias for tool registration closure
-type ToolRegistrationFn = Box<
-    dyn FnOnce() -> Pin<Box<dyn Future<Output = Result<RouterSet<HttpServer>>> + Send>>
+type ToolRegistrationFn<SM> = Box<
+    dyn FnOnce() -> Pin<Box<dyn Future<Output = Result<RouterSet<HttpServer<SM>>>> + Send>>
     + Send
 >;
 
+/// Type alias for session manager construction (receives category and session config)
+type SessionManagerFactory<SM> = Box<dyn FnOnce(&str, SessionConfig) -> Arc<SM> + Send>;
+
 /// Builder for configuring and running an HTTP MCP server
 ///
 /// This is the recommended API for category servers. It collects all configuration
@@ -97,11 +138,25 @@ type ToolRegistrationFn = Box<
 ///         .await
 /// }
 /// ```
-pub struct ServerBuilder {
+///
+/// The builder is generic over the session backend. It defaults to rmcp's in-memory
+/// `LocalSessionManager`; call `.persistent_sessions()` or `.session_manager()`
+/// **before** `.register_tools()` to use another one.
+pub struct ServerBuilder<SM = LocalSessionManager>
+where
+    SM: SessionManager,
+{
     category: Option<String>,
-    register_tools_fn: Option<ToolRegistrationFn>,
+    register_tools_fn: Option<ToolRegistrationFn<SM>>,
+    /// Set when a session backend change discarded the registration function
+    register_tools_discarded: bool,
+    session_manager_fn: SessionManagerFactory<SM>,
     listener: Option<tokio::net::TcpListener>,
     tls_config: Option<(std::path::PathBuf, std::path::PathBuf)>,
+    output_validation: Option<OutputSchemaValidation>,
+    event_store_config: Option<EventStoreConfig>,
+    config: Option<ServerConfig>,
+    log_filters: Vec<(String, log::LevelFilter)>,
 }
 
 impl ServerBuilder {
@@ -110,8 +165,62 @@ impl ServerBuilder {
         Self {
             category: None,
             register_tools_fn: None,
+            register_tools_discarded: false,
+            session_manager_fn: Box::new(|_category, session_config| {
+                Arc::new(LocalSessionManager {
+                    sessions: Default::default(),
+                    session_config,
+                })
+            }),
             listener: None,
             tls_config: None,
+            output_validation: None,
+            event_store_config: None,
+            config: None,
+            log_filters: Vec::new(),
+        }
+    }
+}
+
+impl<SM> ServerBuilder<SM>
+where
+    SM: SessionManager + std::any::Any,
+{
+    /// Use a custom session manager (optional, call before `.register_tools()`)
+    ///
+    /// Tool routers are typed by the server's session manager, so a registration
+    /// function set earlier cannot be carried over: `.run()` and `.serve()` return
+    /// an error unless `.register_tools()` is called again.
+    pub fn session_manager<SM2>(self, session_manager: Arc<SM2>) -> ServerBuilder<SM2>
+    where
+        SM2: SessionManager + std::any::Any,
+    {
+        self.with_session_manager_fn(Box::new(move |_category, _session_config| session_manager))
+    }
+
+    /// Persist sessions to disk so clients survive server restarts (optional, call before `.register_tools()`)
+    ///
+    /// Uses [`PersistentSessionManager`], which stores session metadata under
+    /// `KodegenConfig::data_dir()` and restores sessions on first use after a restart.
+    pub fn persistent_sessions(self) -> ServerBuilder<PersistentSessionManager> {
+        self.with_session_manager_fn(Box::new(PersistentSessionManager::new))
+    }
+
+    fn with_session_manager_fn<SM2>(self, session_manager_fn: SessionManagerFactory<SM2>) -> ServerBuilder<SM2>
+    where
+        SM2: SessionManager + std::any::Any,
+    {
+        ServerBuilder {
+            category: self.category,
+            register_tools_fn: None,
+            register_tools_discarded: self.register_tools_discarded || self.register_tools_fn.is_some(),
+            session_manager_fn,
+            listener: self.listener,
+            tls_config: self.tls_config,
+            output_validation: self.output_validation,
+            event_store_config: self.event_store_config,
+            config: self.config,
+            log_filters: self.log_filters,
         }
     }
 
@@ -129,9 +238,10 @@ impl ServerBuilder {
     pub fn register_tools<F, Fut>(mut self, f: F) -> Self
     where
         F: FnOnce() -> Fut + Send + 'static,
-        Fut: Future<Output = Result<RouterSet<HttpServer>>> + Send + 'static,
+        Fut: Future<Output = Result<RouterSet<HttpServer<SM>>>> + Send + 'static,
     {
         self.register_tools_fn = Some(Box::new(move || Box::pin(f())));
+        self.register_tools_discarded = false;
         self
     }
 
@@ -154,11 +264,50 @@ impl ServerBuilder {
         self
     }
 
+    /// Set output-schema validation mode (optional)
+    ///
+    /// Controls whether `structured_content` returned by tools is checked against
+    /// their declared output schema. Defaults to Warn in debug builds and Off in
+    /// release builds.
+    pub fn output_validation(mut self, output_validation: OutputSchemaValidation) -> Self {
+        self.output_validation = Some(output_validation);
+        self
+    }
+
+    /// Set SSE event retention (optional)
+    ///
+    /// Server-to-client messages are retained per session so a client that
+    /// reconnects with `Last-Event-ID` receives the messages it missed.
+    /// Defaults to 1024 messages per session, kept for 5 minutes.
+    pub fn event_store_config(mut self, event_store_config: EventStoreConfig) -> Self {
+        self.event_store_config = Some(event_store_config);
+        self
+    }
+
+    /// Set the server configuration (optional)
+    ///
+    /// Replaces loading from the config file and `KODEGEN_*` environment variables.
+    /// `.run()` still applies CLI flags on top. See [`ServerConfig`] for precedence.
+    pub fn config(mut self, config: ServerConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Set the log level of one module (optional, repeatable)
+    ///
+    /// Applied on top of `RUST_LOG` and the configured `log_filters`, e.g.
+    /// `.log_filter("tantivy", log::LevelFilter::Error)`. Has no effect if another
+    /// server in the process already initialized logging.
+    pub fn log_filter(mut self, module: impl Into<String>, level: log::LevelFilter) -> Self {
+        self.log_filters.push((module.into(), level));
+        self
+    }
+
     /// Run the HTTP server (blocking until shutdown signal)
     ///
     /// This method:
+    /// - Parses CLI arguments and loads `ServerConfig`
     /// - Initializes logging
-    /// - Parses CLI arguments
     /// - Creates ConfigManager, UsageTracker, ToolHistory
     /// - Calls the tool registration function
     /// - Starts the HTTP/HTTPS server
@@ -167,49 +316,64 @@ impl ServerBuilder {
     pub async fn run(self) -> Result<()> {
         let category = self.category
             .ok_or_else(|| anyhow::anyhow!("category is required - call .category() before .run()"))?;
-        let register_tools_fn = self.register_tools_fn
-            .ok_or_else(|| anyhow::anyhow!("register_tools is required - call .register_tools() before .run()"))?;
-
-        // Initialize logging with chromiumoxide CDP error filtering and tantivy spam reduction
-        env_logger::Builder::from_default_env()
-            .filter_module("chromiumoxide::handler", log::LevelFilter::Off)
-            .filter_module("chromiumoxide::conn", log::LevelFilter::Off)
-            .filter_module("tantivy::indexer::index_writer", log::LevelFilter::Warn)
-            .filter_module("tantivy::indexer::prepared_commit", log::LevelFilter::Warn)
-            .filter_module("tantivy::indexer::segment_updater", log::LevelFilter::Warn)
-            .filter_module("tantivy::directory::managed_directory", log::LevelFilter::Warn)
-            .filter_module("tantivy::directory::file_watcher", log::LevelFilter::Warn)
-            .init();
+        let register_tools_fn = match self.register_tools_fn {
+            Some(register_tools_fn) => register_tools_fn,
+            None if self.register_tools_discarded => anyhow::bail!(
+                "register_tools was discarded by a later session manager change - call .session_manager() or .persistent_sessions() before .register_tools()"
+            ),
+            None => anyhow::bail!("register_tools is required - call .register_tools() before .run()"),
+        };
+
+        // Parse CLI arguments and resolve configuration (file < env < CLI)
+        let cli = Cli::parse();
+        let config = match self.config {
+            Some(mut config) => {
+                config.apply_cli(&cli);
+                config.validate()?;
+                config
+            }
+            None => ServerConfig::load(Some(&cli))?,
+        };
+
+        if let Some(cli::Command::VerifyAuditLog { path }) = &cli.command {
+            let path = path.clone().unwrap_or_else(|| config.audit_log_file(&category));
+            return audit::run_verify_command(&path);
+        }
+
+        // Create instance ID
+        let timestamp = chrono::Utc::now();
+        let pid = std::process::id();
+        let instance_id = format!("{}-{}", timestamp.format("%Y%m%d-%H%M%S-%9f"), pid);
+
+        // Initialize logging (configured module filters, then builder overrides)
+        let mut log_filters = config.log_filters();
+        log_filters.extend(self.log_filters);
+        if !logging::init_logging(config.log_format, &log_filters, &category, &instance_id) {
+            log::debug!("Logger already initialized");
+        }
 
         // Install rustls CryptoProvider (idempotent)
         if rustls::crypto::ring::default_provider().install_default().is_err() {
             log::debug!("rustls crypto provider already installed");
         }
 
-        // Parse CLI arguments
-        let cli = Cli::parse();
-
         // Initialize ConfigManager
         let config_manager = ConfigManager::new();
         config_manager.init().await?;
 
-        // Create instance ID
-        let timestamp = chrono::Utc::now();
-        let pid = std::process::id();
-        let instance_id = format!("{}-{}", timestamp.format("%Y%m%d-%H%M%S-%9f"), pid);
-
         // Create UsageTracker and ToolHistory
-        let usage_tracker = UsageTracker::new(format!("{}-{}", category, instance_id));
+        let storage = storage::open(config.storage, &format!("{}-{}", category, instance_id))?;
+        let usage_tracker = UsageTracker::with_storage(Arc::clone(&storage), config.bucket_retention());
         log::debug!("Initializing tool history tracking for instance: {}", instance_id);
-        let tool_history = Arc::new(ToolHistory::new(format!("{}-{}", category, instance_id)).await);
+        let tool_history = Arc::new(ToolHistory::with_storage(storage).await);
 
         // Call tool registration function
         let routers = register_tools_fn().await?;
 
         // Create session manager
         let session_config = SessionConfig {
-            channel_capacity: 16,
-            keep_alive: cli.session_keep_alive(),
+            channel_capacity: config.channel_capacity,
+            keep_alive: config.session_keep_alive(),
         };
 
         match session_config.keep_alive {
@@ -217,10 +381,7 @@ impl ServerBuilder {
             Some(duration) => log::info!("Session keep-alive: {:?}", duration),
         }
 
-        let session_manager = Arc::new(LocalSessionManager {
-            sessions: Default::default(),
-            session_config,
-        });
+        let session_manager = (self.session_manager_fn)(&category, session_config);
 
         // Get listener and address (either from pre-bound listener or CLI)
         let (addr, listener) = if let Some(listener) = self.listener {
@@ -228,9 +389,19 @@ impl ServerBuilder {
                 .map_err(|e| anyhow::anyhow!("Failed to get listener address: {}", e))?;
             (addr, listener)
         } else {
-            let addr = cli.http_address()?;
-            let listener = tokio::net::TcpListener::bind(addr).await
+            let addr = config.http_address()?;
+            let socket = if addr.is_ipv4() {
+                tokio::net::TcpSocket::new_v4()?
+            } else {
+                tokio::net::TcpSocket::new_v6()?
+            };
+            // Same SO_REUSEADDR as TcpListener::bind, plus the configured backlog
+            socket.set_reuseaddr(true)
+                .map_err(|e| anyhow::anyhow!("Failed to set SO_REUSEADDR: {}", e))?;
+            socket.bind(addr)
                 .map_err(|e| anyhow::anyhow!("Failed to bind to {}: {}", addr, e))?;
+            let listener = socket.listen(config.listen_backlog)
+                .map_err(|e| anyhow::anyhow!("Failed to listen on {}: {}", addr, e))?;
             (addr, listener)
         };
 
@@ -250,24 +421,38 @@ impl ServerBuilder {
             .tool_history(tool_history)
             .config_manager(config_manager)
             .managers(routers.managers)
-            .session_manager(session_manager);
+            .session_manager(session_manager)
+            .server_config(config.clone());
 
         if let Some(cleanup) = routers.connection_cleanup {
             builder = builder.connection_cleanup(cleanup);
         }
 
+        if let Some(on_pressure) = routers.memory_pressure {
+            builder = builder.memory_pressure(on_pressure);
+        }
+
+        if let Some(output_validation) = self.output_validation {
+            builder = builder.output_validation(output_validation);
+        }
+
+        if let Some(event_store_config) = self.event_store_config {
+            builder = builder.event_store_config(event_store_config);
+        }
+
         let server = builder.build()
-            .expect("Failed to build HttpServer - all required fields provided");
+            .map_err(|e| anyhow::anyhow!("Failed to build HttpServer: {}", e))?;
 
         // Start server with pre-bound listener
-        let protocol = if cli.tls_config().is_some() { "https" } else { "http" };
+        let tls_config = config.tls_config();
+        let protocol = if tls_config.is_some() { "https" } else { "http" };
         log::info!("Starting {} HTTP server on {}://{}", category, protocol, addr);
 
-        let timeout = cli.shutdown_timeout();
-        let handle = server.serve_with_listener(listener, cli.tls_config(), timeout).await?;
+        let timeout = config.shutdown_timeout();
+        let handle = server.serve_with_listener(listener, tls_config.clone(), timeout).await?;
 
         log::info!("{} server running on {}://{}", category, protocol, addr);
-        if cli.tls_config().is_some() {
+        if tls_config.is_some() {
             log::info!("TLS/HTTPS enabled - using encrypted connections");
         }
         log::info!("Press Ctrl+C or send SIGTERM to initiate graceful shutdown");
@@ -317,19 +502,32 @@ impl ServerBuilder {
     pub async fn serve(self) -> Result<ServerHandle> {
         let category = self.category
             .ok_or_else(|| anyhow::anyhow!("category is required - call .category() before .serve()"))?;
-        let register_tools_fn = self.register_tools_fn
-            .ok_or_else(|| anyhow::anyhow!("register_tools is required - call .register_tools() before .serve()"))?;
-
-        // Initialize logging (may be called multiple times by different servers - idempotent)
-        let _ = env_logger::Builder::from_default_env()
-            .filter_module("chromiumoxide::handler", log::LevelFilter::Off)
-            .filter_module("chromiumoxide::conn", log::LevelFilter::Off)
-            .filter_module("tantivy::indexer::index_writer", log::LevelFilter::Warn)
-            .filter_module("tantivy::indexer::prepared_commit", log::LevelFilter::Warn)
-            .filter_module("tantivy::indexer::segment_updater", log::LevelFilter::Warn)
-            .filter_module("tantivy::directory::managed_directory", log::LevelFilter::Warn)
-            .filter_module("tantivy::directory::file_watcher", log::LevelFilter::Warn)
-            .try_init();
+        let register_tools_fn = match self.register_tools_fn {
+            Some(register_tools_fn) => register_tools_fn,
+            None if self.register_tools_discarded => anyhow::bail!(
+                "register_tools was discarded by a later session manager change - call .session_manager() or .persistent_sessions() before .register_tools()"
+            ),
+            None => anyhow::bail!("register_tools is required - call .register_tools() before .serve()"),
+        };
+
+        // Resolve configuration (file < env; the command line belongs to the host process)
+        let config = match self.config {
+            Some(config) => {
+                config.validate()?;
+                config
+            }
+            None => ServerConfig::load(None)?,
+        };
+
+        // Create instance ID
+        let timestamp = chrono::Utc::now();
+        let pid = std::process::id();
+        let instance_id = format!("{}-{}", timestamp.format("%Y%m%d-%H%M%S-%9f"), pid);
+
+        // Initialize logging (may be called multiple times by different servers - first one wins)
+        let mut log_filters = config.log_filters();
+        log_filters.extend(self.log_filters);
+        logging::init_logging(config.log_format, &log_filters, &category, &instance_id);
 
         // Install rustls CryptoProvider (idempotent)
         if rustls::crypto::ring::default_provider().install_default().is_err() {
@@ -340,29 +538,22 @@ impl ServerBuilder {
         let config_manager = ConfigManager::new();
         config_manager.init().await?;
 
-        // Create instance ID
-        let timestamp = chrono::Utc::now();
-        let pid = std::process::id();
-        let instance_id = format!("{}-{}", timestamp.format("%Y%m%d-%H%M%S-%9f"), pid);
-
         // Create UsageTracker and ToolHistory
-        let usage_tracker = UsageTracker::new(format!("{}-{}", category, instance_id));
+        let storage = storage::open(config.storage, &format!("{}-{}", category, instance_id))?;
+        let usage_tracker = UsageTracker::with_storage(Arc::clone(&storage), config.bucket_retention());
         log::debug!("Initializing tool history tracking for instance: {}", instance_id);
-        let tool_history = Arc::new(ToolHistory::new(format!("{}-{}", category, instance_id)).await);
+        let tool_history = Arc::new(ToolHistory::with_storage(storage).await);
 
         // Call tool registration function
         let routers = register_tools_fn().await?;
 
         // Create session manager
         let session_config = SessionConfig {
-            channel_capacity: 16,
-            keep_alive: Some(std::time::Duration::from_secs(3600)),
+            channel_capacity: config.channel_capacity,
+            keep_alive: config.embedded_session_keep_alive(),
         };
 
-        let session_manager = Arc::new(LocalSessionManager {
-            sessions: Default::default(),
-            session_config,
-        });
+        let session_manager = (self.session_manager_fn)(&category, session_config);
 
         // Get listener and address (must have pre-bound listener for embedded servers)
         let listener = self.listener
@@ -387,22 +578,35 @@ impl ServerBuilder {
             .tool_history(tool_history)
             .config_manager(config_manager)
             .managers(routers.managers)
-            .session_manager(session_manager);
+            .session_manager(session_manager)
+            .server_config(config.clone());
 
         if let Some(cleanup) = routers.connection_cleanup {
             builder = builder.connection_cleanup(cleanup);
         }
 
+        if let Some(on_pressure) = routers.memory_pressure {
+            builder = builder.memory_pressure(on_pressure);
+        }
+
+        if let Some(output_validation) = self.output_validation {
+            builder = builder.output_validation(output_validation);
+        }
+
+        if let Some(event_store_config) = self.event_store_config {
+            builder = builder.event_store_config(event_store_config);
+        }
+
         let server = builder.build()
-            .expect("Failed to build HttpServer - all required fields provided");
+            .map_err(|e| anyhow::anyhow!("Failed to build HttpServer: {}", e))?;
 
         // Start server with pre-bound listener
-        let tls_config = self.tls_config;
+        let tls_config = self.tls_config.or_else(|| config.tls_config());
         let has_tls = tls_config.is_some();
         let protocol = if has_tls { "https" } else { "http" };
         log::info!("Starting {} HTTP server on {}://{}", category, protocol, addr);
 
-        let shutdown_timeout = std::time::Duration::from_secs(30);
+        let shutdown_timeout = config.shutdown_timeout();
         let handle = server.serve_with_listener(listener, tls_config, shutdown_timeout).await?;
 
         log::info!("{} server running on {}://{}", category, protocol, addr);
@@ -0,0 +1,537 @@
+use chrono::{DateTime, Utc};
+use log::LevelFilter;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Log output format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Human-readable env_logger output
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(format!("unknown log format '{}' (expected text or json)", other)),
+        }
+    }
+}
+
+/// Module filters applied unless overridden: silence chromiumoxide CDP errors and tantivy spam
+pub fn default_log_filters() -> BTreeMap<String, String> {
+    [
+        ("chromiumoxide::handler", "off"),
+        ("chromiumoxide::conn", "off"),
+        ("tantivy::indexer::index_writer", "warn"),
+        ("tantivy::indexer::prepared_commit", "warn"),
+        ("tantivy::indexer::segment_updater", "warn"),
+        ("tantivy::directory::managed_directory", "warn"),
+        ("tantivy::directory::file_watcher", "warn"),
+    ]
+    .into_iter()
+    .map(|(module, level)| (module.to_string(), level.to_string()))
+    .collect()
+}
+
+/// Parse `module=level` pairs separated by commas (as in `KODEGEN_LOG_FILTERS`)
+pub fn parse_log_filters(s: &str) -> Result<BTreeMap<String, String>, String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .map(parse_log_filter)
+        .collect()
+}
+
+/// Parse one `module=level` directive (as in `--log-filter`)
+pub fn parse_log_filter(directive: &str) -> Result<(String, String), String> {
+    let (module, level) = directive
+        .split_once('=')
+        .ok_or_else(|| format!("invalid log filter '{}' (expected module=level)", directive))?;
+    let (module, level) = (module.trim(), level.trim());
+    if module.is_empty() {
+        return Err(format!("invalid log filter '{}' (module is empty)", directive));
+    }
+    LevelFilter::from_str(level)
+        .map_err(|_| format!("invalid log level '{}' for module {}", level, module))?;
+    Ok((module.to_string(), level.to_string()))
+}
+
+/// Per-task fields added to JSON log lines (text lines get the request id)
+#[derive(Debug, Clone, Default)]
+pub struct LogContext {
+    pub category: Option<String>,
+    pub connection_id: Option<String>,
+    pub tool_name: Option<String>,
+    pub request_id: Option<String>,
+}
+
+tokio::task_local! {
+    static LOG_CONTEXT: LogContext;
+}
+
+/// Run `future` with `context` attached to every log line it emits
+pub async fn with_log_context<F>(context: LogContext, future: F) -> F::Output
+where
+    F: Future,
+{
+    LOG_CONTEXT.scope(context, future).await
+}
+
+/// Category and instance of the server that initialized logging
+struct ProcessIdentity {
+    category: String,
+    instance_id: String,
+}
+
+static PROCESS_IDENTITY: OnceLock<ProcessIdentity> = OnceLock::new();
+
+/// Initialize the global logger (no-op if a logger is already installed)
+///
+/// `RUST_LOG` sets the base filter; `filters` then override individual modules.
+/// Levels can be changed afterwards with [`set_log_level`].
+/// Returns false if logging was already initialized, e.g. by another embedded server.
+pub fn init_logging(
+    format: LogFormat,
+    filters: &[(String, LevelFilter)],
+    category: &str,
+    instance_id: &str,
+) -> bool {
+    if DYNAMIC_FILTER.get().is_some() {
+        return false;
+    }
+
+    // The inner logger only formats; filtering is done by the reloadable DynamicFilter
+    let mut builder = env_logger::Builder::from_env(env_logger::Env::new().write_style("RUST_LOG_STYLE"));
+    builder.filter_level(LevelFilter::Trace);
+
+    if format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let identity = PROCESS_IDENTITY.get();
+            let context = LOG_CONTEXT.try_with(LogContext::clone).unwrap_or_default();
+
+            let mut line = serde_json::json!({
+                "timestamp": Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+                "category": context.category.or_else(|| identity.map(|identity| identity.category.clone())),
+                "instance_id": identity.map(|identity| identity.instance_id.as_str()),
+            });
+            if let Some(connection_id) = context.connection_id {
+                line["connection_id"] = connection_id.into();
+            }
+            if let Some(tool_name) = context.tool_name {
+                line["tool_name"] = tool_name.into();
+            }
+            if let Some(request_id) = context.request_id {
+                line["request_id"] = request_id.into();
+            }
+
+            writeln!(buf, "{}", line)
+        });
+    }
+
+    let state = LevelState {
+        rust_log: std::env::var("RUST_LOG").unwrap_or_default(),
+        module_filters: filters.to_vec(),
+        global: None,
+        modules: BTreeMap::new(),
+        next_id: 0,
+    };
+    let filter: &'static DynamicFilter = Box::leak(Box::new(DynamicFilter {
+        filter: RwLock::new(state.build_filter()),
+        state: Mutex::new(state),
+    }));
+    let max_level = filter.filter.read().filter();
+
+    let logger = KodegenLogger {
+        inner: builder.build(),
+        format,
+        filter,
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_err() {
+        return false;
+    }
+    log::set_max_level(max_level);
+
+    // First server in the process wins, matching the logger itself
+    let _ = DYNAMIC_FILTER.set(filter);
+    let _ = PROCESS_IDENTITY.set(ProcessIdentity {
+        category: category.to_string(),
+        instance_id: instance_id.to_string(),
+    });
+    true
+}
+
+/// Error changing log levels at runtime
+#[derive(Debug, Error)]
+pub enum LogLevelError {
+    #[error("log levels are not managed by this server (another logger is installed)")]
+    Unmanaged,
+    #[error("ttl is too large")]
+    TtlOutOfRange,
+}
+
+/// Runtime override of a log level
+#[derive(Debug, Clone, Serialize)]
+pub struct LevelOverride {
+    pub level: String,
+    /// When the override reverts (None = until reset)
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Current log level configuration
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLevels {
+    /// Base filter from `RUST_LOG` at startup (empty = errors only)
+    pub rust_log: String,
+    /// Module filters from the config and `ServerBuilder::log_filter()`
+    pub module_filters: BTreeMap<String, String>,
+    /// Runtime override of the default level
+    pub global: Option<LevelOverride>,
+    /// Runtime overrides of individual modules
+    pub modules: BTreeMap<String, LevelOverride>,
+    /// Most verbose level enabled for any module
+    pub max_level: String,
+}
+
+/// Current log levels, or None if another logger is installed
+pub fn log_levels() -> Option<LogLevels> {
+    let filter = DYNAMIC_FILTER.get()?;
+    let state = filter.state.lock();
+
+    Some(LogLevels {
+        rust_log: state.rust_log.clone(),
+        module_filters: state
+            .module_filters
+            .iter()
+            .map(|(module, level)| (module.clone(), level.to_string().to_lowercase()))
+            .collect(),
+        global: state.global.as_ref().map(Override::describe),
+        modules: state
+            .modules
+            .iter()
+            .map(|(module, level)| (module.clone(), level.describe()))
+            .collect(),
+        max_level: log::max_level().to_string().to_lowercase(),
+    })
+}
+
+/// Change the default level (`module` = None) or one module's level at runtime
+///
+/// With a `ttl` the override reverts on its own, so verbose logging cannot be
+/// left on by accident. Replaces any earlier override of the same target.
+pub fn set_log_level(module: Option<&str>, level: LevelFilter, ttl: Option<Duration>) -> Result<(), LogLevelError> {
+    let filter = DYNAMIC_FILTER.get().ok_or(LogLevelError::Unmanaged)?;
+    let expires_at = ttl
+        .map(|ttl| {
+            chrono::Duration::from_std(ttl)
+                .ok()
+                .and_then(|ttl| Utc::now().checked_add_signed(ttl))
+                .ok_or(LogLevelError::TtlOutOfRange)
+        })
+        .transpose()?;
+
+    let id = {
+        let mut state = filter.state.lock();
+        state.next_id += 1;
+        let entry = Override {
+            id: state.next_id,
+            level,
+            expires_at,
+        };
+        match module {
+            Some(module) => {
+                state.modules.insert(module.to_string(), entry);
+            }
+            None => state.global = Some(entry),
+        }
+        filter.rebuild(&state);
+        state.next_id
+    };
+
+    let target = module.unwrap_or("default");
+    match ttl {
+        Some(ttl) => {
+            log::info!("Log level for {} set to {} for {:?}", target, level, ttl);
+            let module = module.map(str::to_string);
+            spawn_expiry(ttl, move || filter.expire(module.as_deref(), id));
+        }
+        None => log::info!("Log level for {} set to {}", target, level),
+    }
+    Ok(())
+}
+
+/// Remove the runtime override of one module, or all overrides (`module` = None)
+pub fn reset_log_levels(module: Option<&str>) -> Result<(), LogLevelError> {
+    let filter = DYNAMIC_FILTER.get().ok_or(LogLevelError::Unmanaged)?;
+
+    let mut state = filter.state.lock();
+    match module {
+        Some(module) => {
+            state.modules.remove(module);
+        }
+        None => {
+            state.global = None;
+            state.modules.clear();
+        }
+    }
+    filter.rebuild(&state);
+    drop(state);
+
+    log::info!("Log level overrides reset for {}", module.unwrap_or("all modules"));
+    Ok(())
+}
+
+/// Run `f` after `delay`, on the Tokio runtime if there is one
+fn spawn_expiry<F>(delay: Duration, f: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn(async move {
+                tokio::time::sleep(delay).await;
+                f();
+            });
+        }
+        Err(_) => {
+            std::thread::spawn(move || {
+                std::thread::sleep(delay);
+                f();
+            });
+        }
+    }
+}
+
+static DYNAMIC_FILTER: OnceLock<&'static DynamicFilter> = OnceLock::new();
+
+/// Log filter that is rebuilt whenever levels change at runtime
+struct DynamicFilter {
+    state: Mutex<LevelState>,
+    filter: RwLock<env_filter::Filter>,
+}
+
+impl DynamicFilter {
+    /// Swap in the filter for `state` and update the global max level
+    fn rebuild(&self, state: &LevelState) {
+        let filter = state.build_filter();
+        log::set_max_level(filter.filter());
+        *self.filter.write() = filter;
+    }
+
+    /// Drop override `id` if it has not been replaced since
+    fn expire(&self, module: Option<&str>, id: u64) {
+        let mut state = self.state.lock();
+        let expired = match module {
+            Some(module) => {
+                let current = state.modules.get(module).is_some_and(|entry| entry.id == id);
+                current && state.modules.remove(module).is_some()
+            }
+            None => {
+                let current = state.global.as_ref().is_some_and(|entry| entry.id == id);
+                current && state.global.take().is_some()
+            }
+        };
+        if expired {
+            self.rebuild(&state);
+            drop(state);
+            log::info!("Log level override for {} expired", module.unwrap_or("default"));
+        }
+    }
+}
+
+struct LevelState {
+    rust_log: String,
+    module_filters: Vec<(String, LevelFilter)>,
+    global: Option<Override>,
+    modules: BTreeMap<String, Override>,
+    next_id: u64,
+}
+
+impl LevelState {
+    /// `RUST_LOG`, then configured module filters, then runtime overrides (later wins)
+    fn build_filter(&self) -> env_filter::Filter {
+        let mut builder = env_filter::Builder::new();
+        builder.parse(&self.rust_log);
+        for (module, level) in &self.module_filters {
+            builder.filter_module(module, *level);
+        }
+        if let Some(ref global) = self.global {
+            builder.filter_level(global.level);
+        }
+        for (module, entry) in &self.modules {
+            builder.filter_module(module, entry.level);
+        }
+        builder.build()
+    }
+}
+
+struct Override {
+    id: u64,
+    level: LevelFilter,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl Override {
+    fn describe(&self) -> LevelOverride {
+        LevelOverride {
+            level: self.level.to_string().to_lowercase(),
+            expires_at: self.expires_at,
+        }
+    }
+}
+
+/// env_logger output behind a [`DynamicFilter`]
+struct KodegenLogger {
+    inner: env_logger::Logger,
+    format: LogFormat,
+    filter: &'static DynamicFilter,
+}
+
+impl log::Log for KodegenLogger {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        self.filter.filter.read().enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        if !self.filter.filter.read().matches(record) {
+            return;
+        }
+
+        // JSON lines carry the request id as a field; prefix it in text lines
+        let request_id = match self.format {
+            LogFormat::Text => LOG_CONTEXT.try_with(|context| context.request_id.clone()).ok().flatten(),
+            LogFormat::Json => None,
+        };
+        match request_id {
+            Some(request_id) => self.inner.log(
+                &log::Record::builder()
+                    .args(format_args!("[{}] {}", request_id, record.args()))
+                    .metadata(record.metadata().clone())
+                    .module_path(record.module_path())
+                    .file(record.file())
+                    .line(record.line())
+                    .build(),
+            ),
+            None => self.inner.log(record),
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Install the runtime level filter without a logger, so level changes can be tested
+#[cfg(test)]
+pub(crate) fn install_test_filter() {
+    DYNAMIC_FILTER.get_or_init(|| {
+        let state = LevelState {
+            rust_log: String::new(),
+            module_filters: Vec::new(),
+            global: None,
+            modules: BTreeMap::new(),
+            next_id: 0,
+        };
+        Box::leak(Box::new(DynamicFilter {
+            filter: RwLock::new(state.build_filter()),
+            state: Mutex::new(state),
+        }))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Whether `level` is enabled for `target` by the installed filter
+    fn enabled(target: &str, level: log::Level) -> bool {
+        let metadata = log::Metadata::builder().target(target).level(level).build();
+        DYNAMIC_FILTER.get().unwrap().filter.read().enabled(&metadata)
+    }
+
+    fn module_override(module: &str) -> Option<LevelOverride> {
+        log_levels().unwrap().modules.remove(module)
+    }
+
+    /// Poll until `condition` holds, failing after `timeout`
+    fn wait_for(timeout: Duration, condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + timeout;
+        while !condition() {
+            assert!(Instant::now() < deadline, "condition not met within {:?}", timeout);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn parses_filter_directives() {
+        assert_eq!(parse_log_filter("tantivy=warn"), Ok(("tantivy".to_string(), "warn".to_string())));
+        assert!(parse_log_filter("tantivy").is_err());
+        assert!(parse_log_filter("tantivy=loud").is_err());
+
+        let filters = parse_log_filters("a=info, b=debug").unwrap();
+        assert_eq!(filters.get("a").map(String::as_str), Some("info"));
+        assert_eq!(filters.get("b").map(String::as_str), Some("debug"));
+    }
+
+    #[test]
+    fn module_level_with_ttl_takes_effect_then_reverts() {
+        install_test_filter();
+        let module = "kodegen_logging_test::ttl";
+        assert!(!enabled(module, log::Level::Debug));
+
+        set_log_level(Some(module), LevelFilter::Debug, Some(Duration::from_millis(100))).unwrap();
+        assert!(enabled(module, log::Level::Debug));
+        assert!(!enabled(module, log::Level::Trace));
+        assert!(!enabled("kodegen_logging_test::other", log::Level::Debug), "only the module is raised");
+        let active = module_override(module).unwrap();
+        assert_eq!(active.level, "debug");
+        assert!(active.expires_at.is_some());
+
+        wait_for(Duration::from_secs(5), || module_override(module).is_none());
+        assert!(!enabled(module, log::Level::Debug));
+    }
+
+    #[test]
+    fn replaced_override_is_not_reverted_by_the_earlier_ttl() {
+        install_test_filter();
+        let module = "kodegen_logging_test::replaced";
+
+        set_log_level(Some(module), LevelFilter::Debug, Some(Duration::from_millis(50))).unwrap();
+        set_log_level(Some(module), LevelFilter::Trace, None).unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+
+        assert!(enabled(module, log::Level::Trace));
+        assert_eq!(module_override(module).map(|o| o.level), Some("trace".to_string()));
+
+        reset_log_levels(Some(module)).unwrap();
+        assert!(module_override(module).is_none());
+        assert!(!enabled(module, log::Level::Debug));
+    }
+
+    #[test]
+    fn oversized_ttl_is_rejected() {
+        install_test_filter();
+        let result = set_log_level(Some("kodegen_logging_test::forever"), LevelFilter::Debug, Some(Duration::MAX));
+        assert!(matches!(result, Err(LogLevelError::TtlOutOfRange)));
+        assert!(module_override("kodegen_logging_test::forever").is_none());
+    }
+}
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use serde::Serialize;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -14,6 +15,12 @@ use tokio::sync::Mutex;
 /// - File cleanup: <1 second
 const PER_MANAGER_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// Maximum time to wait for a single manager's health check.
+///
+/// Readiness probes typically time out after a few seconds, so a slow check
+/// is reported as unhealthy rather than stalling the probe.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
 /// Container for managers that require explicit shutdown
 ///
 /// Category servers populate this based on what managers their tools use.
@@ -31,6 +38,71 @@ pub struct Managers {
 /// - SearchManager::shutdown() - cancels background search tasks
 pub trait ShutdownHook: Send + Sync {
     fn shutdown(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>>;
+
+    /// Report health for `/mcp/health/ready` (default: always healthy)
+    ///
+    /// Override to surface problems such as a crashed browser pool or an
+    /// exhausted DB pool. Checks should be cheap; they run on every probe.
+    fn health(&self) -> Pin<Box<dyn Future<Output = ManagerHealth> + Send + '_>> {
+        Box::pin(async { ManagerHealth::healthy() })
+    }
+
+    /// Name shown in readiness reports (default: the type name)
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+/// Health status reported by probes and managers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum HealthStatus {
+    #[serde(rename = "HEALTHY")]
+    Healthy,
+    /// Working with reduced capacity; the server stays ready
+    #[serde(rename = "DEGRADED")]
+    Degraded,
+    /// Not working; the server reports not ready
+    #[serde(rename = "UNHEALTHY")]
+    Unhealthy,
+}
+
+/// Result of a manager health check
+#[derive(Debug, Clone, Serialize)]
+pub struct ManagerHealth {
+    pub status: HealthStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl ManagerHealth {
+    pub fn healthy() -> Self {
+        Self {
+            status: HealthStatus::Healthy,
+            message: None,
+        }
+    }
+
+    pub fn degraded(message: impl Into<String>) -> Self {
+        Self {
+            status: HealthStatus::Degraded,
+            message: Some(message.into()),
+        }
+    }
+
+    pub fn unhealthy(message: impl Into<String>) -> Self {
+        Self {
+            status: HealthStatus::Unhealthy,
+            message: Some(message.into()),
+        }
+    }
+}
+
+/// Health of one registered manager, as listed by `/mcp/health/ready`
+#[derive(Debug, Clone, Serialize)]
+pub struct ManagerHealthReport {
+    pub name: String,
+    #[serde(flatten)]
+    pub health: ManagerHealth,
 }
 
 impl Managers {
@@ -71,6 +143,31 @@ impl Managers {
         self.shutdown_hooks.lock().await.push(Arc::new(hook));
     }
 
+    /// Check the health of all registered managers concurrently
+    ///
+    /// Reports are in registration order. A check that exceeds its timeout is
+    /// reported as unhealthy.
+    pub async fn health_check(&self) -> Vec<ManagerHealthReport> {
+        // Clone the Arcs so the lock is not held across the checks
+        let hooks: Vec<_> = self.shutdown_hooks.lock().await.clone();
+
+        let checks = hooks.iter().map(|hook| async move {
+            let health = match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, hook.health()).await {
+                Ok(health) => health,
+                Err(_) => ManagerHealth::unhealthy(format!(
+                    "health check timed out after {:?}",
+                    HEALTH_CHECK_TIMEOUT
+                )),
+            };
+            ManagerHealthReport {
+                name: hook.name().to_string(),
+                health,
+            }
+        });
+
+        futures::future::join_all(checks).await
+    }
+
     /// Shutdown all registered managers gracefully in reverse registration order (LIFO)
     ///
     /// Managers are shut down **sequentially** in reverse order of registration.
@@ -0,0 +1,208 @@
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::collections::HashMap;
+
+/// Tool latency histogram buckets in seconds (tools range from sub-millisecond to minutes)
+const DURATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+];
+
+/// `tool` label for calls to tools that are not registered, keeping label sets bounded
+pub const UNKNOWN_TOOL: &str = "unknown";
+
+/// Point-in-time values sampled when `/metrics` is scraped
+pub struct MetricsSnapshot {
+    pub active_requests: usize,
+    pub requests_processed: u64,
+    pub sessions: usize,
+    pub memory_used: Option<u64>,
+    pub history_write_queue_depth: usize,
+}
+
+/// Prometheus metrics served on `/metrics`
+///
+/// Call outcomes and latencies are recorded as they happen; gauges that mirror
+/// server state are refreshed from a [`MetricsSnapshot`] on each scrape. Every
+/// metric carries a constant `category` label.
+pub struct Metrics {
+    registry: Registry,
+    calls: IntCounterVec,
+    tool_calls: IntCounterVec,
+    tool_duration: HistogramVec,
+    active_requests: IntGauge,
+    requests_processed: IntCounter,
+    sessions: IntGauge,
+    memory_rss: IntGauge,
+    history_write_queue_depth: IntGauge,
+}
+
+impl Metrics {
+    pub fn new(category: &str) -> Result<Self, prometheus::Error> {
+        let labels = HashMap::from([("category".to_string(), category.to_string())]);
+        let registry = Registry::new_custom(Some("kodegen".to_string()), Some(labels))?;
+
+        let calls = IntCounterVec::new(
+            Opts::new("calls_total", "Tool calls by outcome"),
+            &["status"],
+        )?;
+        let tool_calls = IntCounterVec::new(
+            Opts::new("tool_calls_total", "Tool calls by tool and outcome"),
+            &["tool", "status"],
+        )?;
+        let tool_duration = HistogramVec::new(
+            HistogramOpts::new("tool_call_duration_seconds", "Tool call latency")
+                .buckets(DURATION_BUCKETS.to_vec()),
+            &["tool"],
+        )?;
+        let active_requests = IntGauge::new("active_requests", "Tool calls currently executing")?;
+        let requests_processed = IntCounter::new("requests_processed_total", "Tool calls received")?;
+        let sessions = IntGauge::new("sessions", "Open MCP sessions")?;
+        let memory_rss = IntGauge::new("process_resident_memory_bytes", "Process resident set size")?;
+        let history_write_queue_depth = IntGauge::new(
+            "tool_history_write_queue_depth",
+            "Tool history records waiting to be written to disk",
+        )?;
+
+        registry.register(Box::new(calls.clone()))?;
+        registry.register(Box::new(tool_calls.clone()))?;
+        registry.register(Box::new(tool_duration.clone()))?;
+        registry.register(Box::new(active_requests.clone()))?;
+        registry.register(Box::new(requests_processed.clone()))?;
+        registry.register(Box::new(sessions.clone()))?;
+        registry.register(Box::new(memory_rss.clone()))?;
+        registry.register(Box::new(history_write_queue_depth.clone()))?;
+
+        Ok(Self {
+            registry,
+            calls,
+            tool_calls,
+            tool_duration,
+            active_requests,
+            requests_processed,
+            sessions,
+            memory_rss,
+            history_write_queue_depth,
+        })
+    }
+
+    /// Record the outcome and duration of one tool call
+    ///
+    /// Pass [`UNKNOWN_TOOL`] for tools that are not registered.
+    pub fn record_call(&self, tool_name: &str, success: bool, duration_ms: u64) {
+        let status = if success { "success" } else { "failure" };
+        self.calls.with_label_values(&[status]).inc();
+        self.tool_calls.with_label_values(&[tool_name, status]).inc();
+        self.tool_duration
+            .with_label_values(&[tool_name])
+            .observe(duration_ms as f64 / 1000.0);
+    }
+
+    /// Render all metrics in Prometheus text format, refreshing gauges from `snapshot`
+    pub fn render(&self, snapshot: MetricsSnapshot) -> Result<String, prometheus::Error> {
+        self.active_requests.set(snapshot.active_requests as i64);
+        // Counter mirrors the server's atomic; only ever moves forward
+        let processed = self.requests_processed.get();
+        self.requests_processed
+            .inc_by(snapshot.requests_processed.saturating_sub(processed));
+        self.sessions.set(snapshot.sessions as i64);
+        if let Some(memory_used) = snapshot.memory_used {
+            self.memory_rss.set(memory_used as i64);
+        }
+        self.history_write_queue_depth
+            .set(snapshot.history_write_queue_depth as i64);
+
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        String::from_utf8(buffer).map_err(|e| prometheus::Error::Msg(e.to_string()))
+    }
+
+    /// Content type of the rendered text format
+    pub fn content_type() -> &'static str {
+        prometheus::TEXT_FORMAT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::CallStatus;
+    use rmcp::ErrorData as McpError;
+    use rmcp::model::{CallToolResult, Content};
+
+    fn snapshot() -> MetricsSnapshot {
+        MetricsSnapshot {
+            active_requests: 2,
+            requests_processed: 3,
+            sessions: 1,
+            memory_used: Some(4096),
+            history_write_queue_depth: 0,
+        }
+    }
+
+    /// Record `result` the way `call_tool` does
+    fn record(metrics: &Metrics, tool_name: &str, result: Result<CallToolResult, McpError>, duration_ms: u64) {
+        let success = CallStatus::from_result(&result) == CallStatus::Success;
+        metrics.record_call(tool_name, success, duration_ms);
+    }
+
+    fn has_line(text: &str, line: &str) -> bool {
+        text.lines().any(|l| l == line)
+    }
+
+    #[test]
+    fn renders_outcomes_and_latency_by_tool() {
+        let metrics = Metrics::new("test").unwrap();
+        record(&metrics, "read", Ok(CallToolResult::success(vec![Content::text("ok")])), 20);
+        record(&metrics, "read", Ok(CallToolResult::error(vec![Content::text("no such file")])), 3);
+        record(&metrics, UNKNOWN_TOOL, Err(McpError::invalid_params("tool not found", None)), 1);
+
+        let text = metrics.render(snapshot()).unwrap();
+
+        for line in [
+            r#"kodegen_calls_total{status="success",category="test"} 1"#,
+            r#"kodegen_calls_total{status="failure",category="test"} 2"#,
+            r#"kodegen_tool_calls_total{status="success",tool="read",category="test"} 1"#,
+            r#"kodegen_tool_calls_total{status="failure",tool="read",category="test"} 1"#,
+            r#"kodegen_tool_calls_total{status="failure",tool="unknown",category="test"} 1"#,
+            r#"kodegen_tool_call_duration_seconds_count{tool="read",category="test"} 2"#,
+            r#"kodegen_tool_call_duration_seconds_sum{tool="read",category="test"} 0.023"#,
+            r#"kodegen_tool_call_duration_seconds_bucket{tool="read",category="test",le="0.005"} 1"#,
+            r#"kodegen_tool_call_duration_seconds_bucket{tool="read",category="test",le="0.025"} 2"#,
+            r#"kodegen_tool_call_duration_seconds_bucket{tool="read",category="test",le="+Inf"} 2"#,
+            r#"kodegen_tool_call_duration_seconds_count{tool="unknown",category="test"} 1"#,
+        ] {
+            assert!(has_line(&text, line), "missing {line:?} in:\n{text}");
+        }
+    }
+
+    #[test]
+    fn renders_gauges_from_the_snapshot() {
+        let metrics = Metrics::new("test").unwrap();
+        let text = metrics.render(snapshot()).unwrap();
+
+        for line in [
+            r#"kodegen_active_requests{category="test"} 2"#,
+            r#"kodegen_requests_processed_total{category="test"} 3"#,
+            r#"kodegen_sessions{category="test"} 1"#,
+            r#"kodegen_process_resident_memory_bytes{category="test"} 4096"#,
+            r#"kodegen_tool_history_write_queue_depth{category="test"} 0"#,
+        ] {
+            assert!(has_line(&text, line), "missing {line:?} in:\n{text}");
+        }
+    }
+
+    #[test]
+    fn requests_processed_never_moves_backwards() {
+        let metrics = Metrics::new("test").unwrap();
+        metrics.render(snapshot()).unwrap();
+        let text = metrics
+            .render(MetricsSnapshot { requests_processed: 1, memory_used: None, ..snapshot() })
+            .unwrap();
+
+        assert!(has_line(&text, r#"kodegen_requests_processed_total{category="test"} 3"#), "{text}");
+        // Unsampled memory keeps the last known value
+        assert!(has_line(&text, r#"kodegen_process_resident_memory_bytes{category="test"} 4096"#), "{text}");
+    }
+}
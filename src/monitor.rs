@@ -1,8 +1,22 @@
+use std::collections::VecDeque;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 use tokio_util::sync::CancellationToken;
+use crate::config::ServerConfig;
 use crate::memory::{get_memory_used, format_bytes};
+use crate::connection_tracker::ConnectionTracker;
+use crate::session::SessionRegistry;
+use crate::{ConnectionCleanupFn, MemoryPressureFn};
+
+/// Number of recent samples kept for `/mcp/health`
+const MEMORY_SAMPLE_HISTORY: usize = 20;
+
+/// Load shedding stops once RSS falls below this fraction of the limit,
+/// so the server does not flap around the limit
+const MEMORY_RESUME_RATIO: f64 = 0.9;
 
 struct MemorySnapshot {
     memory: u64,
@@ -10,12 +24,133 @@ struct MemorySnapshot {
     timestamp: std::time::Instant,
 }
 
+/// One RSS reading taken by the memory monitor
+#[derive(Debug, Clone, Serialize)]
+pub struct MemorySample {
+    pub timestamp: DateTime<Utc>,
+    pub rss_bytes: u64,
+    pub requests_processed: u64,
+}
+
+/// Passed to the memory pressure callback while RSS is over the limit
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryPressure {
+    pub rss_bytes: u64,
+    pub limit_bytes: u64,
+}
+
+/// Memory monitor state reported by `/mcp/health`
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryStatus {
+    pub limit_bytes: Option<u64>,
+    /// Whether new tool calls are being rejected
+    pub shedding_load: bool,
+    /// Most recent samples, oldest first
+    pub samples: Vec<MemorySample>,
+}
+
+/// Periodic RSS sampling with growth warnings and an optional hard limit
+///
+/// Above the limit the monitor switches to load shedding: `call_tool` rejects new
+/// calls with a retryable error until RSS drops below 90% of the limit. The
+/// pressure callback runs on every check while over the limit.
+pub struct MemoryMonitor {
+    interval: Duration,
+    growth_threshold: u64,
+    limit: Option<u64>,
+    on_pressure: Option<MemoryPressureFn>,
+    shedding: AtomicBool,
+    samples: parking_lot::Mutex<VecDeque<MemorySample>>,
+}
+
+impl MemoryMonitor {
+    pub fn new(config: &ServerConfig, on_pressure: Option<MemoryPressureFn>) -> Self {
+        Self {
+            interval: config.memory_check_interval(),
+            growth_threshold: config.memory_growth_warning_mb.saturating_mul(1024 * 1024),
+            limit: config.memory_limit(),
+            on_pressure,
+            shedding: AtomicBool::new(false),
+            samples: parking_lot::Mutex::new(VecDeque::with_capacity(MEMORY_SAMPLE_HISTORY)),
+        }
+    }
+
+    /// Whether new tool calls should be rejected
+    pub fn is_shedding_load(&self) -> bool {
+        self.shedding.load(Ordering::Acquire)
+    }
+
+    /// Hard RSS limit in bytes
+    pub fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    /// Time between checks (how long a rejected caller should wait before retrying)
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Most recent sample, if any
+    pub fn latest(&self) -> Option<MemorySample> {
+        self.samples.lock().back().cloned()
+    }
+
+    pub fn status(&self) -> MemoryStatus {
+        MemoryStatus {
+            limit_bytes: self.limit,
+            shedding_load: self.is_shedding_load(),
+            samples: self.samples.lock().iter().cloned().collect(),
+        }
+    }
+
+    fn record(&self, sample: MemorySample) {
+        let mut samples = self.samples.lock();
+        if samples.len() == MEMORY_SAMPLE_HISTORY {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+    }
+
+    /// Enter or leave load shedding, running the pressure callback while over the limit
+    async fn check_limit(&self, memory: u64) {
+        let Some(limit) = self.limit else {
+            return;
+        };
+
+        if memory >= limit {
+            if !self.shedding.swap(true, Ordering::AcqRel) {
+                log::warn!(
+                    "Memory usage {} exceeds limit {} - rejecting new tool calls",
+                    format_bytes(memory),
+                    format_bytes(limit)
+                );
+            }
+            if let Some(ref on_pressure) = self.on_pressure {
+                on_pressure(MemoryPressure {
+                    rss_bytes: memory,
+                    limit_bytes: limit,
+                })
+                .await;
+            }
+        } else if (memory as f64) < limit as f64 * MEMORY_RESUME_RATIO
+            && self.shedding.swap(false, Ordering::AcqRel)
+        {
+            log::info!(
+                "Memory usage {} back under limit {} - accepting tool calls",
+                format_bytes(memory),
+                format_bytes(limit)
+            );
+        }
+    }
+}
+
 pub fn spawn_memory_monitor(
+    monitor: Arc<MemoryMonitor>,
     requests_processed: Arc<AtomicU64>,
     ct: CancellationToken,
 ) {
     tokio::spawn(async move {
-        let mut interval = tokio::time::interval(Duration::from_secs(30));
+        let mut interval = tokio::time::interval(monitor.interval);
         interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
         let mut last_snapshot: Option<MemorySnapshot> = None;
@@ -31,11 +166,21 @@ pub fn spawn_memory_monitor(
                     let requests = requests_processed.load(Ordering::SeqCst);
                     let now = std::time::Instant::now();
 
+                    monitor.record(MemorySample {
+                        timestamp: Utc::now(),
+                        rss_bytes: memory,
+                        requests_processed: requests,
+                    });
+                    monitor.check_limit(memory).await;
+
+                    if monitor.growth_threshold == 0 {
+                        continue;
+                    }
+
                     if let Some(ref prev) = last_snapshot {
                         let memory_growth = memory.saturating_sub(prev.memory);
-                        let threshold = 100 * 1024 * 1024; // 100MB
 
-                        if memory_growth >= threshold {
+                        if memory_growth >= monitor.growth_threshold {
                             let elapsed = now.duration_since(prev.timestamp);
                             let requests_delta = requests.saturating_sub(prev.requests);
 
@@ -66,3 +211,132 @@ pub fn spawn_memory_monitor(
         }
     });
 }
+
+/// Longest interval between idle-connection checks
+const MAX_REAPER_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Periodically run `cleanup` for connections idle longer than `idle_timeout`
+///
+/// Covers clients that disappear without `DELETE /mcp/connection/{id}` (e.g. kodegend
+/// crashed), so their per-connection resources do not leak. Connections that still
+/// have an open session in `sessions` are left alone, however long they are idle.
+pub fn spawn_connection_reaper(
+    connections: Arc<ConnectionTracker>,
+    sessions: Arc<SessionRegistry>,
+    idle_timeout: Duration,
+    cleanup: ConnectionCleanupFn,
+    ct: CancellationToken,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval((idle_timeout / 4).clamp(Duration::from_secs(1), MAX_REAPER_INTERVAL));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            tokio::select! {
+                _ = ct.cancelled() => break,
+                _ = interval.tick() => {
+                    for connection_id in connections.idle_connections(idle_timeout) {
+                        if sessions.has_connection(&connection_id) {
+                            continue;
+                        }
+                        log::info!(
+                            "Connection {} idle for over {:?} - releasing its resources",
+                            connection_id,
+                            idle_timeout
+                        );
+                        cleanup(connection_id).await;
+                    }
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    /// Monitor with an optional limit, returning the RSS readings passed to its pressure callback
+    fn monitor(limit_mb: Option<u64>) -> (MemoryMonitor, Arc<parking_lot::Mutex<Vec<u64>>>) {
+        let pressure = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let readings = pressure.clone();
+        let on_pressure: MemoryPressureFn = Arc::new(move |pressure: MemoryPressure| {
+            assert_eq!(Some(pressure.limit_bytes), limit_mb.map(|mb| mb * MB));
+            readings.lock().push(pressure.rss_bytes);
+            Box::pin(async {})
+        });
+        let config = ServerConfig {
+            memory_limit_mb: limit_mb,
+            ..ServerConfig::default()
+        };
+        (MemoryMonitor::new(&config, Some(on_pressure)), pressure)
+    }
+
+    #[tokio::test]
+    async fn sheds_load_at_the_limit() {
+        let (monitor, pressure) = monitor(Some(100));
+
+        monitor.check_limit(99 * MB).await;
+        assert!(!monitor.is_shedding_load());
+        assert!(pressure.lock().is_empty());
+
+        monitor.check_limit(100 * MB).await;
+        assert!(monitor.is_shedding_load());
+        assert!(monitor.status().shedding_load);
+
+        // The callback runs on every check over the limit
+        monitor.check_limit(120 * MB).await;
+        assert_eq!(*pressure.lock(), [100 * MB, 120 * MB]);
+    }
+
+    #[tokio::test]
+    async fn resumes_only_below_ninety_percent() {
+        let (monitor, pressure) = monitor(Some(100));
+        monitor.check_limit(150 * MB).await;
+
+        // Between 90% and the limit the monitor keeps shedding, without pressure callbacks
+        monitor.check_limit(95 * MB).await;
+        assert!(monitor.is_shedding_load());
+        monitor.check_limit(90 * MB).await;
+        assert!(monitor.is_shedding_load());
+        assert_eq!(pressure.lock().len(), 1);
+
+        monitor.check_limit(90 * MB - 1).await;
+        assert!(!monitor.is_shedding_load());
+
+        // Climbing back into the band does not restart shedding
+        monitor.check_limit(95 * MB).await;
+        assert!(!monitor.is_shedding_load());
+    }
+
+    #[tokio::test]
+    async fn never_sheds_without_a_limit() {
+        let (monitor, pressure) = monitor(None);
+        monitor.check_limit(u64::MAX).await;
+
+        assert!(!monitor.is_shedding_load());
+        assert!(pressure.lock().is_empty());
+        assert_eq!(monitor.status().limit_bytes, None);
+    }
+
+    #[test]
+    fn keeps_the_most_recent_samples() {
+        let (monitor, _) = monitor(Some(100));
+        assert!(monitor.latest().is_none());
+
+        for rss_bytes in 0..(MEMORY_SAMPLE_HISTORY as u64 + 5) {
+            monitor.record(MemorySample {
+                timestamp: Utc::now(),
+                rss_bytes,
+                requests_processed: rss_bytes,
+            });
+        }
+
+        let samples = monitor.status().samples;
+        assert_eq!(samples.len(), MEMORY_SAMPLE_HISTORY);
+        assert_eq!(samples[0].rss_bytes, 5);
+        assert_eq!(monitor.latest().unwrap().rss_bytes, MEMORY_SAMPLE_HISTORY as u64 + 4);
+    }
+}
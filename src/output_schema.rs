@@ -0,0 +1,220 @@
+use dashmap::DashMap;
+use rmcp::ErrorData as McpError;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+/// How `call_tool` treats `structured_content` that does not match a tool's `output_schema`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputSchemaValidation {
+    /// Skip validation entirely
+    Off,
+    /// Validate and log mismatches, but return the result unchanged
+    Warn,
+    /// Validate and turn mismatches into an internal error
+    Strict,
+}
+
+impl Default for OutputSchemaValidation {
+    /// Warn in debug builds, skip in release builds
+    fn default() -> Self {
+        if cfg!(debug_assertions) {
+            Self::Warn
+        } else {
+            Self::Off
+        }
+    }
+}
+
+/// Compiled output-schema validators, cached per tool name
+///
+/// Schemas are compiled lazily on the first call that returns `structured_content`
+/// and reused for the lifetime of the server. Schemas that fail to compile are
+/// cached as `None` so the compile error is only logged once.
+#[derive(Clone, Default)]
+pub struct OutputValidator {
+    validators: Arc<DashMap<String, Option<Arc<jsonschema::Validator>>>>,
+}
+
+impl OutputValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply `mode` to a tool result's `structured_content`
+    ///
+    /// Only validates when the mode is not Off, the tool declares an output schema,
+    /// and the result carries `structured_content`. In Strict mode a mismatch is
+    /// returned as an internal error; in Warn mode it is only logged.
+    pub fn check(
+        &self,
+        mode: OutputSchemaValidation,
+        tool_name: &str,
+        schema: Option<&Map<String, Value>>,
+        structured_content: Option<&Value>,
+    ) -> Result<(), McpError> {
+        if mode == OutputSchemaValidation::Off {
+            return Ok(());
+        }
+        let (Some(schema), Some(structured_content)) = (schema, structured_content) else {
+            return Ok(());
+        };
+
+        let Err(errors) = self.validate(tool_name, schema, structured_content) else {
+            return Ok(());
+        };
+
+        log::warn!(
+            "Tool '{}' returned structured_content that does not match its output schema: {}",
+            tool_name,
+            errors.join("; ")
+        );
+
+        if mode == OutputSchemaValidation::Strict {
+            return Err(McpError::internal_error(
+                format!("Tool '{}' returned structured_content that does not match its output schema", tool_name),
+                Some(serde_json::json!({ "validation_errors": errors })),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Validate `structured_content` against the tool's declared output schema
+    ///
+    /// Returns Ok(()) if the content matches or the schema cannot be compiled.
+    /// Returns Err with one message per violation otherwise.
+    pub fn validate(
+        &self,
+        tool_name: &str,
+        schema: &Map<String, Value>,
+        structured_content: &Value,
+    ) -> Result<(), Vec<String>> {
+        let validator = self
+            .validators
+            .entry(tool_name.to_string())
+            .or_insert_with(|| {
+                match jsonschema::validator_for(&Value::Object(schema.clone())) {
+                    Ok(validator) => Some(Arc::new(validator)),
+                    Err(e) => {
+                        log::warn!("Output schema for tool '{}' does not compile: {}", tool_name, e);
+                        None
+                    }
+                }
+            })
+            .clone();
+
+        let Some(validator) = validator else {
+            return Ok(());
+        };
+
+        let errors: Vec<String> = validator
+            .iter_errors(structured_content)
+            .map(|e| format!("{} (at '{}')", e, e.instance_path()))
+            .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Map<String, Value> {
+        let Value::Object(schema) = json!({
+            "type": "object",
+            "properties": { "count": { "type": "integer" } },
+            "required": ["count"]
+        }) else {
+            unreachable!()
+        };
+        schema
+    }
+
+    #[test]
+    fn valid_payload_passes_in_every_mode() {
+        let validator = OutputValidator::new();
+        let content = json!({ "count": 3 });
+        for mode in [OutputSchemaValidation::Off, OutputSchemaValidation::Warn, OutputSchemaValidation::Strict] {
+            assert!(validator.check(mode, "count", Some(&schema()), Some(&content)).is_ok(), "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn invalid_payload_reports_each_violation() {
+        let errors = OutputValidator::new()
+            .validate("count", &schema(), &json!({ "count": "three" }))
+            .unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("/count"), "{errors:?}");
+    }
+
+    #[test]
+    fn invalid_payload_is_only_rejected_in_strict_mode() {
+        let validator = OutputValidator::new();
+        let content = json!({ "count": "three" });
+
+        assert!(validator.check(OutputSchemaValidation::Off, "count", Some(&schema()), Some(&content)).is_ok());
+        assert!(validator.check(OutputSchemaValidation::Warn, "count", Some(&schema()), Some(&content)).is_ok());
+
+        let error = validator
+            .check(OutputSchemaValidation::Strict, "count", Some(&schema()), Some(&content))
+            .unwrap_err();
+        assert!(error.message.contains("count"));
+        let data = error.data.unwrap();
+        assert_eq!(data["validation_errors"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn off_mode_does_not_compile_schemas() {
+        let validator = OutputValidator::new();
+        validator
+            .check(OutputSchemaValidation::Off, "count", Some(&schema()), Some(&json!({})))
+            .unwrap();
+        assert!(validator.validators.is_empty());
+    }
+
+    #[test]
+    fn tools_without_schema_or_content_are_not_validated() {
+        let validator = OutputValidator::new();
+        let strict = OutputSchemaValidation::Strict;
+
+        validator.check(strict, "untyped", None, Some(&json!({ "count": "three" }))).unwrap();
+        validator.check(strict, "count", Some(&schema()), None).unwrap();
+        assert!(validator.validators.is_empty());
+    }
+
+    #[test]
+    fn compiled_schema_is_cached_per_tool() {
+        let validator = OutputValidator::new();
+        validator.validate("count", &schema(), &json!({ "count": 1 })).unwrap();
+        let first = validator.validators.get("count").unwrap().clone().unwrap();
+
+        // A different schema under the same tool name is ignored: the cached validator is reused
+        let Value::Object(permissive) = json!({}) else { unreachable!() };
+        assert!(validator.validate("count", &permissive, &json!({ "count": "three" })).is_err());
+
+        let second = validator.validators.get("count").unwrap().clone().unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(validator.validators.len(), 1);
+
+        // Clones share the cache
+        assert!(validator.clone().validate("count", &permissive, &json!({})).is_err());
+    }
+
+    #[test]
+    fn schema_that_does_not_compile_is_skipped() {
+        let validator = OutputValidator::new();
+        let Value::Object(broken) = json!({ "type": 42 }) else { unreachable!() };
+
+        validator
+            .check(OutputSchemaValidation::Strict, "broken", Some(&broken), Some(&json!({})))
+            .unwrap();
+        assert!(validator.validators.get("broken").unwrap().is_none());
+    }
+}
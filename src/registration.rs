@@ -136,3 +136,36 @@ where
     
     (tool_router, prompt_router)
 }
+
+/// Register a single tool with an explicit output schema
+///
+/// Same as [`register_tool`], but replaces the output schema derived from
+/// `<T::Args as ToolArgs>::Output` with `output_schema`. Use this when a tool
+/// returns `structured_content` whose shape differs from its metadata type.
+///
+/// The schema is advertised in `tools/list` and used by `call_tool` to
+/// validate `structured_content` (see [`crate::OutputSchemaValidation`]).
+pub fn register_tool_with_output_schema<S, T>(
+    tool_router: ToolRouter<S>,
+    prompt_router: PromptRouter<S>,
+    tool: T,
+    output_schema: Arc<serde_json::Map<String, serde_json::Value>>,
+) -> (ToolRouter<S>, PromptRouter<S>)
+where
+    S: Send + Sync + 'static,
+    T: Tool,
+{
+    let tool_name = T::name();
+    
+    info!("Registering tool with output schema: {}", tool_name);
+    
+    let tool = Arc::new(tool);
+    let mut tool_route = tool.clone().arc_into_tool_route();
+    tool_route.attr.output_schema = Some(output_schema);
+    let tool_router = tool_router.with_route(tool_route);
+    let prompt_router = prompt_router.with_route(tool.arc_into_prompt_route());
+    
+    info!("✓ Successfully registered tool with output schema: {}", tool_name);
+    
+    (tool_router, prompt_router)
+}
@@ -0,0 +1,167 @@
+use crate::logging::{self, LogContext};
+use axum::extract::Request;
+use axum::http::{HeaderValue, request::Parts};
+use axum::middleware::Next;
+use axum::response::Response;
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Header carrying the request id in both directions
+pub const X_REQUEST_ID: &str = "x-request-id";
+
+/// Longest client-supplied request id accepted; longer ones are replaced
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Use the client's `X-Request-Id` if it is usable, otherwise generate one
+pub fn from_header(value: Option<&HeaderValue>) -> String {
+    value
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| is_valid(id))
+        .map(str::to_string)
+        .unwrap_or_else(generate)
+}
+
+/// Request id of a tool call
+///
+/// `forwarded` is the `X-Request-Id` value forwarded in request meta (sent by
+/// kodegend); otherwise the header of the underlying HTTP request is used, which
+/// [`propagate_request_id`] always sets.
+pub fn resolve(forwarded: Option<&str>, parts: Option<&Parts>) -> String {
+    match forwarded.filter(|id| is_valid(id)) {
+        Some(id) => id.to_string(),
+        None => from_header(parts.and_then(|parts| parts.headers.get(X_REQUEST_ID))),
+    }
+}
+
+/// Generate a new request id (32 hex characters)
+pub fn generate() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    // RandomState is seeded randomly per process, so ids differ across restarts
+    let mut high = RandomState::new().build_hasher();
+    high.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    high.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos())
+            .unwrap_or_default(),
+    );
+    let high = high.finish();
+
+    let mut low = RandomState::new().build_hasher();
+    low.write_u64(high);
+    format!("{:016x}{:016x}", high, low.finish())
+}
+
+/// Visible ASCII only, so the id is safe to log and echo back in a header
+fn is_valid(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Middleware giving every HTTP request a request id
+///
+/// The id is written to the request's `X-Request-Id` header (where `call_tool`
+/// reads it), attached to log lines emitted while the request is handled, and
+/// returned in the response's `X-Request-Id` header.
+pub async fn propagate_request_id(mut request: Request, next: Next) -> Response {
+    let request_id = from_header(request.headers().get(X_REQUEST_ID));
+    let header = HeaderValue::from_str(&request_id).expect("request ids are visible ASCII");
+    request.headers_mut().insert(X_REQUEST_ID, header.clone());
+
+    let log_context = LogContext {
+        request_id: Some(request_id),
+        ..LogContext::default()
+    };
+    let mut response = logging::with_log_context(log_context, next.run(request)).await;
+    response.headers_mut().insert(X_REQUEST_ID, header);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::HeaderMap;
+    use axum::routing::get;
+    use tower::Service;
+
+    /// Send a request through the middleware, returning the id the handler saw and the response header
+    async fn round_trip(request_id: Option<&str>) -> (String, String) {
+        let mut router = Router::new()
+            .route(
+                "/",
+                get(|headers: HeaderMap| async move {
+                    headers.get(X_REQUEST_ID).unwrap().to_str().unwrap().to_string()
+                }),
+            )
+            .layer(axum::middleware::from_fn(propagate_request_id));
+
+        let mut request = axum::http::Request::builder().uri("/");
+        if let Some(request_id) = request_id {
+            request = request.header(X_REQUEST_ID, request_id);
+        }
+        let response = router.call(request.body(Body::empty()).unwrap()).await.unwrap();
+
+        let echoed = response.headers().get(X_REQUEST_ID).unwrap().to_str().unwrap().to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (String::from_utf8(body.to_vec()).unwrap(), echoed)
+    }
+
+    fn is_generated(id: &str) -> bool {
+        id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit())
+    }
+
+    #[tokio::test]
+    async fn client_request_id_is_used_and_echoed() {
+        let (seen, echoed) = round_trip(Some("client-abc-123")).await;
+        assert_eq!(seen, "client-abc-123");
+        assert_eq!(echoed, "client-abc-123");
+    }
+
+    #[tokio::test]
+    async fn missing_request_id_is_generated_and_echoed() {
+        let (seen, echoed) = round_trip(None).await;
+        assert!(is_generated(&seen), "{seen}");
+        assert_eq!(echoed, seen);
+    }
+
+    #[tokio::test]
+    async fn unusable_request_ids_are_replaced() {
+        let oversized = "a".repeat(MAX_REQUEST_ID_LEN + 1);
+        for id in [oversized.as_str(), "has space", ""] {
+            let (seen, echoed) = round_trip(Some(id)).await;
+            assert!(is_generated(&seen), "{id:?} was kept as {seen:?}");
+            assert_eq!(echoed, seen);
+        }
+
+        let longest = "a".repeat(MAX_REQUEST_ID_LEN);
+        assert_eq!(round_trip(Some(&longest)).await.0, longest);
+    }
+
+    #[test]
+    fn non_ascii_header_values_are_replaced() {
+        let value = HeaderValue::from_bytes("caf\u{e9}".as_bytes()).unwrap();
+        assert!(is_generated(&from_header(Some(&value))));
+    }
+
+    #[test]
+    fn forwarded_id_wins_over_the_http_header() {
+        let (parts, ()) = axum::http::Request::builder()
+            .header(X_REQUEST_ID, "from-header")
+            .body(())
+            .unwrap()
+            .into_parts();
+
+        assert_eq!(resolve(Some("from-meta"), Some(&parts)), "from-meta");
+        assert_eq!(resolve(Some("bad id"), Some(&parts)), "from-header");
+        assert_eq!(resolve(None, Some(&parts)), "from-header");
+        assert!(is_generated(&resolve(None, None)));
+    }
+
+    #[test]
+    fn generated_ids_are_unique() {
+        let ids: std::collections::HashSet<_> = (0..1000).map(|_| generate()).collect();
+        assert_eq!(ids.len(), 1000);
+    }
+}
@@ -1,7 +1,26 @@
 use anyhow::Result;
-use crate::usage_tracker::{UsageTracker, UsageStats};
-use crate::tool_history::ToolHistory;
-use kodegen_mcp_schema::tool::{tool_history::ToolCallRecord, ToolStatus, add_branded_line_to_result};
+use crate::usage_tracker::{InstanceStats, UsageTracker, UsageStats};
+use crate::tool_history::{CallAnnotations, HistoryRecord, ToolHistory};
+use crate::config::ServerConfig;
+use crate::connection_identity::{ClientCertificateFingerprint, ConnectionIdSource, ConnectionIdentity};
+use crate::connection_tracker::ConnectionTracker;
+use crate::latency::{self, LatencySummary};
+use crate::logging::{self, LogContext};
+use crate::managers::{HealthStatus, ManagerHealthReport};
+use crate::metrics::{Metrics, MetricsSnapshot};
+use crate::monitor::{MemoryMonitor, MemoryStatus};
+use crate::telemetry::{Telemetry, ToolSpan};
+use crate::diagnostics::{self, ProcessSampler, ProcessStats, RuntimeStats};
+use crate::dashboard::{ConnectionSection, Dashboard, DASHBOARD_HISTORY_LIMIT};
+use crate::events::{CallStatus, EventBus, EventFilter, ToolEvent};
+use crate::audit::{AuditEvent, AuditLog, AuditOutcome};
+use crate::request_id;
+use crate::slow_calls::{SlowCall, SlowCallDetector};
+use crate::time_buckets::{self, BucketReport, Granularity};
+use crate::storage::HistoryQuery;
+use crate::output_schema::{OutputSchemaValidation, OutputValidator};
+use crate::session::{EventStore, EventStoreConfig, PersistentSessionManager, SessionClosedFn, SessionInfo, SessionLayer, SessionRegistry};
+use kodegen_mcp_schema::tool::{ToolStatus, add_branded_line_to_result};
 use thiserror::Error;
 use rmcp::{
     ErrorData as McpError, RoleServer, ServerHandler,
@@ -11,15 +30,17 @@ use rmcp::{
     transport::streamable_http_server::{
         SessionManager,
         StreamableHttpService, StreamableHttpServerConfig,
-        session::local::LocalSessionManager,
+        session::{SessionId, local::LocalSessionManager},
     },
+    transport::common::http_header::HEADER_SESSION_ID,
 };
+use std::collections::BTreeMap;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::time::Duration;
-use axum::{extract::Path, response::Json, routing::{delete, get}, Router};
+use axum::{extract::Path, response::{Html, IntoResponse, Json, sse::{KeepAlive, Sse}}, routing::{delete, get}, Router};
 use serde::Serialize;
 use tower::Service;
 use tower_http::cors::CorsLayer;
@@ -30,9 +51,13 @@ use tokio_rustls::{
 };
 
 /// Build rustls ServerConfig from PEM files
+///
+/// With `client_ca_path`, clients may present a certificate signed by one of its CAs
+/// (used to derive their connection id); clients without one are still accepted.
 fn build_rustls_config(
     cert_path: PathBuf,
     key_path: PathBuf,
+    client_ca_path: Option<&std::path::Path>,
 ) -> Result<Arc<rustls::ServerConfig>> {
     let key = PrivateKeyDer::from_pem_file(key_path)
         .map_err(|e| anyhow::anyhow!("Failed to load private key: {e}"))?;
@@ -42,8 +67,28 @@ fn build_rustls_config(
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| anyhow::anyhow!("Invalid certificate: {e}"))?;
     
-    let mut config = rustls::ServerConfig::builder()
-        .with_no_client_auth()
+    let builder = rustls::ServerConfig::builder();
+    let builder = match client_ca_path {
+        Some(client_ca_path) => {
+            let mut roots = rustls::RootCertStore::empty();
+            for ca in CertificateDer::pem_file_iter(client_ca_path)
+                .map_err(|e| anyhow::anyhow!("Failed to load client CA bundle: {e}"))?
+            {
+                let ca = ca.map_err(|e| anyhow::anyhow!("Invalid client CA certificate: {e}"))?;
+                roots
+                    .add(ca)
+                    .map_err(|e| anyhow::anyhow!("Invalid client CA certificate: {e}"))?;
+            }
+            let verifier = rustls::server::WebPkiClientVerifier::builder(Arc::new(roots))
+                .allow_unauthenticated()
+                .build()
+                .map_err(|e| anyhow::anyhow!("Failed to build client certificate verifier: {e}"))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut config = builder
         .with_single_cert(certs, key)
         .map_err(|e| anyhow::anyhow!("Failed to build TLS config: {e}"))?;
     
@@ -54,23 +99,125 @@ fn build_rustls_config(
 }
 
 
+/// Accept TLS connections on `listener` and serve `router` on them until `ct` is cancelled
+///
+/// Requests on a connection whose client presented a certificate carry its
+/// `ClientCertificateFingerprint` as an extension, for connection identity.
+fn spawn_tls_server(
+    listener: tokio::net::TcpListener,
+    tls_acceptor: TlsAcceptor,
+    router: Router,
+    ct: tokio_util::sync::CancellationToken,
+    active_requests: Arc<AtomicUsize>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            // Accept TCP connection
+            let (tcp_stream, remote_addr) = tokio::select! {
+                _ = ct.cancelled() => break,
+                result = listener.accept() => {
+                    match result {
+                        Ok(conn) => conn,
+                        Err(e) => {
+                            log::error!("Failed to accept connection: {e}");
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            // Clone for task
+            let tls_acceptor = tls_acceptor.clone();
+            let router = router.clone();
+            let active_requests = active_requests.clone();
+
+            // Spawn connection handler
+            tokio::spawn(async move {
+                // TLS handshake
+                let tls_stream = match tls_acceptor.accept(tcp_stream).await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        log::error!("TLS handshake failed from {remote_addr}: {e}");
+                        return;
+                    }
+                };
+
+                // Fingerprint the client certificate (if any) for connection identity
+                let client_certificate = tls_stream
+                    .get_ref()
+                    .1
+                    .peer_certificates()
+                    .and_then(|certs| certs.first())
+                    .map(|cert| ClientCertificateFingerprint::from_der(cert.as_ref()));
+
+                // Convert to hyper-compatible IO
+                let io = TokioIo::new(tls_stream);
+
+                // Create hyper service from router
+                let hyper_service = hyper::service::service_fn(move |mut request: hyper::Request<hyper::body::Incoming>| {
+                    if let Some(ref fingerprint) = client_certificate {
+                        request.extensions_mut().insert(fingerprint.clone());
+                    }
+                    router.clone().call(request)
+                });
+
+                // Track active request
+                let _guard = RequestGuard::new(active_requests);
+
+                // Serve connection
+                if let Err(e) = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
+                    .serve_connection_with_upgrades(io, hyper_service)
+                    .await
+                {
+                    log::debug!("Connection error from {remote_addr}: {e}");
+                }
+            });
+        }
+    })
+}
 
 /// Health check response returned by /mcp/health endpoint
 #[derive(Serialize)]
 struct HealthResponse {
     timestamp: String,
     status: HealthStatus,
+    category: String,
+    instance_id: String,
+    port: u16,
+    uptime_secs: u64,
     requests_processed: u64,
+    active_requests: usize,
+    active_sessions: usize,
     memory_used: u64,
+    memory: MemoryStatus,
+    process: Option<ProcessStats>,
+    runtime: Option<RuntimeStats>,
 }
 
-/// Health status enumeration
+/// JSON-RPC server error returned while shedding load; clients should retry later
+const SERVER_OVERLOADED: ErrorCode = ErrorCode(-32000);
+
+/// Calls returned by a /mcp/history query without `limit`
+const HISTORY_QUERY_LIMIT: usize = 1000;
+
+/// Liveness response returned by /mcp/health/live
 #[derive(Serialize)]
-enum HealthStatus {
-    #[serde(rename = "HEALTHY")]
-    Healthy,
-    #[serde(rename = "UNHEALTHY")]
-    Unhealthy,
+struct LiveResponse {
+    timestamp: String,
+    status: HealthStatus,
+}
+
+/// Readiness response returned by /mcp/health/ready
+#[derive(Serialize)]
+struct ReadyResponse {
+    timestamp: String,
+    /// Worst status among the server and its managers
+    status: HealthStatus,
+    /// Whether the server is accepting new work (false while shutting down)
+    accepting: bool,
+    /// Whether tool calls are rejected because memory is over the limit
+    shedding_load: bool,
+    managers: Vec<ManagerHealthReport>,
 }
 
 /// Server identity and configuration details
@@ -86,7 +233,47 @@ pub struct ServerIdentity {
 struct StatsResponse {
     category: String,
     connection_id: String,
+    connection_id_source: Option<ConnectionIdSource>,
     stats: UsageStats,
+    /// Per-tool latency for this connection
+    latency: BTreeMap<String, LatencySummary>,
+    /// Per-tool latency across all connections
+    global_latency: BTreeMap<String, LatencySummary>,
+    timestamp: String,
+}
+
+/// Server-wide totals returned by /mcp/stats without a connection_id
+#[derive(Serialize)]
+struct StatsTotalsResponse {
+    category: String,
+    connections: usize,
+    /// Stats summed over all connections (null before the first tracked call)
+    totals: Option<UsageStats>,
+    /// Per-tool latency across all connections
+    global_latency: BTreeMap<String, LatencySummary>,
+    timestamp: String,
+}
+
+/// Category-wide usage across restarts returned by /mcp/stats/history
+#[derive(Serialize)]
+struct StatsHistoryResponse {
+    category: String,
+    /// Every instance with a stats file, oldest first
+    instances: Vec<InstanceStats>,
+    totals: Option<UsageStats>,
+    global_latency: BTreeMap<String, LatencySummary>,
+    timestamp: String,
+}
+
+/// Hourly or daily usage buckets returned by /mcp/stats with `granularity`, `from` or `to`
+#[derive(Serialize)]
+struct StatsBucketsResponse {
+    category: String,
+    granularity: Granularity,
+    from: String,
+    to: String,
+    /// Buckets with at least one call, oldest first
+    buckets: Vec<BucketReport>,
     timestamp: String,
 }
 
@@ -95,7 +282,24 @@ struct StatsResponse {
 struct HistoryResponse {
     category: String,
     connection_id: String,
-    history: Vec<ToolCallRecord>,
+    connection_id_source: Option<ConnectionIdSource>,
+    history: Vec<HistoryRecord>,
+    timestamp: String,
+}
+
+/// Recent slow calls returned by /mcp/slow-calls endpoint
+#[derive(Serialize)]
+struct SlowCallsResponse {
+    category: String,
+    slow_calls: Vec<SlowCall>,
+    timestamp: String,
+}
+
+/// Open session list returned by /mcp/sessions endpoint
+#[derive(Serialize)]
+struct SessionsResponse {
+    category: String,
+    sessions: Vec<SessionInfo>,
     timestamp: String,
 }
 
@@ -116,6 +320,10 @@ where
     managers: Option<crate::managers::Managers>,
     session_manager: Option<Arc<SM>>,
     connection_cleanup: Option<crate::ConnectionCleanupFn>,
+    memory_pressure: Option<crate::MemoryPressureFn>,
+    output_validation: OutputSchemaValidation,
+    event_store_config: EventStoreConfig,
+    config: ServerConfig,
 }
 
 impl<SM> HttpServerBuilder<SM>
@@ -134,6 +342,10 @@ where
             managers: None,
             session_manager: None,
             connection_cleanup: None,
+            memory_pressure: None,
+            output_validation: OutputSchemaValidation::default(),
+            event_store_config: EventStoreConfig::default(),
+            config: ServerConfig::default(),
         }
     }
 
@@ -191,12 +403,57 @@ where
         self
     }
 
+    /// Set callback invoked while memory is over the configured limit (optional)
+    pub fn memory_pressure(mut self, memory_pressure: crate::MemoryPressureFn) -> Self {
+        self.memory_pressure = Some(memory_pressure);
+        self
+    }
+
+    /// Set output-schema validation mode (default: Warn in debug builds, Off in release)
+    pub fn output_validation(mut self, output_validation: OutputSchemaValidation) -> Self {
+        self.output_validation = output_validation;
+        self
+    }
+
+    /// Set SSE event retention used to replay messages on `Last-Event-ID` reconnects
+    pub fn event_store_config(mut self, event_store_config: EventStoreConfig) -> Self {
+        self.event_store_config = event_store_config;
+        self
+    }
+
+    /// Set runtime configuration (SSE keep-alive, drain timeout, listen backlog)
+    pub fn server_config(mut self, config: ServerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
     /// Build the HttpServer, validating that all required fields are set
     ///
     /// Returns Err if any required field is missing.
     pub fn build(self) -> Result<HttpServer<SM>, String> {
+        let server_identity = self.server_identity.ok_or("server_identity is required")?;
+        let metrics = Metrics::new(&server_identity.category)
+            .map_err(|e| format!("Failed to create metrics registry: {e}"))?;
+        // Tracing is best-effort: a bad collector endpoint must not keep the server from starting
+        let telemetry = match &self.config.otlp_endpoint {
+            Some(endpoint) => Telemetry::otlp(endpoint, &server_identity.category, &server_identity.instance_id)
+                .unwrap_or_else(|e| {
+                    log::warn!("{:#} - continuing without trace export", e);
+                    Telemetry::disabled()
+                }),
+            None => Telemetry::disabled(),
+        };
+        let audit_log = if self.config.audit_log {
+            let path = self.config.audit_log_file(&server_identity.category);
+            let audit_log = AuditLog::open(path, &server_identity.category, &server_identity.instance_id)
+                .map_err(|e| format!("{e:#}"))?;
+            Some(Arc::new(audit_log))
+        } else {
+            None
+        };
+
         Ok(HttpServer {
-            server_identity: self.server_identity.ok_or("server_identity is required")?,
+            server_identity,
             tool_router: self.tool_router.ok_or("tool_router is required")?,
             prompt_router: self.prompt_router.ok_or("prompt_router is required")?,
             usage_tracker: self.usage_tracker.ok_or("usage_tracker is required")?,
@@ -207,6 +464,19 @@ where
             requests_processed: Arc::new(AtomicU64::new(0)),
             session_manager: self.session_manager.ok_or("session_manager is required")?,
             connection_cleanup: self.connection_cleanup,
+            memory_monitor: Arc::new(MemoryMonitor::new(&self.config, self.memory_pressure)),
+            slow_calls: Arc::new(SlowCallDetector::new(&self.config)),
+            audit_log,
+            process_sampler: Arc::new(ProcessSampler::new()),
+            events: Arc::new(EventBus::new()),
+            output_validation: self.output_validation,
+            output_validator: OutputValidator::new(),
+            event_store: Arc::new(EventStore::new(self.event_store_config)),
+            session_registry: Arc::new(SessionRegistry::new()),
+            connections: Arc::new(ConnectionTracker::new()),
+            config: self.config,
+            metrics: Arc::new(metrics),
+            telemetry,
         })
     }
 }
@@ -239,6 +509,19 @@ where
     requests_processed: Arc<AtomicU64>,
     session_manager: Arc<SM>,
     connection_cleanup: Option<crate::ConnectionCleanupFn>,
+    memory_monitor: Arc<MemoryMonitor>,
+    slow_calls: Arc<SlowCallDetector>,
+    audit_log: Option<Arc<AuditLog>>,
+    process_sampler: Arc<ProcessSampler>,
+    events: Arc<EventBus>,
+    output_validation: OutputSchemaValidation,
+    output_validator: OutputValidator,
+    event_store: Arc<EventStore>,
+    session_registry: Arc<SessionRegistry>,
+    connections: Arc<ConnectionTracker>,
+    config: ServerConfig,
+    metrics: Arc<Metrics>,
+    telemetry: Telemetry,
 }
 
 // Manual Clone implementation for HttpServer
@@ -260,6 +543,19 @@ where
             requests_processed: self.requests_processed.clone(),
             session_manager: self.session_manager.clone(),
             connection_cleanup: self.connection_cleanup.clone(),
+            memory_monitor: self.memory_monitor.clone(),
+            slow_calls: self.slow_calls.clone(),
+            audit_log: self.audit_log.clone(),
+            process_sampler: self.process_sampler.clone(),
+            events: self.events.clone(),
+            output_validation: self.output_validation,
+            output_validator: self.output_validator.clone(),
+            event_store: self.event_store.clone(),
+            session_registry: self.session_registry.clone(),
+            connections: self.connections.clone(),
+            config: self.config.clone(),
+            metrics: self.metrics.clone(),
+            telemetry: self.telemetry.clone(),
         }
     }
 }
@@ -324,12 +620,167 @@ where
         builder.build().expect("All required fields provided")
     }
 
+    /// Wrap the configured session manager in the server's `SessionLayer`
+    ///
+    /// Sessions that end (closed, expired or evicted) release their connection's
+    /// resources, keeping its stats and history. Skipped once `ct` is cancelled,
+    /// since managers are shut down as a whole.
+    fn session_layer(&self, ct: &tokio_util::sync::CancellationToken) -> SessionLayer<SM> {
+        let on_close: SessionClosedFn = {
+            let server = self.clone();
+            let ct = ct.clone();
+            Arc::new(move |info: SessionInfo| {
+                let server = server.clone();
+                let shutting_down = ct.is_cancelled();
+                Box::pin(async move {
+                    if !shutting_down {
+                        server.cleanup_session_connection(info).await;
+                    }
+                })
+            })
+        };
+
+        SessionLayer::new(
+            self.session_manager.clone(),
+            self.event_store.clone(),
+            self.session_registry.clone(),
+        )
+        .with_limit(self.config.session_limit())
+        .on_close(on_close)
+    }
+
+    /// Release an ended session's connection, unless another session still uses it
+    async fn cleanup_session_connection(&self, info: SessionInfo) {
+        let Some(connection_id) = info.connection_id else {
+            return;
+        };
+        if self.session_registry.has_connection(&connection_id) {
+            log::debug!(
+                "Session {} ended; connection {} still has open sessions",
+                info.session_id,
+                connection_id
+            );
+            return;
+        }
+        log::info!("Session {} ended - releasing connection {}", info.session_id, connection_id);
+        self.release_connection(&connection_id).await;
+    }
+
+    /// Start the idle connection reaper, if an idle timeout is configured
+    fn spawn_connection_reaper(&self, ct: &tokio_util::sync::CancellationToken) {
+        let Some(idle_timeout) = self.config.connection_idle_timeout() else {
+            log::debug!("Idle connection cleanup disabled");
+            return;
+        };
+
+        let cleanup: crate::ConnectionCleanupFn = {
+            let server = self.clone();
+            Arc::new(move |connection_id: String| {
+                let server = server.clone();
+                Box::pin(async move { server.release_connection(&connection_id).await })
+            })
+        };
+
+        crate::monitor::spawn_connection_reaper(
+            self.connections.clone(),
+            self.session_registry.clone(),
+            idle_timeout,
+            cleanup,
+            ct.clone(),
+        );
+    }
+
+    /// The session manager as a `PersistentSessionManager`, if that is the configured backend
+    fn persistent_session_manager(&self) -> Option<&PersistentSessionManager>
+    where
+        SM: std::any::Any,
+    {
+        (self.session_manager.as_ref() as &dyn std::any::Any).downcast_ref::<PersistentSessionManager>()
+    }
+
+    /// Let a `PersistentSessionManager` serve sessions it restores from disk
+    ///
+    /// Restored sessions are adopted by `layer`, so they count toward the session cap
+    /// and end through the same close path as sessions created over HTTP.
+    /// No-op for other session managers.
+    fn attach_session_restorer(&self, layer: &Arc<SessionLayer<SM>>, ct: &tokio_util::sync::CancellationToken)
+    where
+        SM: std::any::Any,
+    {
+        use rmcp::ServiceExt;
+
+        let Some(persistent) = self.persistent_session_manager() else {
+            return;
+        };
+
+        let server = self.clone();
+        // Weak: the layer owns the session manager, which owns this restorer
+        let layer = Arc::downgrade(layer);
+        let ct_for_sessions = ct.clone();
+        persistent.attach(
+            Arc::new(move |session_id, transport| {
+                let server = server.clone();
+                let layer = layer.clone();
+                let ct = ct_for_sessions.child_token();
+                Box::pin(async move {
+                    let Some(layer) = layer.upgrade() else {
+                        return;
+                    };
+                    let transport = match layer.adopt(&session_id, transport).await {
+                        Ok(transport) => transport,
+                        Err(e) => {
+                            log::warn!("Not restoring session {}: {}", session_id, e);
+                            return;
+                        }
+                    };
+                    drop(layer);
+
+                    match server.serve_with_ct(transport, ct).await {
+                        Ok(running) => {
+                            let _ = running.waiting().await;
+                        }
+                        Err(e) => log::error!("Failed to serve restored session: {e}"),
+                    }
+                })
+            }),
+            ct.clone(),
+        );
+
+        log::info!(
+            "Persistent sessions enabled ({} restorable sessions in {})",
+            persistent.persisted_count(),
+            persistent.sessions_file_path().display()
+        );
+    }
+
+    /// Check `structured_content` against the tool's declared output schema
+    ///
+    /// See `OutputValidator::check` for how each validation mode treats a mismatch.
+    fn validate_structured_content(
+        &self,
+        tool_name: &str,
+        call_result: &CallToolResult,
+    ) -> Result<(), McpError> {
+        let output_schema = self.tool_router
+            .map
+            .get(tool_name)
+            .and_then(|route| route.attr.output_schema.clone());
+
+        self.output_validator.check(
+            self.output_validation,
+            tool_name,
+            output_schema.as_deref(),
+            call_result.structured_content.as_ref(),
+        )
+    }
+
     /// Handle health check requests
     ///
     /// Returns JSON response with timestamp, status, requests processed count, and memory usage.
     async fn handle_health(&self) -> Json<HealthResponse> {
         use chrono::Utc;
-        let memory_used = crate::memory::get_memory_used().unwrap_or(0);
+        let process = self.process_sampler.sample();
+        let memory_used = process.map_or(0, |process| process.rss_bytes);
         let status = if memory_used > 0 {
             HealthStatus::Healthy
         } else {
@@ -339,11 +790,69 @@ where
         Json(HealthResponse {
             timestamp: Utc::now().to_rfc3339(),
             status,
+            category: self.server_identity.category.clone(),
+            instance_id: self.server_identity.instance_id.clone(),
+            port: self.server_identity.port,
+            uptime_secs: self.usage_tracker.uptime().as_secs(),
             requests_processed: self.requests_processed.load(Ordering::SeqCst),
+            active_requests: self.active_requests.load(Ordering::SeqCst),
+            active_sessions: self.session_registry.len(),
             memory_used,
+            memory: self.memory_monitor.status(),
+            process,
+            runtime: diagnostics::runtime_stats(),
         })
     }
 
+    /// Handle liveness probes
+    ///
+    /// Answers as long as the runtime can serve requests; a failure means the
+    /// process should be restarted.
+    async fn handle_health_live(&self) -> Json<LiveResponse> {
+        Json(LiveResponse {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            status: HealthStatus::Healthy,
+        })
+    }
+
+    /// Handle readiness probes
+    ///
+    /// Returns 503 while shutting down, while shedding load over the memory limit,
+    /// or when any manager reports Unhealthy.
+    /// Degraded managers are listed but keep the server ready.
+    async fn handle_health_ready(
+        &self,
+        ct: &tokio_util::sync::CancellationToken,
+    ) -> (axum::http::StatusCode, Json<ReadyResponse>) {
+        let accepting = !ct.is_cancelled();
+        let shedding_load = self.memory_monitor.is_shedding_load();
+        let managers = self.managers.health_check().await;
+
+        let manager_status = managers
+            .iter()
+            .map(|report| report.health.status)
+            .max()
+            .unwrap_or(HealthStatus::Healthy);
+        let status = if accepting && !shedding_load { manager_status } else { HealthStatus::Unhealthy };
+
+        let code = if status == HealthStatus::Unhealthy {
+            axum::http::StatusCode::SERVICE_UNAVAILABLE
+        } else {
+            axum::http::StatusCode::OK
+        };
+
+        (
+            code,
+            Json(ReadyResponse {
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                status,
+                accepting,
+                shedding_load,
+                managers,
+            }),
+        )
+    }
+
     /// Handle connection cleanup notification
     ///
     /// Called when a connection drops to cleanup connection-specific resources.
@@ -360,12 +869,7 @@ where
         // Remove connection-specific tool history
         self.tool_history.remove_connection(&connection_id);
 
-        // Invoke cleanup handler if registered
-        if let Some(cleanup) = &self.connection_cleanup {
-            cleanup(connection_id.clone()).await;
-        } else {
-            log::debug!("No cleanup handler registered for this server");
-        }
+        self.release_connection(&connection_id).await;
 
         let elapsed = start.elapsed();
         log::info!(
@@ -375,6 +879,21 @@ where
         );
     }
 
+    /// Release a connection's resources, keeping its stats and history
+    ///
+    /// Used when a connection goes idle or its last session ends. Only an explicit
+    /// `DELETE /mcp/connection/{id}` also removes its stats and history.
+    async fn release_connection(&self, connection_id: &str) {
+        self.connections.remove(connection_id);
+
+        // Invoke cleanup handler if registered
+        if let Some(cleanup) = &self.connection_cleanup {
+            cleanup(connection_id.to_string()).await;
+        } else {
+            log::debug!("No cleanup handler registered for this server");
+        }
+    }
+
     /// Handle statistics endpoint requests
     async fn handle_stats(
         &self,
@@ -393,14 +912,98 @@ where
                 )
             })?;
 
+        let global_latency = self.usage_tracker.get_global_latency();
+
         Ok(Json(StatsResponse {
             category: self.server_identity.category.clone(),
+            connection_id_source: self.connections.source(&connection_id),
             connection_id,
+            latency: latency::summarize(&stats.tool_latency),
+            global_latency: latency::summarize(&global_latency),
             stats,
             timestamp: Utc::now().to_rfc3339(),
         }))
     }
 
+    /// Handle stats requests without a connection_id: totals across all connections
+    async fn handle_stats_totals(&self) -> Json<StatsTotalsResponse> {
+        let all_stats = self.usage_tracker.get_all_stats();
+
+        Json(StatsTotalsResponse {
+            category: self.server_identity.category.clone(),
+            connections: all_stats.len(),
+            totals: UsageStats::merged(all_stats.values()),
+            global_latency: latency::summarize(&self.usage_tracker.get_global_latency()),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+
+    /// Handle stats requests for time buckets (`granularity`, `from`, `to`, optional `tool`)
+    ///
+    /// Defaults to hourly buckets over the last 24 hours, or daily buckets over the last 30 days.
+    async fn handle_stats_buckets(
+        &self,
+        params: &std::collections::HashMap<String, String>,
+    ) -> Result<Json<StatsBucketsResponse>, (axum::http::StatusCode, String)> {
+        let bad_request = |message: String| (axum::http::StatusCode::BAD_REQUEST, message);
+
+        if params.contains_key("connection_id") {
+            return Err(bad_request(
+                "Time buckets are server-wide; connection_id cannot be combined with granularity, from or to".to_string(),
+            ));
+        }
+        let granularity: Granularity = match params.get("granularity") {
+            Some(granularity) => granularity.parse().map_err(bad_request)?,
+            None => Granularity::Hour,
+        };
+        let to = match params.get("to") {
+            Some(to) => time_buckets::parse_timestamp(to).map_err(bad_request)?,
+            None => chrono::Utc::now(),
+        };
+        let from = match params.get("from") {
+            Some(from) => time_buckets::parse_timestamp(from).map_err(bad_request)?,
+            None => {
+                let span = match granularity {
+                    Granularity::Hour => chrono::Duration::hours(24),
+                    Granularity::Day => chrono::Duration::days(30),
+                };
+                to.checked_sub_signed(span)
+                    .ok_or_else(|| bad_request(format!("to ({}) is out of range", to.to_rfc3339())))?
+            }
+        };
+        if from >= to {
+            return Err(bad_request(format!("from ({}) must be before to ({})", from.to_rfc3339(), to.to_rfc3339())));
+        }
+
+        Ok(Json(StatsBucketsResponse {
+            category: self.server_identity.category.clone(),
+            granularity,
+            buckets: self
+                .usage_tracker
+                .get_buckets(granularity, from, to, params.get("tool").map(String::as_str)),
+            from: from.to_rfc3339(),
+            to: to.to_rfc3339(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }))
+    }
+
+    /// Handle stats history requests: every instance's stats file for this category merged
+    async fn handle_stats_history(&self) -> Result<Json<StatsHistoryResponse>, (axum::http::StatusCode, String)> {
+        let usage_tracker = self.usage_tracker.clone();
+        let category = self.server_identity.category.clone();
+        let history = tokio::task::spawn_blocking(move || usage_tracker.category_history(&category))
+            .await
+            .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read stats files: {}", e)))?;
+
+        Ok(Json(StatsHistoryResponse {
+            category: self.server_identity.category.clone(),
+            instances: history.instances,
+            totals: history.totals,
+            global_latency: latency::summarize(&history.global_latency),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }))
+    }
+
     /// Handle history endpoint requests
     async fn handle_history(
         &self,
@@ -410,7 +1013,7 @@ where
 
         // Get history for this specific connection
         let history = self.tool_history
-            .get_history_for_connection(&connection_id)
+            .get_annotated_history_for_connection(&connection_id)
             .ok_or_else(|| {
                 (
                     axum::http::StatusCode::NOT_FOUND,
@@ -420,12 +1023,470 @@ where
 
         Ok(Json(HistoryResponse {
             category: self.server_identity.category.clone(),
+            connection_id_source: self.connections.source(&connection_id),
             connection_id,
             history,
             timestamp: Utc::now().to_rfc3339(),
         }))
     }
 
+    /// Handle history requests with `tool`, `from`, `to` or `limit`: query persisted calls
+    ///
+    /// Unlike the in-memory history this reaches calls from before a restart and
+    /// beyond the per-connection window, up to what storage keeps.
+    async fn handle_history_query(
+        &self,
+        connection_id: String,
+        params: &std::collections::HashMap<String, String>,
+    ) -> Result<Json<HistoryResponse>, (axum::http::StatusCode, String)> {
+        let bad_request = |message: String| (axum::http::StatusCode::BAD_REQUEST, message);
+        let timestamp = |key: &str| {
+            params
+                .get(key)
+                .map(|value| time_buckets::parse_timestamp(value).map_err(bad_request))
+                .transpose()
+        };
+
+        let query = HistoryQuery {
+            connection_id: Some(connection_id.clone()),
+            tool_name: params.get("tool").cloned(),
+            from: timestamp("from")?,
+            to: timestamp("to")?,
+            limit: match params.get("limit") {
+                Some(limit) => limit.parse().map_err(|_| bad_request(format!("Invalid limit: {}", limit)))?,
+                None => HISTORY_QUERY_LIMIT,
+            },
+        };
+        let calls = self.tool_history.query(query).await.map_err(|e| {
+            (axum::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to query tool history: {:#}", e))
+        })?;
+
+        Ok(Json(HistoryResponse {
+            category: self.server_identity.category.clone(),
+            connection_id_source: self.connections.source(&connection_id),
+            connection_id,
+            history: calls.into_iter().map(|call| call.call).collect(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }))
+    }
+
+    /// Handle dashboard requests: one self-contained HTML page (no external assets)
+    async fn handle_dashboard(&self, refresh_secs: Option<u64>) -> Html<String> {
+        let health = serde_json::to_value(&self.handle_health().await.0).unwrap_or_default();
+
+        let mut connections: Vec<ConnectionSection> = self
+            .usage_tracker
+            .get_all_stats()
+            .into_iter()
+            .map(|(connection_id, stats)| {
+                let recent = self
+                    .tool_history
+                    .get_annotated_history_for_connection(&connection_id)
+                    .map(|history| history.into_iter().rev().take(DASHBOARD_HISTORY_LIMIT).collect())
+                    .unwrap_or_default();
+                ConnectionSection {
+                    source: self.connections.source(&connection_id),
+                    connection_id,
+                    stats,
+                    recent,
+                }
+            })
+            .collect();
+        connections.sort_by_key(|connection| std::cmp::Reverse(connection.stats.last_used));
+
+        let mut tools = self.tool_router.list_all();
+        tools.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Html(
+            Dashboard {
+                category: self.server_identity.category.clone(),
+                instance_id: self.server_identity.instance_id.clone(),
+                port: self.server_identity.port,
+                generated_at: chrono::Utc::now(),
+                refresh_secs,
+                health,
+                connections,
+                tools,
+            }
+            .render(),
+        )
+    }
+
+    /// Handle slow-call listing requests (newest first)
+    async fn handle_slow_calls(
+        &self,
+        connection_id: Option<&str>,
+        tool_name: Option<&str>,
+        limit: usize,
+    ) -> Json<SlowCallsResponse> {
+        Json(SlowCallsResponse {
+            category: self.server_identity.category.clone(),
+            slow_calls: self.slow_calls.recent(connection_id, tool_name, limit),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        })
+    }
+
+    /// Handle Prometheus scrape requests
+    async fn handle_metrics(&self) -> axum::response::Response {
+
+        let snapshot = MetricsSnapshot {
+            active_requests: self.active_requests.load(Ordering::SeqCst),
+            requests_processed: self.requests_processed.load(Ordering::SeqCst),
+            sessions: self.session_registry.len(),
+            memory_used: crate::memory::get_memory_used(),
+            history_write_queue_depth: self.tool_history.write_queue_depth(),
+        };
+
+        match self.metrics.render(snapshot) {
+            Ok(body) => (
+                [(axum::http::header::CONTENT_TYPE, Metrics::content_type())],
+                body,
+            )
+                .into_response(),
+            Err(e) => {
+                log::error!("Failed to render metrics: {}", e);
+                (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            }
+        }
+    }
+
+    /// Handle session list requests
+    async fn handle_sessions(&self) -> Json<SessionsResponse> {
+        use chrono::Utc;
+
+        Json(SessionsResponse {
+            category: self.server_identity.category.clone(),
+            sessions: self.session_registry.list(),
+            timestamp: Utc::now().to_rfc3339(),
+        })
+    }
+
+    /// Handle session termination requests
+    ///
+    /// Closes the session in the session manager, drops its retained SSE messages and,
+    /// if its tool calls were attributed to a connection, cleans up that connection too.
+    /// A persisted session that is not live is removed from disk without being restored.
+    async fn handle_session_delete(&self, session_id: String) -> axum::http::StatusCode {
+        log::info!("DELETE /mcp/sessions/{}", session_id);
+
+        let session_id = SessionId::from(session_id);
+        // Removing the entry first keeps SessionLayer from running its own close callback
+        let info = self.session_registry.remove(&session_id);
+        // A persisted session that is not live is forgotten without restoring it first
+        let persistent = self.persistent_session_manager();
+        let is_live = match persistent {
+            Some(persistent) => persistent.is_live(&session_id).await,
+            None => match self.session_manager.has_session(&session_id).await {
+                Ok(is_live) => is_live,
+                Err(e) => {
+                    log::warn!("Failed to look up session {}: {}", session_id, e);
+                    false
+                }
+            },
+        };
+        let forgotten = !is_live && persistent.is_some_and(|persistent| persistent.forget(&session_id));
+
+        if info.is_none() && !is_live && !forgotten {
+            return axum::http::StatusCode::NOT_FOUND;
+        }
+
+        if is_live && let Err(e) = self.session_manager.close_session(&session_id).await {
+            log::error!("Failed to close session {}: {}", session_id, e);
+            return axum::http::StatusCode::INTERNAL_SERVER_ERROR;
+        }
+        self.event_store.remove_session(&session_id);
+
+        if let Some(info) = info {
+            self.cleanup_session_connection(info).await;
+        }
+
+        axum::http::StatusCode::NO_CONTENT
+    }
+
+    /// Build the Axum router: admin endpoints plus the MCP service nested under /mcp
+    fn build_router<M>(
+        &self,
+        http_service: StreamableHttpService<Self, M>,
+        ct: &tokio_util::sync::CancellationToken,
+    ) -> Router
+    where
+        M: SessionManager,
+    {
+        // Create health handler closure
+        let health_handler = {
+            let server = self.clone();
+            move || {
+                let server = server.clone();
+                async move { server.handle_health().await }
+            }
+        };
+
+        // Create liveness handler closure
+        let health_live_handler = {
+            let server = self.clone();
+            move || {
+                let server = server.clone();
+                async move { server.handle_health_live().await }
+            }
+        };
+
+        // Create readiness handler closure
+        let health_ready_handler = {
+            let server = self.clone();
+            let ct = ct.clone();
+            move || {
+                let server = server.clone();
+                let ct = ct.clone();
+                async move { server.handle_health_ready(&ct).await }
+            }
+        };
+
+        // Create stats handler closure
+        let stats_handler = {
+            let server = self.clone();
+            move |axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>| {
+                let server = server.clone();
+                async move {
+                    if ["granularity", "from", "to"].iter().any(|key| params.contains_key(*key)) {
+                        return server.handle_stats_buckets(&params).await.into_response();
+                    }
+                    // Without a connection_id, report totals across all connections
+                    match params.get("connection_id") {
+                        Some(connection_id) => server.handle_stats(connection_id.clone()).await.into_response(),
+                        None => server.handle_stats_totals().await.into_response(),
+                    }
+                }
+            }
+        };
+
+        // Create stats history handler closure
+        let stats_history_handler = {
+            let server = self.clone();
+            move || {
+                let server = server.clone();
+                async move { server.handle_stats_history().await }
+            }
+        };
+
+        // Create history handler closure
+        let history_handler = {
+            let server = self.clone();
+            move |axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>| {
+                let server = server.clone();
+                async move {
+                    let connection_id = params.get("connection_id")
+                        .ok_or_else(|| (
+                            axum::http::StatusCode::BAD_REQUEST,
+                            "Missing required parameter: connection_id".to_string(),
+                        ))?;
+                    if ["tool", "from", "to", "limit"].iter().any(|key| params.contains_key(*key)) {
+                        return server.handle_history_query(connection_id.clone(), &params).await;
+                    }
+                    server.handle_history(connection_id.clone()).await
+                }
+            }
+        };
+
+        // Create dashboard handler closure
+        let dashboard_handler = {
+            let server = self.clone();
+            move |axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>| {
+                let server = server.clone();
+                async move {
+                    let refresh_secs = params
+                        .get("refresh")
+                        .and_then(|secs| secs.parse().ok())
+                        .filter(|secs| *secs > 0);
+                    server.handle_dashboard(refresh_secs).await
+                }
+            }
+        };
+
+        // Create live event stream handler closure (ends on shutdown)
+        let events_handler = {
+            let server = self.clone();
+            let ct = ct.clone();
+            move |axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>| {
+                let server = server.clone();
+                let ct = ct.clone();
+                async move {
+                    let filter = EventFilter {
+                        connection_id: params.get("connection_id").cloned(),
+                        tool_name: params.get("tool").cloned(),
+                    };
+                    let sse = Sse::new(server.events.sse_stream(filter, ct));
+                    match server.config.sse_keep_alive() {
+                        Some(interval) => sse.keep_alive(KeepAlive::new().interval(interval)).into_response(),
+                        None => sse.into_response(),
+                    }
+                }
+            }
+        };
+
+        // Create slow calls handler closure
+        let slow_calls_handler = {
+            let server = self.clone();
+            move |axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>| {
+                let server = server.clone();
+                async move {
+                    let limit = match params.get("limit") {
+                        Some(limit) => limit.parse().map_err(|_| (
+                            axum::http::StatusCode::BAD_REQUEST,
+                            format!("Invalid limit: {}", limit),
+                        ))?,
+                        None => usize::MAX,
+                    };
+                    Ok::<_, (axum::http::StatusCode, String)>(
+                        server
+                            .handle_slow_calls(
+                                params.get("connection_id").map(String::as_str),
+                                params.get("tool").map(String::as_str),
+                                limit,
+                            )
+                            .await,
+                    )
+                }
+            }
+        };
+
+        // Create connection delete handler closure
+        let connection_delete_handler = {
+            let server = self.clone();
+            move |Path(connection_id): Path<String>| {
+                let server = server.clone();
+                async move {
+                    server.handle_connection_delete(connection_id).await;
+                    axum::http::StatusCode::NO_CONTENT
+                }
+            }
+        };
+
+        // Create metrics handler closure
+        let metrics_handler = {
+            let server = self.clone();
+            move || {
+                let server = server.clone();
+                async move { server.handle_metrics().await }
+            }
+        };
+
+        // Create sessions handler closure
+        let sessions_handler = {
+            let server = self.clone();
+            move || {
+                let server = server.clone();
+                async move { server.handle_sessions().await }
+            }
+        };
+
+        // Create session delete handler closure
+        let session_delete_handler = {
+            let server = self.clone();
+            move |Path(session_id): Path<String>| {
+                let server = server.clone();
+                async move { server.handle_session_delete(session_id).await }
+            }
+        };
+
+        // Build Axum router with CORS
+        let router = Router::new()
+            .route("/mcp/health", get(health_handler))
+            .route("/mcp/health/live", get(health_live_handler))
+            .route("/mcp/health/ready", get(health_ready_handler))
+            .route("/mcp/stats", get(stats_handler))
+            .route("/mcp/stats/history", get(stats_history_handler))
+            .route("/mcp/history", get(history_handler))
+            .route("/mcp/slow-calls", get(slow_calls_handler))
+            .route("/mcp/events", get(events_handler))
+            .route("/mcp/dashboard", get(dashboard_handler))
+            .route("/mcp/connection/{connection_id}", delete(connection_delete_handler))
+            .route("/metrics", get(metrics_handler))
+            .route("/mcp/sessions", get(sessions_handler))
+            .route("/mcp/sessions/{session_id}", delete(session_delete_handler))
+            .route(
+                "/mcp/log-levels",
+                get(handle_log_levels_get).put(handle_log_levels_put).delete(handle_log_levels_delete),
+            )
+            .nest_service("/mcp", http_service);
+
+        // Trace MCP requests when OpenTelemetry export is configured
+        let router = if self.telemetry.is_enabled() {
+            let server = self.clone();
+            router.layer(axum::middleware::from_fn(
+                move |request: axum::extract::Request, next: axum::middleware::Next| {
+                    let server = server.clone();
+                    async move { server.trace_mcp_request(request, next).await }
+                },
+            ))
+        } else {
+            router
+        };
+
+        // Give every request an X-Request-Id (outermost, so traced requests carry it too)
+        router
+            .layer(axum::middleware::from_fn(request_id::propagate_request_id))
+            .layer(CorsLayer::permissive())
+    }
+
+    /// Wrap a request to the MCP endpoint in a JSON-RPC request span
+    ///
+    /// The span context is stored in the request extensions, where `call_tool`
+    /// picks it up as the parent of the tool call span.
+    async fn trace_mcp_request(
+        &self,
+        request: axum::extract::Request,
+        next: axum::middleware::Next,
+    ) -> axum::response::Response {
+
+        let path = request.uri().path();
+        if path != "/mcp" && path != "/mcp/" {
+            return next.run(request).await;
+        }
+
+        let (mut parts, body) = request.into_parts();
+        let parent = Telemetry::extract_context(&parts.headers);
+        let session_id = parts
+            .headers
+            .get(HEADER_SESSION_ID)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string());
+
+        // Buffer POST bodies to name the span after the JSON-RPC method (up to a size cap)
+        let (body, rpc_method, request_id) = if parts.method == axum::http::Method::POST {
+            match crate::telemetry::buffer_body(body).await {
+                Ok((body, Some(bytes))) => {
+                    let (rpc_method, request_id) = crate::telemetry::jsonrpc_method_and_id(&bytes);
+                    (body, rpc_method, request_id)
+                }
+                Ok((body, None)) => (body, None, None),
+                Err(e) => {
+                    return (
+                        axum::http::StatusCode::BAD_REQUEST,
+                        format!("Failed to read request body: {e}"),
+                    )
+                        .into_response();
+                }
+            }
+        } else {
+            (body, None, None)
+        };
+
+        let Some(cx) = self.telemetry.start_request_span(
+            &parent,
+            parts.method.as_str(),
+            rpc_method.as_deref(),
+            request_id.as_deref(),
+            session_id.as_deref(),
+        ) else {
+            return next.run(axum::extract::Request::from_parts(parts, body)).await;
+        };
+
+        // rmcp hands the request parts to the tool call, carrying the span context with them
+        parts.extensions.insert(cx.clone());
+        let response = next.run(axum::extract::Request::from_parts(parts, body)).await;
+        crate::telemetry::end_request_span_after(cx, response)
+    }
+
     /// Create and serve HTTP server with optional TLS configuration
     ///
     /// Returns ServerHandle for graceful shutdown coordination.
@@ -443,6 +1504,9 @@ where
         use tokio_util::sync::CancellationToken;
 
         let managers = self.managers.clone();
+        let telemetry = self.telemetry.clone();
+        let audit_log = self.audit_log.clone();
+        let sessions = self.session_manager.clone();
         let protocol = if tls_config.is_some() { "https" } else { "http" };
 
         log::info!("Starting HTTP server on {protocol}://{addr}");
@@ -477,8 +1541,8 @@ where
         socket.bind(addr)
             .map_err(|e| anyhow::anyhow!("Failed to bind to {}: {}", addr, e))?;
 
-        // Convert to listener with backlog of 1024 (standard for HTTP servers)
-        let listener = socket.listen(1024)
+        // Convert to listener with configured backlog (default 1024, standard for HTTP servers)
+        let listener = socket.listen(self.config.listen_backlog)
             .map_err(|e| anyhow::anyhow!("Failed to listen on {}: {}", addr, e))?;
 
         log::info!("Successfully bound to {} with SO_REUSEADDR enabled", addr);
@@ -499,156 +1563,54 @@ where
         let ct = CancellationToken::new();
 
         // Session shutdown is handled by rmcp via cancellation_token in StreamableHttpServerConfig
-        let session_manager = self.session_manager.clone();
+        // SessionLayer adds Last-Event-ID replay, session tracking and the session cap
+        let session_manager = Arc::new(self.session_layer(&ct));
 
         // Spawn background memory monitor
         crate::monitor::spawn_memory_monitor(
+            self.memory_monitor.clone(),
             self.requests_processed.clone(),
             ct.clone(),
         );
 
-        // Create service factory closure
-        let service_factory = {
-            let server = self.clone();
-            move || Ok::<_, std::io::Error>(server.clone())
-        };
-
-        // Create StreamableHttpService
-        let http_service = StreamableHttpService::new(
-            service_factory,
-            session_manager,
-            StreamableHttpServerConfig {
-                stateful_mode: true,
-                sse_keep_alive: Some(Duration::from_secs(15)),
-                cancellation_token: ct.clone(),
-            },
-        );
-
-        // Create health handler closure
-        let health_handler = {
-            let server = self.clone();
-            move || {
-                let server = server.clone();
-                async move { server.handle_health().await }
-            }
-        };
+        // Spawn background cleanup of idle connections
+        self.spawn_connection_reaper(&ct);
 
-        // Create stats handler closure
-        let stats_handler = {
-            let server = self.clone();
-            move |axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>| {
-                let server = server.clone();
-                async move {
-                    let connection_id = params.get("connection_id")
-                        .ok_or_else(|| (
-                            axum::http::StatusCode::BAD_REQUEST,
-                            "Missing required parameter: connection_id".to_string(),
-                        ))?;
-                    server.handle_stats(connection_id.clone()).await
-                }
-            }
-        };
-
-        // Create history handler closure
-        let history_handler = {
-            let server = self.clone();
-            move |axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>| {
-                let server = server.clone();
-                async move {
-                    let connection_id = params.get("connection_id")
-                        .ok_or_else(|| (
-                            axum::http::StatusCode::BAD_REQUEST,
-                            "Missing required parameter: connection_id".to_string(),
-                        ))?;
-                    server.handle_history(connection_id.clone()).await
-                }
-            }
-        };
-
-        // Create connection delete handler closure
-        let connection_delete_handler = {
+        // Create service factory closure
+        let service_factory = {
             let server = self.clone();
-            move |Path(connection_id): Path<String>| {
-                let server = server.clone();
-                async move {
-                    server.handle_connection_delete(connection_id).await;
-                    axum::http::StatusCode::NO_CONTENT
-                }
-            }
+            move || Ok::<_, std::io::Error>(server.clone())
         };
 
-        // Build Axum router with CORS
-        let router = Router::new()
-            .route("/mcp/health", get(health_handler))
-            .route("/mcp/stats", get(stats_handler))
-            .route("/mcp/history", get(history_handler))
-            .route("/mcp/connection/{connection_id}", delete(connection_delete_handler))
-            .nest_service("/mcp", http_service)
-            .layer(CorsLayer::permissive());
+        // Persistent sessions are restored by serving them with a fresh server instance
+        self.attach_session_restorer(&session_manager, &ct);
+
+        // Create StreamableHttpService
+        let http_service = StreamableHttpService::new(
+            service_factory,
+            session_manager,
+            StreamableHttpServerConfig {
+                stateful_mode: true,
+                sse_keep_alive: self.config.sse_keep_alive(),
+                cancellation_token: ct.clone(),
+            },
+        );
+
+        // Build Axum router with MCP service and admin endpoints
+        let router = self.build_router(http_service, &ct);
 
         // Spawn server with or without TLS
         let server_task = if let Some((cert_path, key_path)) = tls_config {
             log::info!("Loading TLS certificate from: {cert_path:?}");
             
-            let rustls_config = build_rustls_config(cert_path, key_path)?;
-            let tls_acceptor = TlsAcceptor::from(rustls_config);
-            let ct_for_tls = ct.clone();
-            let active_requests = self.active_requests.clone();
-            
-            tokio::spawn(async move {
-                loop {
-                    // Accept TCP connection
-                    let (tcp_stream, remote_addr) = tokio::select! {
-                        _ = ct_for_tls.cancelled() => break,
-                        result = listener.accept() => {
-                            match result {
-                                Ok(conn) => conn,
-                                Err(e) => {
-                                    log::error!("Failed to accept connection: {e}");
-                                    continue;
-                                }
-                            }
-                        }
-                    };
-                    
-                    // Clone for task
-                    let tls_acceptor = tls_acceptor.clone();
-                    let router = router.clone();
-                    let active_requests = active_requests.clone();
-                    
-                    // Spawn connection handler
-                    tokio::spawn(async move {
-                        // TLS handshake
-                        let tls_stream = match tls_acceptor.accept(tcp_stream).await {
-                            Ok(stream) => stream,
-                            Err(e) => {
-                                log::error!("TLS handshake failed from {remote_addr}: {e}");
-                                return;
-                            }
-                        };
-                        
-                        // Convert to hyper-compatible IO
-                        let io = TokioIo::new(tls_stream);
-                        
-                        // Create hyper service from router
-                        let tower_service = router.clone();
-                        let hyper_service = hyper::service::service_fn(move |request| {
-                            tower_service.clone().call(request)
-                        });
-                        
-                        // Track active request
-                        let _guard = RequestGuard::new(active_requests.clone());
-                        
-                        // Serve connection
-                        if let Err(e) = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
-                            .serve_connection_with_upgrades(io, hyper_service)
-                            .await
-                        {
-                            log::debug!("Connection error from {remote_addr}: {e}");
-                        }
-                    });
-                }
-            })
+            let rustls_config = build_rustls_config(cert_path, key_path, self.config.tls_client_ca.as_deref())?;
+            spawn_tls_server(
+                listener,
+                TlsAcceptor::from(rustls_config),
+                router,
+                ct.clone(),
+                self.active_requests.clone(),
+            )
         } else {
             // HTTP (no TLS) - use axum::serve directly
             let ct_for_http = ct.clone();
@@ -666,6 +1628,7 @@ where
 
         let ct_clone = ct.clone();
         let active_requests = self.active_requests.clone();
+        let drain_timeout = self.config.drain_timeout();
 
         // Spawn monitor task for graceful shutdown with immediate panic detection
         tokio::spawn(async move {
@@ -758,7 +1721,6 @@ where
                 log::info!("Draining in-flight request handlers before manager shutdown");
             }
             
-            let drain_timeout = Duration::from_secs(30);
             let drain_start = std::time::Instant::now();
             
             loop {
@@ -789,6 +1751,24 @@ where
             }
             log::debug!("Manager shutdown complete");
 
+            // Flush buffered trace spans
+            telemetry.shutdown().await;
+
+            // Write out queued audit entries
+            if let Some(audit_log) = audit_log {
+                let _ = tokio::task::spawn_blocking(move || audit_log.flush()).await;
+            }
+
+            // Write out session activity so open sessions can be restored after a restart
+            let _ = tokio::task::spawn_blocking(move || {
+                if let Some(persistent) =
+                    (sessions.as_ref() as &dyn std::any::Any).downcast_ref::<PersistentSessionManager>()
+                {
+                    persistent.flush();
+                }
+            })
+            .await;
+
             // Signal shutdown complete (may fail if receiver timed out)
             if completion_tx.send(()).is_err() {
                 log::debug!(
@@ -838,6 +1818,9 @@ where
         use tokio_util::sync::CancellationToken;
 
         let managers = self.managers.clone();
+        let telemetry = self.telemetry.clone();
+        let audit_log = self.audit_log.clone();
+        let sessions = self.session_manager.clone();
         let protocol = if tls_config.is_some() { "https" } else { "http" };
         
         // Get the address the listener is bound to
@@ -862,150 +1845,54 @@ where
         let ct = CancellationToken::new();
 
         // Session shutdown is handled by rmcp via cancellation_token in StreamableHttpServerConfig
-        let session_manager = self.session_manager.clone();
+        // SessionLayer adds Last-Event-ID replay, session tracking and the session cap
+        let session_manager = Arc::new(self.session_layer(&ct));
 
         // Spawn background memory monitor
         crate::monitor::spawn_memory_monitor(
+            self.memory_monitor.clone(),
             self.requests_processed.clone(),
             ct.clone(),
         );
 
+        // Spawn background cleanup of idle connections
+        self.spawn_connection_reaper(&ct);
+
         // Create service factory closure
         let service_factory = {
             let server = self.clone();
             move || Ok::<_, std::io::Error>(server.clone())
         };
 
+        // Persistent sessions are restored by serving them with a fresh server instance
+        self.attach_session_restorer(&session_manager, &ct);
+
         // Create StreamableHttpService
         let http_service = StreamableHttpService::new(
             service_factory,
             session_manager,
             StreamableHttpServerConfig {
                 stateful_mode: true,
-                sse_keep_alive: Some(Duration::from_secs(15)),
+                sse_keep_alive: self.config.sse_keep_alive(),
                 cancellation_token: ct.clone(),
             },
         );
 
-        // Create health handler closure
-        let health_handler = {
-            let server = self.clone();
-            move || {
-                let server = server.clone();
-                async move { server.handle_health().await }
-            }
-        };
-
-        // Create stats handler closure
-        let stats_handler = {
-            let server = self.clone();
-            move |axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>| {
-                let server = server.clone();
-                async move {
-                    let connection_id = params.get("connection_id")
-                        .ok_or_else(|| (
-                            axum::http::StatusCode::BAD_REQUEST,
-                            "Missing required parameter: connection_id".to_string(),
-                        ))?;
-                    server.handle_stats(connection_id.clone()).await
-                }
-            }
-        };
-
-        // Create history handler closure
-        let history_handler = {
-            let server = self.clone();
-            move |axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>| {
-                let server = server.clone();
-                async move {
-                    let connection_id = params.get("connection_id")
-                        .ok_or_else(|| (
-                            axum::http::StatusCode::BAD_REQUEST,
-                            "Missing required parameter: connection_id".to_string(),
-                        ))?;
-                    server.handle_history(connection_id.clone()).await
-                }
-            }
-        };
-
-        // Create connection delete handler closure
-        let connection_delete_handler = {
-            let server = self.clone();
-            move |Path(connection_id): Path<String>| {
-                let server = server.clone();
-                async move {
-                    server.handle_connection_delete(connection_id).await;
-                    axum::http::StatusCode::NO_CONTENT
-                }
-            }
-        };
-
-        // Build Axum router with CORS
-        let router = Router::new()
-            .route("/mcp/health", get(health_handler))
-            .route("/mcp/stats", get(stats_handler))
-            .route("/mcp/history", get(history_handler))
-            .route("/mcp/connection/{connection_id}", delete(connection_delete_handler))
-            .nest_service("/mcp", http_service)
-            .layer(CorsLayer::permissive());
+        // Build Axum router with MCP service and admin endpoints
+        let router = self.build_router(http_service, &ct);
 
         // Spawn server with or without TLS
         let server_task = if let Some((cert_path, key_path)) = tls_config {
             log::info!("Loading TLS certificate from: {cert_path:?}");
             
-            let rustls_config = build_rustls_config(cert_path, key_path)?;
-            let tls_acceptor = TlsAcceptor::from(rustls_config);
-            let ct_for_tls = ct.clone();
-            let active_requests = self.active_requests.clone();
-            
-            tokio::spawn(async move {
-                loop {
-                    // Accept TCP connection from pre-bound listener
-                    let (tcp_stream, remote_addr) = tokio::select! {
-                        _ = ct_for_tls.cancelled() => break,
-                        result = listener.accept() => {
-                            match result {
-                                Ok(conn) => conn,
-                                Err(e) => {
-                                    log::error!("Failed to accept connection: {e}");
-                                    continue;
-                                }
-                            }
-                        }
-                    };
-                    
-                    // Clone for task
-                    let tls_acceptor = tls_acceptor.clone();
-                    let router = router.clone();
-                    let active_requests = active_requests.clone();
-                    
-                    // Spawn connection handler (same as serve_with_tls)
-                    tokio::spawn(async move {
-                        let tls_stream = match tls_acceptor.accept(tcp_stream).await {
-                            Ok(stream) => stream,
-                            Err(e) => {
-                                log::error!("TLS handshake failed from {remote_addr}: {e}");
-                                return;
-                            }
-                        };
-                        
-                        let io = TokioIo::new(tls_stream);
-                        let tower_service = router.clone();
-                        let hyper_service = hyper::service::service_fn(move |request| {
-                            tower_service.clone().call(request)
-                        });
-                        
-                        let _guard = RequestGuard::new(active_requests.clone());
-                        
-                        if let Err(e) = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
-                            .serve_connection_with_upgrades(io, hyper_service)
-                            .await
-                        {
-                            log::debug!("Connection error from {remote_addr}: {e}");
-                        }
-                    });
-                }
-            })
+            let rustls_config = build_rustls_config(cert_path, key_path, self.config.tls_client_ca.as_deref())?;
+            spawn_tls_server(
+                listener,
+                TlsAcceptor::from(rustls_config),
+                router,
+                ct.clone(),
+                self.active_requests.clone(),
+            )
         } else {
             // HTTP (no TLS) - use axum::serve with pre-bound listener
             let ct_for_http = ct.clone();
@@ -1024,6 +1911,7 @@ where
         // Spawn monitor task for graceful shutdown (identical pattern to serve_with_tls)
         let ct_clone = ct.clone();
         let active_requests = self.active_requests.clone();
+        let drain_timeout = self.config.drain_timeout();
 
         tokio::spawn(async move {
             tokio::pin!(server_task);
@@ -1065,7 +1953,6 @@ where
                 log::info!("Draining in-flight request handlers before manager shutdown");
             }
             
-            let drain_timeout = Duration::from_secs(30);
             let drain_start = std::time::Instant::now();
             
             loop {
@@ -1096,40 +1983,74 @@ where
             }
             log::debug!("Manager shutdown complete");
 
+            // Flush buffered trace spans
+            telemetry.shutdown().await;
+
+            // Write out queued audit entries
+            if let Some(audit_log) = audit_log {
+                let _ = tokio::task::spawn_blocking(move || audit_log.flush()).await;
+            }
+
+            // Write out session activity so open sessions can be restored after a restart
+            let _ = tokio::task::spawn_blocking(move || {
+                if let Some(persistent) =
+                    (sessions.as_ref() as &dyn std::any::Any).downcast_ref::<PersistentSessionManager>()
+                {
+                    persistent.flush();
+                }
+            })
+            .await;
+
             // Signal completion
             let _ = completion_tx.send(());
         });
 
         Ok(ServerHandle::new(ct, completion_rx))
     }
-}
 
-impl<SM> ServerHandler for HttpServer<SM>
-where
-    SM: SessionManager,
-{
-    fn get_info(&self) -> ServerInfo {
-        ServerInfo {
-            protocol_version: ProtocolVersion::V_2024_11_05,
-            capabilities: ServerCapabilities::builder()
-                .enable_tools()
-                .enable_prompts()
-                .build(),
-            server_info: Implementation::from_build_env(),
-            instructions: Some("KODEGEN HTTP Server".to_string()),
-        }
+    /// Append a finished (or rejected) tool call to the audit log, if enabled
+    fn audit_call(
+        &self,
+        connection_identity: Option<&ConnectionIdentity>,
+        request_id: &str,
+        tool_name: &str,
+        arguments: &serde_json::Value,
+        result: &Result<CallToolResult, McpError>,
+        duration_ms: u64,
+    ) {
+        let Some(ref audit_log) = self.audit_log else {
+            return;
+        };
+        let (outcome, error) = match result {
+            Ok(call_result) if call_result.is_error == Some(true) => (AuditOutcome::ToolError, None),
+            Ok(_) => (AuditOutcome::Success, None),
+            Err(error) => (AuditOutcome::Error, Some(error.message.as_ref())),
+        };
+        audit_log.record(AuditEvent {
+            connection_id: connection_identity.map(|identity| identity.id.as_str()),
+            connection_id_source: connection_identity.map(|identity| identity.source),
+            request_id: Some(request_id),
+            tool_name,
+            arguments,
+            outcome,
+            error,
+            duration_ms,
+        });
     }
 
-    async fn call_tool(
+    /// Run a tool call with tracking, tracing and history (the body of `call_tool`)
+    async fn run_tool_call(
         &self,
         request: CallToolRequestParam,
         context: RequestContext<RoleServer>,
+        request_id: String,
     ) -> Result<CallToolResult, McpError> {
         let tool_name = request.name.clone();
+
         let args_value = serde_json::Value::Object(request.arguments.clone().unwrap_or_default());
 
-        // Extract connection_id from HTTP headers (if present)
-        let connection_id: Option<String> = context
+        // Extract connection_id from forwarded HTTP headers (if present)
+        let header_connection_id: Option<String> = context
             .meta
             .0
             .get("headers")
@@ -1138,6 +2059,85 @@ where
             .and_then(|v| v.as_str())
             .map(|s| s.to_string());
 
+        let parts = context.extensions.get::<axum::http::request::Parts>();
+
+        // Fall back to an id derived from the client certificate, token or MCP session
+        let connection_identity = ConnectionIdentity::resolve(header_connection_id, parts);
+        let connection_id_source = connection_identity.as_ref().map(|identity| identity.source);
+
+        // Shed load while memory is over the limit; the monitor resumes once it drops
+        if self.memory_monitor.is_shedding_load() {
+            let latest = self.memory_monitor.latest();
+            log::debug!("Rejecting tool call '{}' - memory over limit", tool_name);
+            let result = Err(McpError::new(
+                SERVER_OVERLOADED,
+                "Server memory usage is over its limit - retry later",
+                Some(serde_json::json!({
+                    "retryable": true,
+                    "retry_after_ms": self.memory_monitor.interval().as_millis() as u64,
+                    "rss_bytes": latest.map(|sample| sample.rss_bytes),
+                    "limit_bytes": self.memory_monitor.limit(),
+                    "request_id": request_id,
+                })),
+            ));
+            self.audit_call(
+                connection_identity.as_ref(),
+                &request_id,
+                &tool_name,
+                &args_value,
+                &result,
+                0,
+            );
+            self.events.publish(|| ToolEvent::CallFinished {
+                timestamp: chrono::Utc::now(),
+                tool_name: tool_name.to_string(),
+                connection_id: connection_identity.as_ref().map(|identity| identity.id.clone()),
+                request_id: request_id.clone(),
+                duration_ms: 0,
+                status: CallStatus::Error,
+                slow: false,
+            });
+            return result;
+        }
+
+        // Keep the connection active (exempt from idle cleanup) while the call runs
+        let _connection_call = connection_identity
+            .as_ref()
+            .map(|identity| self.connections.begin_call(identity));
+        let connection_id = connection_identity.as_ref().map(|identity| identity.id.clone());
+
+        // Extract the MCP session id from the underlying HTTP request (if present)
+        let session_id: Option<SessionId> = parts
+            .and_then(|parts| parts.headers.get(HEADER_SESSION_ID))
+            .and_then(|v| v.to_str().ok())
+            .map(SessionId::from);
+
+        // Attribute the session to its connection and count the call as in flight
+        let _in_flight = session_id.as_ref().map(|session_id| {
+            if let Some(ref conn_id) = connection_id {
+                self.session_registry.set_connection_id(session_id, conn_id);
+            }
+            self.session_registry.begin_call(session_id)
+        });
+
+        // Open the tool call span under the JSON-RPC request span (or the caller's traceparent)
+        let tool_span: Option<ToolSpan> = if self.telemetry.is_enabled() {
+            let trace_parent = parts
+                .and_then(|parts| parts.extensions.get::<opentelemetry::Context>().cloned())
+                .or_else(|| parts.map(|parts| Telemetry::extract_context(&parts.headers)))
+                .unwrap_or_default();
+            self.telemetry.start_tool_span(&trace_parent, &tool_name)
+        } else {
+            None
+        };
+
+        self.events.publish(|| ToolEvent::CallStarted {
+            timestamp: chrono::Utc::now(),
+            tool_name: tool_name.to_string(),
+            connection_id: connection_id.clone(),
+            request_id: request_id.clone(),
+        });
+
         // Increment total tool calls counter
         self.requests_processed.fetch_add(1, Ordering::SeqCst);
 
@@ -1149,7 +2149,25 @@ where
 
         let tcc = rmcp::handler::server::tool::ToolCallContext::new(self, request, context);
 
-        let mut result = self.tool_router.call(tcc).await;
+        // Tag log lines emitted by the tool with its name and connection
+        let log_context = LogContext {
+            category: Some(self.server_identity.category.clone()),
+            connection_id: connection_id.clone(),
+            tool_name: Some(tool_name.to_string()),
+            request_id: Some(request_id.clone()),
+        };
+        let mut result = logging::with_log_context(log_context, async {
+            let mut result = self.tool_router.call(tcc).await;
+
+            // Validate structured_content against the declared output schema (debug/strict mode)
+            if let Ok(ref call_result) = result
+                && let Err(error) = self.validate_structured_content(&tool_name, call_result)
+            {
+                result = Err(error);
+            }
+            result
+        })
+        .await;
 
         let duration_ms = start.elapsed().as_millis() as u64;
 
@@ -1181,8 +2199,9 @@ where
             Err(ref mut error) => {
                 // For errors, add branded line if content exists
                 if let Some(ref mut data) = error.data
-                    && let Some(contents_value) = data.get_mut("content")
-                    && let Ok(contents) = serde_json::from_value::<Vec<Content>>(contents_value.clone())
+                    && let Some(data) = data.as_object_mut()
+                    && let Some(contents) = data.get("content")
+                        .and_then(|v| serde_json::from_value::<Vec<Content>>(v.clone()).ok())
                 {
                     // Create a temporary CallToolResult to use add_branded_line_to_result
                     let mut temp_result = CallToolResult {
@@ -1198,11 +2217,62 @@ where
                         duration_ms,
                         ToolStatus::Error,
                     );
-                    *contents_value = serde_json::to_value(&temp_result.content).unwrap_or(contents_value.clone());
+                    if let Ok(contents_value) = serde_json::to_value(&temp_result.content) {
+                        data.insert("content".to_string(), contents_value);
+                    }
                 }
             }
         }
 
+        // Return the request id to the client so it can be matched against server logs
+        match result {
+            Ok(ref mut call_result) => {
+                call_result
+                    .meta
+                    .get_or_insert_with(Meta::new)
+                    .0
+                    .insert("request_id".to_string(), request_id.clone().into());
+            }
+            Err(ref mut error) => match error.data {
+                Some(serde_json::Value::Object(ref mut data)) => {
+                    data.insert("request_id".to_string(), request_id.clone().into());
+                }
+                None => error.data = Some(serde_json::json!({ "request_id": request_id })),
+                Some(_) => {}
+            },
+        }
+
+        // Error results count as failures in every sink (metrics, usage stats, slow calls)
+        let success = CallStatus::from_result(&result) == CallStatus::Success;
+
+        // Log and remember calls over their slow-call threshold
+        let slow = self.slow_calls.check(
+            &tool_name,
+            connection_id.as_deref(),
+            Some(&request_id),
+            &args_value,
+            duration_ms,
+            success,
+        );
+
+        self.audit_call(
+            connection_identity.as_ref(),
+            &request_id,
+            &tool_name,
+            &args_value,
+            &result,
+            duration_ms,
+        );
+        self.events.publish(|| ToolEvent::CallFinished {
+            timestamp: chrono::Utc::now(),
+            tool_name: tool_name.to_string(),
+            connection_id: connection_id.clone(),
+            request_id: request_id.clone(),
+            duration_ms,
+            status: CallStatus::from_result(&result),
+            slow,
+        });
+
         // Track in tool history (only if connection_id is present)
         if let Some(ref conn_id) = connection_id {
             // Serialize result to JSON
@@ -1213,26 +2283,91 @@ where
                 }),
             };
 
-            self.tool_history.track_call(
+            self.tool_history.track_annotated_call(
                 conn_id,
                 tool_name.to_string(),
                 args_value.clone(),
                 output_value,
                 Some(duration_ms),
+                CallAnnotations {
+                    slow,
+                    request_id: Some(request_id),
+                },
             );
         }
 
-        // Track success/failure per-connection (only if connection_id is present)
-        if let Some(conn_id) = connection_id {
-            if result.is_ok() {
-                self.usage_tracker.track_success(&conn_id, &tool_name);
-            } else {
-                self.usage_tracker.track_failure(&conn_id, &tool_name);
-            }
+        // Record outcome and latency for /metrics
+        let metrics_tool = if self.tool_router.has_route(&tool_name) {
+            tool_name.as_ref()
+        } else {
+            crate::metrics::UNKNOWN_TOOL
+        };
+        self.metrics.record_call(metrics_tool, success, duration_ms);
+
+        if let Some(tool_span) = tool_span {
+            let error = match &result {
+                Ok(call_result) if call_result.is_error == Some(true) => Some("tool returned an error result".to_string()),
+                Ok(_) => None,
+                Err(error) => Some(error.message.to_string()),
+            };
+            tool_span.finish(
+                connection_id.as_deref(),
+                connection_id_source.map(ConnectionIdSource::as_str),
+                duration_ms,
+                error,
+            );
         }
 
+        // Track success/failure and latency (per-connection only if connection_id is present)
+        self.usage_tracker.track_call(connection_id.as_deref(), &tool_name, success, duration_ms);
+
         result
     }
+}
+
+impl<SM> ServerHandler for HttpServer<SM>
+where
+    SM: SessionManager,
+{
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            protocol_version: ProtocolVersion::V_2024_11_05,
+            capabilities: ServerCapabilities::builder()
+                .enable_tools()
+                .enable_prompts()
+                .build(),
+            server_info: Implementation::from_build_env(),
+            instructions: Some("KODEGEN HTTP Server".to_string()),
+        }
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        // Resolve the request id first so every log line for the call carries it
+        let forwarded_request_id = context
+            .meta
+            .0
+            .get("headers")
+            .and_then(|v| v.as_object())
+            .and_then(|headers| headers.get(request_id::X_REQUEST_ID))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+        let request_id = request_id::resolve(
+            forwarded_request_id.as_deref(),
+            context.extensions.get::<axum::http::request::Parts>(),
+        );
+
+        let log_context = LogContext {
+            category: Some(self.server_identity.category.clone()),
+            tool_name: Some(request.name.to_string()),
+            request_id: Some(request_id.clone()),
+            ..LogContext::default()
+        };
+        logging::with_log_context(log_context, self.run_tool_call(request, context, request_id)).await
+    }
 
     async fn list_tools(
         &self,
@@ -1361,6 +2496,30 @@ impl ServerHandle {
         self.cancellation_token.cancel();
     }
 
+    /// Current log levels (None if another logger is installed)
+    ///
+    /// Log levels are process-wide, shared by every server in the process.
+    pub fn log_levels(&self) -> Option<logging::LogLevels> {
+        logging::log_levels()
+    }
+
+    /// Change the default level (`module` = None) or one module's level at runtime
+    ///
+    /// With a `ttl` the override reverts on its own. Same as `PUT /mcp/log-levels`.
+    pub fn set_log_level(
+        &self,
+        module: Option<&str>,
+        level: log::LevelFilter,
+        ttl: Option<Duration>,
+    ) -> Result<(), logging::LogLevelError> {
+        logging::set_log_level(module, level, ttl)
+    }
+
+    /// Remove the runtime override of one module, or all overrides (`module` = None)
+    pub fn reset_log_levels(&self, module: Option<&str>) -> Result<(), logging::LogLevelError> {
+        logging::reset_log_levels(module)
+    }
+
     /// Wait for server shutdown to complete (with timeout)
     ///
     /// Returns Ok(()) if shutdown completes within timeout.
@@ -1389,6 +2548,60 @@ impl ServerHandle {
     }
 }
 
+/// Body of `PUT /mcp/log-levels`
+#[derive(Debug, serde::Deserialize)]
+struct LogLevelUpdate {
+    /// Module path to change; omit to change the default level
+    module: Option<String>,
+    /// `off`, `error`, `warn`, `info`, `debug` or `trace`
+    level: String,
+    /// Revert after this many seconds; omit to keep until reset
+    ttl_secs: Option<u64>,
+}
+
+/// `GET /mcp/log-levels`
+async fn handle_log_levels_get() -> Result<Json<logging::LogLevels>, (axum::http::StatusCode, String)> {
+    logging::log_levels()
+        .map(Json)
+        .ok_or_else(|| log_level_error(logging::LogLevelError::Unmanaged))
+}
+
+/// `PUT /mcp/log-levels`: change the default or one module's level
+async fn handle_log_levels_put(
+    Json(update): Json<LogLevelUpdate>,
+) -> Result<Json<logging::LogLevels>, (axum::http::StatusCode, String)> {
+    let level = update.level.parse::<log::LevelFilter>().map_err(|_| (
+        axum::http::StatusCode::BAD_REQUEST,
+        format!("Invalid log level: {}", update.level),
+    ))?;
+    if update.ttl_secs == Some(0) {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            "ttl_secs must be greater than 0 (omit it for no expiry)".to_string(),
+        ));
+    }
+
+    logging::set_log_level(update.module.as_deref(), level, update.ttl_secs.map(Duration::from_secs))
+        .map_err(log_level_error)?;
+    handle_log_levels_get().await
+}
+
+/// `DELETE /mcp/log-levels[?module=...]`: drop runtime overrides
+async fn handle_log_levels_delete(
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Result<axum::http::StatusCode, (axum::http::StatusCode, String)> {
+    logging::reset_log_levels(params.get("module").map(String::as_str)).map_err(log_level_error)?;
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+fn log_level_error(error: logging::LogLevelError) -> (axum::http::StatusCode, String) {
+    let status = match error {
+        logging::LogLevelError::Unmanaged => axum::http::StatusCode::SERVICE_UNAVAILABLE,
+        logging::LogLevelError::TtlOutOfRange => axum::http::StatusCode::BAD_REQUEST,
+    };
+    (status, error.to_string())
+}
+
 /// RAII guard for tracking active request handlers
 ///
 /// Automatically increments the request counter on creation and decrements
@@ -1412,3 +2625,109 @@ impl Drop for RequestGuard {
         self.counter.fetch_sub(1, Ordering::SeqCst);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+    use tokio_rustls::rustls::pki_types::ServerName;
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls").join(name)
+    }
+
+    /// GET `/` over TLS, presenting the fixture client certificate if `with_certificate`
+    async fn get_over_tls(addr: SocketAddr, with_certificate: bool) -> String {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(CertificateDer::from_pem_file(fixture("ca.pem")).unwrap()).unwrap();
+        let builder = rustls::ClientConfig::builder().with_root_certificates(roots);
+        let config = if with_certificate {
+            let certs = vec![CertificateDer::from_pem_file(fixture("client.pem")).unwrap()];
+            let key = PrivateKeyDer::from_pem_file(fixture("client.key")).unwrap();
+            builder.with_client_auth_cert(certs, key).unwrap()
+        } else {
+            builder.with_no_client_auth()
+        };
+
+        let tcp = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let server_name = ServerName::try_from("localhost").unwrap();
+        let mut tls = TlsConnector::from(Arc::new(config)).connect(server_name, tcp).await.unwrap();
+        tls.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        tls.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn tls_connection_id_comes_from_the_client_certificate() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let router = Router::new().route(
+            "/",
+            get(|request: axum::extract::Request| async move {
+                let (parts, _) = request.into_parts();
+                ConnectionIdentity::resolve(None, Some(&parts))
+                    .map(|identity| format!("{}:{}", identity.source.as_str(), identity.id))
+                    .unwrap_or_else(|| "none".to_string())
+            }),
+        );
+        let rustls_config = build_rustls_config(fixture("server.pem"), fixture("server.key"), Some(&fixture("ca.pem")))
+            .unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let ct = tokio_util::sync::CancellationToken::new();
+        let server = spawn_tls_server(
+            listener,
+            TlsAcceptor::from(rustls_config),
+            router,
+            ct.clone(),
+            Arc::new(AtomicUsize::new(0)),
+        );
+
+        let client_der = CertificateDer::from_pem_file(fixture("client.pem")).unwrap();
+        let ClientCertificateFingerprint(fingerprint) = ClientCertificateFingerprint::from_der(client_der.as_ref());
+        let response = get_over_tls(addr, true).await;
+        assert!(
+            response.ends_with(&format!("client_certificate:cert-{fingerprint}")),
+            "{response}"
+        );
+
+        // Clients without a certificate are still served, just not identified by one
+        let response = get_over_tls(addr, false).await;
+        assert!(response.starts_with("HTTP/1.1 200") && response.ends_with("none"), "{response}");
+
+        ct.cancel();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn log_level_endpoints_set_and_reset_module_levels() {
+        logging::install_test_filter();
+        let module = "kodegen_server_test::endpoint";
+        let update = |level: &str, ttl_secs| LogLevelUpdate {
+            module: Some(module.to_string()),
+            level: level.to_string(),
+            ttl_secs,
+        };
+
+        let (status, _) = handle_log_levels_put(Json(update("loud", None))).await.unwrap_err();
+        assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+        let (status, _) = handle_log_levels_put(Json(update("debug", Some(0)))).await.unwrap_err();
+        assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+        let (status, _) = handle_log_levels_put(Json(update("debug", Some(u64::MAX)))).await.unwrap_err();
+        assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+
+        let Json(levels) = handle_log_levels_put(Json(update("trace", Some(3600)))).await.unwrap();
+        let entry = &levels.modules[module];
+        assert_eq!(entry.level, "trace");
+        assert!(entry.expires_at.is_some_and(|at| at > chrono::Utc::now()));
+
+        let params = std::collections::HashMap::from([("module".to_string(), module.to_string())]);
+        let status = handle_log_levels_delete(axum::extract::Query(params)).await.unwrap();
+        assert_eq!(status, axum::http::StatusCode::NO_CONTENT);
+        let Json(levels) = handle_log_levels_get().await.unwrap();
+        assert!(!levels.modules.contains_key(module));
+    }
+}
@@ -0,0 +1,243 @@
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use rmcp::transport::common::server_side_http::ServerSseMessage;
+use rmcp::transport::streamable_http_server::session::SessionId;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Default number of server-to-client messages retained per session
+const DEFAULT_MAX_EVENTS_PER_SESSION: usize = 1024;
+
+/// Default age after which retained messages are discarded (5 minutes)
+const DEFAULT_RETENTION_SECS: u64 = 5 * 60;
+
+/// Retention settings for the per-session SSE event store
+#[derive(Debug, Clone)]
+pub struct EventStoreConfig {
+    /// Maximum number of messages kept per session (oldest dropped first, 0 disables the store)
+    pub max_events_per_session: usize,
+    /// Maximum age of a retained message
+    pub retention: Duration,
+}
+
+impl Default for EventStoreConfig {
+    fn default() -> Self {
+        Self {
+            max_events_per_session: DEFAULT_MAX_EVENTS_PER_SESSION,
+            retention: Duration::from_secs(DEFAULT_RETENTION_SECS),
+        }
+    }
+}
+
+/// Position of a message within an SSE stream, parsed from rmcp's event id
+///
+/// rmcp formats event ids as `<index>` for the standalone (GET) stream and
+/// `<index>/<http_request_id>` for the stream belonging to one POST request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct EventPosition {
+    pub stream: Option<u64>,
+    pub index: usize,
+}
+
+impl EventPosition {
+    pub(crate) fn parse(event_id: &str) -> Option<Self> {
+        match event_id.split_once('/') {
+            Some((index, stream)) => Some(Self {
+                stream: Some(stream.parse().ok()?),
+                index: index.parse().ok()?,
+            }),
+            None => Some(Self {
+                stream: None,
+                index: event_id.parse().ok()?,
+            }),
+        }
+    }
+}
+
+/// A retained message with its parsed position and arrival time
+struct StoredEvent {
+    position: EventPosition,
+    message: ServerSseMessage,
+    stored_at: Instant,
+}
+
+/// Bounded store of recent server-to-client SSE messages, keyed by session
+///
+/// rmcp only caches messages while a request's channel is open, so a client that
+/// reconnects after the response was sent gets "channel closed" instead of the
+/// result. This store keeps messages independently of rmcp's channels so they can
+/// be replayed when a client reconnects with `Last-Event-ID`.
+pub struct EventStore {
+    config: EventStoreConfig,
+    events_by_session: DashMap<SessionId, Mutex<VecDeque<StoredEvent>>>,
+}
+
+impl EventStore {
+    pub fn new(config: EventStoreConfig) -> Self {
+        Self {
+            config,
+            events_by_session: DashMap::new(),
+        }
+    }
+
+    /// Whether messages are retained at all
+    pub fn is_enabled(&self) -> bool {
+        self.config.max_events_per_session > 0
+    }
+
+    /// Retain a message sent to a session (messages without an event id are skipped)
+    pub fn record(&self, session_id: &SessionId, message: &ServerSseMessage) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let Some(position) = message.event_id.as_deref().and_then(EventPosition::parse) else {
+            return;
+        };
+
+        let entry = self.events_by_session.entry(session_id.clone()).or_default();
+        let mut events = entry.lock();
+
+        events.push_back(StoredEvent {
+            position,
+            message: message.clone(),
+            stored_at: Instant::now(),
+        });
+
+        while events.len() > self.config.max_events_per_session {
+            events.pop_front();
+        }
+
+        Self::prune_expired(&mut events, self.config.retention);
+    }
+
+    /// Get retained messages of the same stream that come after `last_event_id`
+    pub fn events_after(&self, session_id: &SessionId, last_event_id: &str) -> Vec<ServerSseMessage> {
+        let Some(last) = EventPosition::parse(last_event_id) else {
+            return Vec::new();
+        };
+
+        let Some(entry) = self.events_by_session.get(session_id) else {
+            return Vec::new();
+        };
+
+        let mut events = entry.lock();
+        Self::prune_expired(&mut events, self.config.retention);
+
+        events
+            .iter()
+            .filter(|event| event.position.stream == last.stream && event.position.index > last.index)
+            .map(|event| event.message.clone())
+            .collect()
+    }
+
+    /// Drop all retained messages for a session (called when the session closes or expires)
+    pub fn remove_session(&self, session_id: &SessionId) {
+        self.events_by_session.remove(session_id);
+    }
+
+    /// Number of messages currently retained for a session
+    pub fn len(&self, session_id: &SessionId) -> usize {
+        self.events_by_session
+            .get(session_id)
+            .map(|entry| entry.lock().len())
+            .unwrap_or(0)
+    }
+
+    fn prune_expired(events: &mut VecDeque<StoredEvent>, retention: Duration) {
+        while events
+            .front()
+            .is_some_and(|event| event.stored_at.elapsed() > retention)
+        {
+            events.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::{NumberOrString, ServerJsonRpcMessage, ServerResult};
+    use std::sync::Arc;
+
+    fn message(event_id: &str) -> ServerSseMessage {
+        ServerSseMessage {
+            event_id: Some(event_id.to_string()),
+            message: Arc::new(ServerJsonRpcMessage::response(
+                ServerResult::empty(()),
+                NumberOrString::Number(1),
+            )),
+        }
+    }
+
+    fn event_ids(messages: &[ServerSseMessage]) -> Vec<&str> {
+        messages.iter().filter_map(|m| m.event_id.as_deref()).collect()
+    }
+
+    fn store(max_events_per_session: usize) -> EventStore {
+        EventStore::new(EventStoreConfig {
+            max_events_per_session,
+            ..EventStoreConfig::default()
+        })
+    }
+
+    #[test]
+    fn parses_standalone_and_request_stream_positions() {
+        assert_eq!(EventPosition::parse("5"), Some(EventPosition { stream: None, index: 5 }));
+        assert_eq!(EventPosition::parse("3/7"), Some(EventPosition { stream: Some(7), index: 3 }));
+    }
+
+    #[test]
+    fn rejects_malformed_event_ids() {
+        for event_id in ["", "abc", "1/", "/2", "1/x", "-1", "1/2/3"] {
+            assert_eq!(EventPosition::parse(event_id), None, "{event_id:?}");
+        }
+    }
+
+    #[test]
+    fn replays_later_events_of_the_same_stream_only() {
+        let store = store(16);
+        let session: SessionId = Arc::from("session");
+        for event_id in ["0", "0/7", "1/7", "1", "0/8", "2/7"] {
+            store.record(&session, &message(event_id));
+        }
+
+        assert_eq!(event_ids(&store.events_after(&session, "0/7")), ["1/7", "2/7"]);
+        assert_eq!(event_ids(&store.events_after(&session, "0")), ["1"]);
+        assert!(store.events_after(&session, "2/7").is_empty());
+        assert!(store.events_after(&session, "not-an-id").is_empty());
+        assert!(store.events_after(&Arc::from("other"), "0").is_empty());
+    }
+
+    #[test]
+    fn drops_oldest_events_beyond_capacity() {
+        let store = store(2);
+        let session: SessionId = Arc::from("session");
+        for index in 0..4 {
+            store.record(&session, &message(&index.to_string()));
+        }
+
+        assert_eq!(store.len(&session), 2);
+        // Index 1 was evicted, so a client that saw 0 only gets what is left
+        assert_eq!(event_ids(&store.events_after(&session, "0")), ["2", "3"]);
+    }
+
+    #[test]
+    fn disabled_store_and_closed_sessions_keep_nothing() {
+        let session: SessionId = Arc::from("session");
+
+        let disabled = store(0);
+        disabled.record(&session, &message("0"));
+        assert_eq!(disabled.len(&session), 0);
+
+        let store = store(16);
+        store.record(&session, &message("0"));
+        store.record(&session, &ServerSseMessage {
+            event_id: None,
+            ..message("1")
+        });
+        assert_eq!(store.len(&session), 1);
+        store.remove_session(&session);
+        assert_eq!(store.len(&session), 0);
+    }
+}
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// What to do when a new session would exceed the session cap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionLimitPolicy {
+    /// Refuse the new session with a "session limit reached" error
+    #[default]
+    Reject,
+    /// Close the least recently active session that has no tool call in flight,
+    /// falling back to Reject if every session is busy
+    EvictIdle,
+}
+
+impl FromStr for SessionLimitPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('-', "_").as_str() {
+            "reject" => Ok(Self::Reject),
+            "evict_idle" => Ok(Self::EvictIdle),
+            other => Err(format!("unknown session limit policy '{}' (expected reject or evict_idle)", other)),
+        }
+    }
+}
+
+/// Cap on concurrent sessions enforced by `SessionLayer`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SessionLimit {
+    /// Maximum number of open sessions (None = unlimited)
+    pub max_sessions: Option<usize>,
+    pub policy: SessionLimitPolicy,
+}
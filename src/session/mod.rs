@@ -0,0 +1,373 @@
+use futures::{Stream, StreamExt, future::Either};
+use rmcp::model::{ClientJsonRpcMessage, ServerJsonRpcMessage};
+use rmcp::transport::common::server_side_http::ServerSseMessage;
+use rmcp::transport::streamable_http_server::{SessionManager, session::SessionId};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+pub mod event_store;
+pub mod limit;
+pub mod persistent;
+pub mod registry;
+pub mod transport;
+
+pub use event_store::{EventStore, EventStoreConfig};
+pub use limit::{SessionLimit, SessionLimitPolicy};
+pub use persistent::{PersistentSessionManager, SessionRestorer};
+pub use registry::{InFlightGuard, SessionInfo, SessionRegistry};
+pub use transport::TrackedTransport;
+use event_store::EventPosition;
+
+/// Callback invoked with a session's last known info after it ends
+///
+/// Runs once per session, whether the client closed it, it expired, or it was
+/// evicted to stay under the session cap.
+pub type SessionClosedFn = Arc<
+    dyn Fn(SessionInfo) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>>
+    + Send
+    + Sync
+>;
+
+/// Error returned by `SessionLayer`
+#[derive(Debug, thiserror::Error)]
+pub enum SessionLayerError<E>
+where
+    E: std::error::Error + 'static,
+{
+    /// Error from the wrapped session manager
+    #[error(transparent)]
+    Inner(#[from] E),
+    /// The session cap was reached and no session could be evicted
+    #[error("session limit reached ({0} open sessions) - close an existing session or retry later")]
+    LimitReached(usize),
+}
+
+/// Session manager wrapper that adds server-side bookkeeping to any `SessionManager`
+///
+/// `HttpServer` wraps its configured session manager in this layer when building the
+/// `StreamableHttpService`, so the added behavior works with every session backend:
+/// - Records server-to-client SSE messages in an [`EventStore`]
+/// - Replays missed messages when a client reconnects with `Last-Event-ID`
+/// - Clears retained messages when the session closes or expires
+/// - Keeps the [`SessionRegistry`] of open sessions up to date
+/// - Enforces the [`SessionLimit`] on concurrent sessions
+/// - Runs a callback when a session ends, however it ends
+pub struct SessionLayer<M>
+where
+    M: SessionManager,
+{
+    inner: Arc<M>,
+    event_store: Arc<EventStore>,
+    registry: Arc<SessionRegistry>,
+    limit: SessionLimit,
+    on_close: Option<SessionClosedFn>,
+    /// Serializes session creation so concurrent initializes cannot overshoot the cap
+    admission: tokio::sync::Mutex<()>,
+}
+
+impl<M> SessionLayer<M>
+where
+    M: SessionManager,
+{
+    pub fn new(inner: Arc<M>, event_store: Arc<EventStore>, registry: Arc<SessionRegistry>) -> Self {
+        Self {
+            inner,
+            event_store,
+            registry,
+            limit: SessionLimit::default(),
+            on_close: None,
+            admission: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Cap the number of concurrent sessions
+    pub fn with_limit(mut self, limit: SessionLimit) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Run `on_close` for every session that ends
+    pub fn on_close(mut self, on_close: SessionClosedFn) -> Self {
+        self.on_close = Some(on_close);
+        self
+    }
+
+    /// Make room for a new session, evicting or rejecting according to the policy
+    async fn enforce_limit(&self) -> Result<(), SessionLayerError<M::Error>> {
+        let Some(max_sessions) = self.limit.max_sessions else {
+            return Ok(());
+        };
+
+        while self.registry.len() >= max_sessions {
+            let victim = match self.limit.policy {
+                SessionLimitPolicy::EvictIdle => self.registry.least_recently_active_idle(),
+                SessionLimitPolicy::Reject => None,
+            };
+
+            let Some(victim) = victim else {
+                log::warn!("Session limit ({}) reached - rejecting new session", max_sessions);
+                return Err(SessionLayerError::LimitReached(max_sessions));
+            };
+
+            log::warn!(
+                "Session limit ({}) reached - evicting least recently active idle session {}",
+                max_sessions,
+                victim
+            );
+            self.evict(&victim).await;
+        }
+
+        Ok(())
+    }
+
+    /// Close a session to make room under the cap
+    async fn evict(&self, id: &SessionId) {
+        if let Err(e) = self.close_session(id).await {
+            log::error!("Failed to close evicted session {}: {}", id, e);
+        }
+        // Make sure a session the backend no longer knows still frees its slot
+        self.registry.remove(id);
+    }
+
+    /// Take over a session the wrapped manager recreated on its own (e.g. restored from disk)
+    ///
+    /// The session is admitted under the cap like a new one and gets the same close
+    /// path, so the registry, the event store and the close callback all see it.
+    pub async fn adopt<T>(&self, id: &SessionId, transport: T) -> Result<TrackedTransport<T>, SessionLayerError<M::Error>> {
+        let _admission = self.admission.lock().await;
+        self.enforce_limit().await?;
+
+        self.registry.register(id);
+        Ok(self.track(id, transport))
+    }
+
+    /// Transport wrapper that finishes the session when its service ends on its own
+    ///
+    /// Sessions closed through `close_session` are already out of the registry when
+    /// their transport is dropped, so the close callback runs exactly once.
+    fn track<T>(&self, id: &SessionId, transport: T) -> TrackedTransport<T> {
+        let inner = self.inner.clone();
+        let event_store = self.event_store.clone();
+        let registry = self.registry.clone();
+        let on_close = self.on_close.clone();
+        let id = id.clone();
+
+        TrackedTransport::new(transport, move || {
+            let Some(info) = end_session(&id, &registry, &event_store) else {
+                return;
+            };
+            let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+                return;
+            };
+
+            log::debug!("Session {} ended", id);
+            runtime.spawn(async move {
+                // rmcp keeps the handle of a session whose worker quit (e.g. keep-alive expiry)
+                if let Err(e) = inner.close_session(&id).await {
+                    log::debug!("Failed to release ended session {}: {}", id, e);
+                }
+                if let Some(on_close) = on_close {
+                    on_close(info).await;
+                }
+            });
+        })
+    }
+
+    /// Record every message of `stream` in the event store as it is sent
+    fn record<S>(&self, id: &SessionId, stream: S) -> impl Stream<Item = ServerSseMessage> + Send + Sync + 'static
+    where
+        S: Stream<Item = ServerSseMessage> + Send + Sync + 'static,
+    {
+        let event_store = self.event_store.clone();
+        let id = id.clone();
+        stream.inspect(move |message| event_store.record(&id, message))
+    }
+}
+
+impl<M> SessionManager for SessionLayer<M>
+where
+    M: SessionManager,
+{
+    type Error = SessionLayerError<M::Error>;
+    type Transport = TrackedTransport<M::Transport>;
+
+    async fn create_session(&self) -> Result<(SessionId, Self::Transport), Self::Error> {
+        let _admission = self.admission.lock().await;
+        self.enforce_limit().await?;
+
+        let (id, transport) = self.inner.create_session().await?;
+        self.registry.register(&id);
+        let transport = self.track(&id, transport);
+        Ok((id, transport))
+    }
+
+    async fn initialize_session(
+        &self,
+        id: &SessionId,
+        message: ClientJsonRpcMessage,
+    ) -> Result<ServerJsonRpcMessage, Self::Error> {
+        self.registry.record_initialize(id, &message);
+        Ok(self.inner.initialize_session(id, message).await?)
+    }
+
+    async fn has_session(&self, id: &SessionId) -> Result<bool, Self::Error> {
+        Ok(self.inner.has_session(id).await?)
+    }
+
+    async fn close_session(&self, id: &SessionId) -> Result<(), Self::Error> {
+        let info = end_session(id, &self.registry, &self.event_store);
+        self.inner.close_session(id).await?;
+
+        if let (Some(on_close), Some(info)) = (&self.on_close, info) {
+            on_close(info).await;
+        }
+        Ok(())
+    }
+
+    async fn create_stream(
+        &self,
+        id: &SessionId,
+        message: ClientJsonRpcMessage,
+    ) -> Result<impl Stream<Item = ServerSseMessage> + Send + Sync + 'static, Self::Error> {
+        let stream = self.inner.create_stream(id, message).await?;
+        self.registry.touch(id);
+        Ok(self.record(id, stream))
+    }
+
+    async fn accept_message(
+        &self,
+        id: &SessionId,
+        message: ClientJsonRpcMessage,
+    ) -> Result<(), Self::Error> {
+        self.inner.accept_message(id, message).await?;
+        self.registry.touch(id);
+        Ok(())
+    }
+
+    async fn create_standalone_stream(
+        &self,
+        id: &SessionId,
+    ) -> Result<impl Stream<Item = ServerSseMessage> + Send + Sync + 'static, Self::Error> {
+        let stream = self.inner.create_standalone_stream(id).await?;
+        Ok(self.record(id, stream))
+    }
+
+    /// Resume a stream from `last_event_id`
+    ///
+    /// Messages retained in the event store are replayed first. If the underlying
+    /// stream is still open, it is resumed after the last replayed message and
+    /// chained on, skipping anything already replayed. If it has already finished
+    /// (e.g. the tool result was sent while the client was disconnected), only the
+    /// retained messages are returned.
+    async fn resume(
+        &self,
+        id: &SessionId,
+        last_event_id: String,
+    ) -> Result<impl Stream<Item = ServerSseMessage> + Send + Sync + 'static, Self::Error> {
+        let replay = self.event_store.events_after(id, &last_event_id);
+        let resume_from = replay
+            .last()
+            .and_then(|message| message.event_id.clone())
+            .unwrap_or_else(|| last_event_id.clone());
+
+        log::debug!(
+            "Resuming session {} from event {} ({} retained messages to replay)",
+            id,
+            last_event_id,
+            replay.len()
+        );
+
+        match self.inner.resume(id, resume_from).await {
+            Ok(live) => {
+                // Highest index already delivered per stream - live messages at or below
+                // it are duplicates of the replay (rmcp re-sends the last seen event)
+                let mut delivered: HashMap<Option<u64>, usize> = HashMap::new();
+                if let Some(position) = EventPosition::parse(&last_event_id) {
+                    delivered.insert(position.stream, position.index);
+                }
+                for message in &replay {
+                    if let Some(position) = message.event_id.as_deref().and_then(EventPosition::parse) {
+                        delivered.insert(position.stream, position.index);
+                    }
+                }
+
+                let live = live.filter(move |message| std::future::ready(!is_delivered(message, &delivered)));
+
+                Ok(Either::Left(futures::stream::iter(replay).chain(self.record(id, live))))
+            }
+            Err(e) if !replay.is_empty() => {
+                log::debug!(
+                    "Session {} stream already closed ({}), replaying {} retained messages only",
+                    id,
+                    e,
+                    replay.len()
+                );
+                Ok(Either::Right(futures::stream::iter(replay)))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Drop a session's bookkeeping, returning its info if it was still registered
+fn end_session(id: &SessionId, registry: &SessionRegistry, event_store: &EventStore) -> Option<SessionInfo> {
+    event_store.remove_session(id);
+    registry.remove(id)
+}
+
+/// Whether `message` was already delivered, given the highest index sent per stream
+fn is_delivered(message: &ServerSseMessage, delivered: &HashMap<Option<u64>, usize>) -> bool {
+    message
+        .event_id
+        .as_deref()
+        .and_then(EventPosition::parse)
+        .is_some_and(|position| {
+            delivered
+                .get(&position.stream)
+                .is_some_and(|&index| position.index <= index)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::transport::streamable_http_server::session::local::LocalSessionManager;
+
+    fn layer(max_sessions: usize) -> SessionLayer<LocalSessionManager> {
+        SessionLayer::new(
+            Arc::new(LocalSessionManager::default()),
+            Arc::new(EventStore::new(EventStoreConfig::default())),
+            Arc::new(SessionRegistry::new()),
+        )
+        .with_limit(SessionLimit {
+            max_sessions: Some(max_sessions),
+            policy: SessionLimitPolicy::Reject,
+        })
+    }
+
+    #[tokio::test]
+    async fn late_updates_for_closed_sessions_do_not_hold_slots() {
+        let layer = layer(2);
+        let mut open = Vec::new();
+        for _ in 0..2 {
+            open.push(layer.create_session().await.unwrap());
+        }
+        assert!(matches!(layer.create_session().await, Err(SessionLayerError::LimitReached(2))));
+
+        for (id, _transport) in &open {
+            layer.close_session(id).await.unwrap();
+        }
+        // Requests and callbacks that arrive after the sessions were closed
+        for (id, _transport) in &open {
+            layer.registry.touch(id);
+            layer.registry.set_connection_id(id, "conn-1");
+        }
+        assert!(layer.registry.is_empty());
+
+        let (id, _transport) = layer.create_session().await.unwrap();
+        assert_eq!(layer.registry.len(), 1);
+        assert!(layer.registry.get(&id).is_some());
+    }
+}
@@ -0,0 +1,645 @@
+use futures::Stream;
+use parking_lot::{Mutex, RwLock};
+use rmcp::model::{ClientJsonRpcMessage, ServerJsonRpcMessage};
+use rmcp::transport::WorkerTransport;
+use rmcp::transport::common::server_side_http::ServerSseMessage;
+use rmcp::transport::streamable_http_server::session::local::{
+    LocalSessionManager, LocalSessionManagerError, SessionConfig, SessionTransport,
+    create_local_session,
+};
+use rmcp::transport::streamable_http_server::{SessionManager, session::SessionId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// How long the writer waits for more changes before writing the sessions file
+const SAVE_DEBOUNCE: Duration = Duration::from_secs(1);
+
+/// Minimum time between sessions-file writes caused only by session activity
+const ACTIVITY_SAVE_INTERVAL_SECS: i64 = 60;
+
+/// Callback that serves a restored session's transport until the session ends
+///
+/// Installed by `HttpServer` when it starts serving, because only the server can
+/// create the MCP service that drives a session. The server hands the session to
+/// its `SessionLayer` first, so restored sessions are tracked and closed like any other.
+pub type SessionRestorer = Arc<
+    dyn Fn(SessionId, SessionTransport) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>>
+    + Send
+    + Sync
+>;
+
+/// Session metadata persisted to disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PersistedSession {
+    /// The client's original `initialize` request, replayed on restore
+    initialize: ClientJsonRpcMessage,
+    created_at: i64,  // Unix timestamp
+    last_active: i64, // Unix timestamp
+}
+
+/// Session manager that persists session metadata so sessions survive restarts
+///
+/// Wraps rmcp's `LocalSessionManager` and records each session's `initialize`
+/// request in `sessions_{category}.json` under `KodegenConfig::data_dir()`.
+/// When a client sends a request with a session id that is no longer live (for
+/// example after kodegend restarted the server), the session is recreated under
+/// the same id and re-initialized from the stored request, so the client keeps
+/// working instead of seeing "session not found".
+///
+/// Sessions closed by the client or expired by keep-alive are removed from disk.
+/// Sessions open during graceful shutdown are kept so they can be restored.
+///
+/// The file is written by a background thread, a moment after the last change,
+/// so request handling never waits on disk. Activity is written at most every
+/// `ACTIVITY_SAVE_INTERVAL_SECS`, so sessions still in use when the process is
+/// killed are not restored as idle since their creation.
+pub struct PersistentSessionManager {
+    local: LocalSessionManager,
+    store: Arc<SessionStore>,
+    restorer: RwLock<Option<SessionRestorer>>,
+    shutdown_token: RwLock<Option<CancellationToken>>,
+    /// Serializes restores so concurrent requests do not restore a session twice
+    restore_lock: tokio::sync::Mutex<()>,
+    /// Unix time of the last save requested because of session activity
+    last_activity_save: AtomicI64,
+    saver: Option<mpsc::Sender<SaveRequest>>,
+    writer: Mutex<Option<std::thread::JoinHandle<()>>>,
+}
+
+/// Persisted sessions and the file they are written to, shared with the writer thread
+struct SessionStore {
+    sessions_file: PathBuf,
+    persisted: RwLock<HashMap<SessionId, PersistedSession>>,
+}
+
+enum SaveRequest {
+    Save,
+    /// Answered once every change requested before it has been written
+    Flush(mpsc::Sender<()>),
+}
+
+impl PersistentSessionManager {
+    /// Create a persistent session manager for a category, loading sessions saved by a previous run
+    pub fn new(category: &str, session_config: SessionConfig) -> Arc<Self> {
+        Self::with_sessions_file(Self::get_sessions_file_path(category), session_config)
+    }
+
+    /// Create a persistent session manager that keeps its sessions in `sessions_file`
+    pub fn with_sessions_file(sessions_file: PathBuf, session_config: SessionConfig) -> Arc<Self> {
+        let persisted = Self::load_from_disk(&sessions_file, &session_config);
+        let store = Arc::new(SessionStore {
+            sessions_file,
+            persisted: RwLock::new(persisted),
+        });
+
+        let (saver, requests) = mpsc::channel();
+        let writer = {
+            let store = store.clone();
+            std::thread::Builder::new()
+                .name("session-store".to_string())
+                .spawn(move || store.run_writer(requests))
+        };
+        let (saver, writer) = match writer {
+            Ok(writer) => (Some(saver), Some(writer)),
+            Err(e) => {
+                log::error!("Failed to start session store writer: {} - sessions will not be persisted", e);
+                (None, None)
+            }
+        };
+
+        Arc::new(Self {
+            local: LocalSessionManager {
+                sessions: Default::default(),
+                session_config,
+            },
+            store,
+            restorer: RwLock::new(None),
+            shutdown_token: RwLock::new(None),
+            restore_lock: tokio::sync::Mutex::new(()),
+            last_activity_save: AtomicI64::new(chrono::Utc::now().timestamp()),
+            saver,
+            writer: Mutex::new(writer),
+        })
+    }
+
+    /// Get the path to the sessions file on disk
+    #[must_use]
+    pub fn sessions_file_path(&self) -> &std::path::Path {
+        &self.store.sessions_file
+    }
+
+    /// Number of sessions known to this manager (live or restorable)
+    pub fn persisted_count(&self) -> usize {
+        self.store.persisted.read().len()
+    }
+
+    /// Block until every change made so far is written to the sessions file
+    pub fn flush(&self) {
+        let Some(ref saver) = self.saver else {
+            return;
+        };
+        let (done, written) = mpsc::channel();
+        if saver.send(SaveRequest::Flush(done)).is_ok() {
+            let _ = written.recv();
+        }
+    }
+
+    /// Whether a session is currently live in memory (persisted sessions are not restored)
+    pub async fn is_live(&self, id: &SessionId) -> bool {
+        self.local.sessions.read().await.contains_key(id)
+    }
+
+    /// Drop a persisted session that is not live, returning whether it was known
+    pub fn forget(&self, id: &SessionId) -> bool {
+        let forgotten = self.store.persisted.write().remove(id).is_some();
+        if forgotten {
+            self.request_save();
+        }
+        forgotten
+    }
+
+    /// Install the callback used to serve restored sessions
+    ///
+    /// `shutdown_token` is the server's cancellation token: sessions closed after it
+    /// is cancelled are kept on disk rather than forgotten.
+    pub fn attach(&self, restorer: SessionRestorer, shutdown_token: CancellationToken) {
+        *self.restorer.write() = Some(restorer);
+        *self.shutdown_token.write() = Some(shutdown_token);
+    }
+
+    /// Get sessions file path using kodegen_config
+    fn get_sessions_file_path(category: &str) -> PathBuf {
+        kodegen_config::KodegenConfig::data_dir()
+            .map(|dir| dir.join("sessions").join(format!("sessions_{category}.json")))
+            .unwrap_or_else(|_| PathBuf::from(format!("sessions_{category}.json")))
+    }
+
+    /// Load persisted sessions, dropping any idle longer than the keep-alive
+    fn load_from_disk(
+        sessions_file: &Path,
+        session_config: &SessionConfig,
+    ) -> HashMap<SessionId, PersistedSession> {
+        let map = match std::fs::read_to_string(sessions_file) {
+            Ok(json) => match serde_json::from_str::<HashMap<String, PersistedSession>>(&json) {
+                Ok(map) => map,
+                Err(e) => {
+                    log::warn!("Failed to parse sessions file {}: {} - starting fresh", sessions_file.display(), e);
+                    return HashMap::new();
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                log::debug!("No existing sessions file at {} - starting fresh", sessions_file.display());
+                return HashMap::new();
+            }
+            Err(e) => {
+                log::warn!("Failed to read sessions file {}: {} - starting fresh", sessions_file.display(), e);
+                return HashMap::new();
+            }
+        };
+
+        let now = chrono::Utc::now().timestamp();
+        let max_idle = session_config
+            .keep_alive
+            .map(|keep_alive| i64::try_from(keep_alive.as_secs()).unwrap_or(i64::MAX));
+
+        let sessions: HashMap<SessionId, PersistedSession> = map
+            .into_iter()
+            .filter(|(_, session)| max_idle.is_none_or(|max_idle| now - session.last_active <= max_idle))
+            .map(|(id, session)| (SessionId::from(id), session))
+            .collect();
+
+        log::info!("Loaded {} restorable sessions from {}", sessions.len(), sessions_file.display());
+        sessions
+    }
+
+    /// Have the writer thread save the sessions file
+    fn request_save(&self) {
+        if let Some(ref saver) = self.saver {
+            let _ = saver.send(SaveRequest::Save);
+        }
+    }
+
+    /// Record activity on a session, saving it if activity was last saved a while ago
+    fn touch(&self, id: &SessionId) {
+        let now = chrono::Utc::now().timestamp();
+        match self.store.persisted.write().get_mut(id) {
+            Some(session) => session.last_active = now,
+            None => return,
+        }
+
+        let last_save = self.last_activity_save.load(Ordering::Relaxed);
+        if now - last_save >= ACTIVITY_SAVE_INTERVAL_SECS
+            && self
+                .last_activity_save
+                .compare_exchange(last_save, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.request_save();
+        }
+    }
+
+    /// Recreate a persisted session under its original id and replay its initialization
+    ///
+    /// Returns Ok(false) if the session is unknown or no restorer is attached yet.
+    async fn restore(&self, id: &SessionId) -> Result<bool, LocalSessionManagerError> {
+        let _guard = self.restore_lock.lock().await;
+
+        // Another request may have restored it while we waited for the lock
+        if self.local.sessions.read().await.contains_key(id) {
+            return Ok(true);
+        }
+
+        let Some(persisted) = self.store.persisted.read().get(id).cloned() else {
+            return Ok(false);
+        };
+
+        let Some(restorer) = self.restorer.read().clone() else {
+            log::warn!("Cannot restore session {} - server has not attached a restorer", id);
+            return Ok(false);
+        };
+
+        log::info!("Restoring session {} (created at {})", id, persisted.created_at);
+
+        let (handle, worker) = create_local_session(id.clone(), self.local.session_config.clone());
+        self.local.sessions.write().await.insert(id.clone(), handle.clone());
+
+        // Serve the session; the server closes it through its session layer when the service ends
+        tokio::spawn(restorer(id.clone(), WorkerTransport::spawn(worker)));
+
+        // Replay the handshake: initialize request, then the initialized notification
+        let initialized: ClientJsonRpcMessage = serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/initialized",
+        }))
+        .expect("initialized notification is valid JSON-RPC");
+        let handshake = async {
+            handle.initialize(persisted.initialize).await?;
+            handle.push_message(initialized, None).await
+        };
+        if let Err(e) = handshake.await {
+            // The service never took the session (e.g. the session cap was reached) - keep it on disk
+            log::warn!("Failed to restore session {}: {}", id, e);
+            self.local.sessions.write().await.remove(id);
+            return Err(e.into());
+        }
+
+        self.touch(id);
+        log::info!("Session {} restored", id);
+        Ok(true)
+    }
+}
+
+impl Drop for PersistentSessionManager {
+    /// Write out pending changes before the manager goes away
+    fn drop(&mut self) {
+        drop(self.saver.take());
+        if let Some(writer) = self.writer.lock().take()
+            && writer.join().is_err()
+        {
+            log::error!("Session store writer for {} panicked", self.store.sessions_file.display());
+        }
+    }
+}
+
+impl SessionStore {
+    /// Writer thread: save a moment after each change, until the manager is dropped
+    fn run_writer(&self, requests: mpsc::Receiver<SaveRequest>) {
+        let mut dirty = false;
+        while let Ok(request) = requests.recv() {
+            let mut flushes = Vec::new();
+            match request {
+                SaveRequest::Save => {
+                    dirty = true;
+                    // Wait for the burst of changes to settle, unless someone is waiting on a flush
+                    let deadline = Instant::now() + SAVE_DEBOUNCE;
+                    while flushes.is_empty() {
+                        match requests.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                            Ok(SaveRequest::Save) => {}
+                            Ok(SaveRequest::Flush(done)) => flushes.push(done),
+                            Err(_) => break,
+                        }
+                    }
+                }
+                SaveRequest::Flush(done) => flushes.push(done),
+            }
+
+            if dirty {
+                self.save();
+                dirty = false;
+            }
+            for done in flushes {
+                let _ = done.send(());
+            }
+        }
+
+        if dirty {
+            self.save();
+        }
+    }
+
+    /// Save persisted sessions to disk (atomic write with temp file)
+    fn save(&self) {
+        let snapshot: HashMap<String, PersistedSession> = self
+            .persisted
+            .read()
+            .iter()
+            .map(|(id, session)| (id.to_string(), session.clone()))
+            .collect();
+
+        let json = match serde_json::to_string_pretty(&snapshot) {
+            Ok(j) => j,
+            Err(e) => {
+                log::error!("Failed to serialize sessions: {}", e);
+                return;
+            }
+        };
+
+        if let Some(parent) = self.sessions_file.parent()
+            && let Err(e) = std::fs::create_dir_all(parent) {
+                log::error!("Failed to create sessions directory {}: {}", parent.display(), e);
+                return;
+            }
+
+        let temp_file = self.sessions_file.with_extension("json.tmp");
+
+        if let Err(e) = std::fs::write(&temp_file, json) {
+            log::error!("Failed to write temp sessions file {}: {}", temp_file.display(), e);
+            return;
+        }
+
+        if let Err(e) = std::fs::rename(&temp_file, &self.sessions_file) {
+            log::error!("Failed to rename {} to {}: {}", temp_file.display(), self.sessions_file.display(), e);
+            let _ = std::fs::remove_file(&temp_file);
+            return;
+        }
+
+        log::debug!("Saved {} sessions to {}", snapshot.len(), self.sessions_file.display());
+    }
+}
+
+impl SessionManager for PersistentSessionManager {
+    type Error = LocalSessionManagerError;
+    type Transport = SessionTransport;
+
+    async fn create_session(&self) -> Result<(SessionId, Self::Transport), Self::Error> {
+        self.local.create_session().await
+    }
+
+    async fn initialize_session(
+        &self,
+        id: &SessionId,
+        message: ClientJsonRpcMessage,
+    ) -> Result<ServerJsonRpcMessage, Self::Error> {
+        let now = chrono::Utc::now().timestamp();
+        self.store.persisted.write().insert(
+            id.clone(),
+            PersistedSession {
+                initialize: message.clone(),
+                created_at: now,
+                last_active: now,
+            },
+        );
+
+        let response = self.local.initialize_session(id, message).await;
+        match &response {
+            Ok(_) => self.request_save(),
+            Err(_) => {
+                self.store.persisted.write().remove(id);
+            }
+        }
+        response
+    }
+
+    async fn has_session(&self, id: &SessionId) -> Result<bool, Self::Error> {
+        if self.local.has_session(id).await? {
+            return Ok(true);
+        }
+        if !self.store.persisted.read().contains_key(id) {
+            return Ok(false);
+        }
+        self.restore(id).await
+    }
+
+    async fn close_session(&self, id: &SessionId) -> Result<(), Self::Error> {
+        let shutting_down = self
+            .shutdown_token
+            .read()
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled());
+
+        // Keep sessions that end because the server is shutting down (saving their last activity)
+        if shutting_down || self.store.persisted.write().remove(id).is_some() {
+            self.request_save();
+        }
+
+        self.local.close_session(id).await
+    }
+
+    async fn create_stream(
+        &self,
+        id: &SessionId,
+        message: ClientJsonRpcMessage,
+    ) -> Result<impl Stream<Item = ServerSseMessage> + Send + Sync + 'static, Self::Error> {
+        self.touch(id);
+        self.local.create_stream(id, message).await
+    }
+
+    async fn accept_message(
+        &self,
+        id: &SessionId,
+        message: ClientJsonRpcMessage,
+    ) -> Result<(), Self::Error> {
+        self.touch(id);
+        self.local.accept_message(id, message).await
+    }
+
+    async fn create_standalone_stream(
+        &self,
+        id: &SessionId,
+    ) -> Result<impl Stream<Item = ServerSseMessage> + Send + Sync + 'static, Self::Error> {
+        self.local.create_standalone_stream(id).await
+    }
+
+    async fn resume(
+        &self,
+        id: &SessionId,
+        last_event_id: String,
+    ) -> Result<impl Stream<Item = ServerSseMessage> + Send + Sync + 'static, Self::Error> {
+        self.local.resume(id, last_event_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::{ServerHandler, ServiceExt};
+
+    struct TestServer;
+
+    impl ServerHandler for TestServer {}
+
+    /// Fresh sessions file path, unique per test
+    fn sessions_file(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kodegen-sessions-test-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.join("sessions_test.json")
+    }
+
+    fn config(keep_alive: Option<Duration>) -> SessionConfig {
+        SessionConfig {
+            keep_alive,
+            ..SessionConfig::default()
+        }
+    }
+
+    fn initialize_request() -> ClientJsonRpcMessage {
+        serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2025-03-26",
+                "capabilities": {},
+                "clientInfo": { "name": "test-client", "version": "1.0.0" },
+            },
+        }))
+        .unwrap()
+    }
+
+    /// Serve a session's transport with `TestServer` until it ends
+    async fn serve(transport: SessionTransport) {
+        if let Ok(service) = TestServer.serve(transport).await {
+            let _ = service.waiting().await;
+        }
+    }
+
+    fn restorer() -> SessionRestorer {
+        Arc::new(|_, transport| Box::pin(serve(transport)))
+    }
+
+    fn write_sessions(path: &Path, sessions: &[(&str, i64)]) {
+        let sessions: HashMap<&str, PersistedSession> = sessions
+            .iter()
+            .map(|&(id, last_active)| {
+                let session = PersistedSession {
+                    initialize: initialize_request(),
+                    created_at: last_active,
+                    last_active,
+                };
+                (id, session)
+            })
+            .collect();
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, serde_json::to_string(&sessions).unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn initialized_sessions_are_restored_by_a_new_manager() {
+        let path = sessions_file("restore");
+        let id = {
+            let manager = PersistentSessionManager::with_sessions_file(path.clone(), config(None));
+            let (id, transport) = manager.create_session().await.unwrap();
+            tokio::spawn(serve(transport));
+            manager.initialize_session(&id, initialize_request()).await.unwrap();
+            manager.flush();
+            id
+        };
+
+        let manager = PersistentSessionManager::with_sessions_file(path.clone(), config(None));
+        assert_eq!(manager.persisted_count(), 1);
+        assert!(!manager.is_live(&id).await);
+
+        // Nothing can serve the session until the server attaches its restorer
+        assert!(!manager.has_session(&id).await.unwrap());
+        manager.attach(restorer(), CancellationToken::new());
+        assert!(manager.has_session(&id).await.unwrap());
+        assert!(manager.is_live(&id).await);
+
+        // Closing it outside of shutdown forgets it
+        manager.close_session(&id).await.unwrap();
+        manager.flush();
+        drop(manager);
+        let manager = PersistentSessionManager::with_sessions_file(path, config(None));
+        assert_eq!(manager.persisted_count(), 0);
+        assert!(!manager.has_session(&id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn sessions_closed_during_shutdown_are_kept() {
+        let path = sessions_file("shutdown");
+        let manager = PersistentSessionManager::with_sessions_file(path.clone(), config(None));
+        let shutdown = CancellationToken::new();
+        manager.attach(restorer(), shutdown.clone());
+        let (id, transport) = manager.create_session().await.unwrap();
+        tokio::spawn(serve(transport));
+        manager.initialize_session(&id, initialize_request()).await.unwrap();
+
+        shutdown.cancel();
+        manager.close_session(&id).await.unwrap();
+        drop(manager);
+
+        let manager = PersistentSessionManager::with_sessions_file(path, config(None));
+        assert_eq!(manager.persisted_count(), 1);
+    }
+
+    #[test]
+    fn sessions_idle_past_the_keep_alive_expire_on_load() {
+        let path = sessions_file("expiry");
+        let now = chrono::Utc::now().timestamp();
+        write_sessions(&path, &[("fresh", now - 60), ("stale", now - 7200)]);
+
+        let manager = PersistentSessionManager::with_sessions_file(path.clone(), config(Some(Duration::from_secs(3600))));
+        assert_eq!(manager.persisted_count(), 1);
+        assert!(manager.store.persisted.read().contains_key(&SessionId::from("fresh")));
+
+        // Without a keep-alive nothing expires
+        let manager = PersistentSessionManager::with_sessions_file(path, config(None));
+        assert_eq!(manager.persisted_count(), 2);
+    }
+
+    #[test]
+    fn activity_is_saved_at_most_once_per_interval() {
+        let path = sessions_file("activity");
+        let created = chrono::Utc::now().timestamp() - 600;
+        write_sessions(&path, &[("active", created)]);
+        let id = SessionId::from("active");
+        let last_active = || {
+            let json = std::fs::read_to_string(&path).unwrap();
+            let sessions: HashMap<String, PersistedSession> = serde_json::from_str(&json).unwrap();
+            sessions["active"].last_active
+        };
+
+        let manager = PersistentSessionManager::with_sessions_file(path.clone(), config(None));
+        manager.touch(&id);
+        manager.flush();
+        assert_eq!(last_active(), created);
+
+        manager.last_activity_save.store(0, Ordering::Relaxed);
+        manager.touch(&id);
+        manager.flush();
+        assert!(last_active() > created);
+    }
+
+    #[test]
+    fn corrupt_sessions_file_starts_fresh() {
+        let path = sessions_file("corrupt");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "{ not json").unwrap();
+
+        let manager = PersistentSessionManager::with_sessions_file(path.clone(), config(None));
+        assert_eq!(manager.persisted_count(), 0);
+
+        // The next save replaces the corrupt file
+        manager.request_save();
+        manager.flush();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().trim(), "{}");
+    }
+}
@@ -0,0 +1,216 @@
+use dashmap::DashMap;
+use rmcp::model::{ClientJsonRpcMessage, ClientRequest, Implementation};
+use rmcp::transport::streamable_http_server::session::SessionId;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Information about one MCP session, returned by `GET /mcp/sessions`
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionInfo {
+    pub session_id: String,
+    pub created_at: String,    // RFC 3339
+    pub last_activity: String, // RFC 3339
+    /// Client implementation reported in the `initialize` request
+    pub client_info: Option<Implementation>,
+    /// Connection id the session's tool calls were attributed to
+    pub connection_id: Option<String>,
+    /// Tool calls currently executing on this session
+    pub in_flight_calls: usize,
+}
+
+/// Registry entry: public info plus a monotonic activity clock for idle checks
+struct SessionEntry {
+    info: SessionInfo,
+    last_active: Instant,
+}
+
+impl SessionEntry {
+    fn new(session_id: &SessionId) -> Self {
+        let now = chrono::Utc::now().to_rfc3339();
+        Self {
+            info: SessionInfo {
+                session_id: session_id.to_string(),
+                created_at: now.clone(),
+                last_activity: now,
+                client_info: None,
+                connection_id: None,
+                in_flight_calls: 0,
+            },
+            last_active: Instant::now(),
+        }
+    }
+}
+
+/// Registry of open MCP sessions
+///
+/// Fed by `SessionLayer` (creation, restore, initialization, activity, close) and
+/// by `call_tool` (connection id, in-flight calls). Only creation and restore add
+/// entries; updates for unknown ids are ignored, so a late request or callback for
+/// a session that already ended cannot bring it back and hold a slot under the cap.
+#[derive(Default)]
+pub struct SessionRegistry {
+    sessions: DashMap<SessionId, SessionEntry>,
+}
+
+impl SessionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a newly created session
+    pub fn register(&self, session_id: &SessionId) {
+        self.sessions
+            .entry(session_id.clone())
+            .or_insert_with(|| SessionEntry::new(session_id));
+    }
+
+    /// Record client info from the session's `initialize` request
+    pub fn record_initialize(&self, session_id: &SessionId, message: &ClientJsonRpcMessage) {
+        let ClientJsonRpcMessage::Request(request) = message else {
+            return;
+        };
+        let ClientRequest::InitializeRequest(initialize) = &request.request else {
+            return;
+        };
+
+        if let Some(mut entry) = self.sessions.get_mut(session_id) {
+            entry.info.client_info = Some(initialize.params.client_info.clone());
+        }
+    }
+
+    /// Record activity on a session
+    pub fn touch(&self, session_id: &SessionId) {
+        if let Some(mut entry) = self.sessions.get_mut(session_id) {
+            entry.info.last_activity = chrono::Utc::now().to_rfc3339();
+            entry.last_active = Instant::now();
+        }
+    }
+
+    /// Record the connection id a session's tool calls are attributed to
+    pub fn set_connection_id(&self, session_id: &SessionId, connection_id: &str) {
+        if let Some(mut entry) = self.sessions.get_mut(session_id)
+            && entry.info.connection_id.as_deref() != Some(connection_id)
+        {
+            entry.info.connection_id = Some(connection_id.to_string());
+        }
+    }
+
+    /// Track a tool call on a session until the returned guard is dropped
+    pub fn begin_call(self: &Arc<Self>, session_id: &SessionId) -> InFlightGuard {
+        if let Some(mut entry) = self.sessions.get_mut(session_id) {
+            entry.info.in_flight_calls += 1;
+        }
+        InFlightGuard {
+            registry: self.clone(),
+            session_id: session_id.clone(),
+        }
+    }
+
+    /// Get info for one session
+    pub fn get(&self, session_id: &SessionId) -> Option<SessionInfo> {
+        self.sessions.get(session_id).map(|entry| entry.info.clone())
+    }
+
+    /// Get info for all sessions, oldest first
+    pub fn list(&self) -> Vec<SessionInfo> {
+        let mut sessions: Vec<SessionInfo> = self
+            .sessions
+            .iter()
+            .map(|entry| entry.info.clone())
+            .collect();
+        sessions.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        sessions
+    }
+
+    /// Session with no tool call in flight that has been inactive the longest
+    pub fn least_recently_active_idle(&self) -> Option<SessionId> {
+        self.sessions
+            .iter()
+            .filter(|entry| entry.info.in_flight_calls == 0)
+            .min_by_key(|entry| entry.last_active)
+            .map(|entry| entry.key().clone())
+    }
+
+    /// Whether any open session is attributed to `connection_id`
+    pub fn has_connection(&self, connection_id: &str) -> bool {
+        self.sessions
+            .iter()
+            .any(|entry| entry.info.connection_id.as_deref() == Some(connection_id))
+    }
+
+    /// Number of open sessions
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Whether no sessions are open
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Forget a session, returning its last known info
+    pub fn remove(&self, session_id: &SessionId) -> Option<SessionInfo> {
+        self.sessions.remove(session_id).map(|(_, entry)| entry.info)
+    }
+}
+
+/// RAII guard for an in-flight tool call on a session
+///
+/// Decrements the session's in-flight counter on drop, even if the call panics.
+pub struct InFlightGuard {
+    registry: Arc<SessionRegistry>,
+    session_id: SessionId,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if let Some(mut entry) = self.registry.sessions.get_mut(&self.session_id) {
+            entry.info.in_flight_calls = entry.info.in_flight_calls.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_for_unknown_sessions_do_not_create_entries() {
+        let registry = Arc::new(SessionRegistry::new());
+        let open: SessionId = Arc::from("open");
+        let closed: SessionId = Arc::from("closed");
+        registry.register(&open);
+        registry.register(&closed);
+        registry.remove(&closed);
+
+        registry.touch(&closed);
+        registry.set_connection_id(&closed, "conn-1");
+        drop(registry.begin_call(&closed));
+
+        assert_eq!(registry.len(), 1);
+        assert!(registry.get(&closed).is_none());
+        assert!(!registry.has_connection("conn-1"));
+
+        registry.set_connection_id(&open, "conn-2");
+        assert!(registry.has_connection("conn-2"));
+    }
+
+    #[test]
+    fn in_flight_calls_keep_a_session_from_being_idle() {
+        let registry = Arc::new(SessionRegistry::new());
+        let busy: SessionId = Arc::from("busy");
+        let idle: SessionId = Arc::from("idle");
+        registry.register(&idle);
+        registry.register(&busy);
+        registry.touch(&idle);
+
+        let call = registry.begin_call(&busy);
+        assert_eq!(registry.get(&busy).unwrap().in_flight_calls, 1);
+        assert_eq!(registry.least_recently_active_idle(), Some(idle.clone()));
+
+        drop(call);
+        assert_eq!(registry.get(&busy).unwrap().in_flight_calls, 0);
+        assert_eq!(registry.least_recently_active_idle(), Some(busy));
+    }
+}
@@ -0,0 +1,58 @@
+use rmcp::RoleServer;
+use rmcp::service::{RxJsonRpcMessage, TxJsonRpcMessage};
+use rmcp::transport::Transport;
+use std::borrow::Cow;
+use std::future::Future;
+
+/// Session transport that runs a callback when the session's service lets go of it
+///
+/// rmcp drops the transport once the service serving a session ends, whatever the
+/// reason (client DELETE, keep-alive expiry, worker error), which makes the drop
+/// the one reliable signal that a session is over.
+pub struct TrackedTransport<T> {
+    inner: T,
+    on_drop: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl<T> TrackedTransport<T> {
+    pub fn new(inner: T, on_drop: impl FnOnce() + Send + 'static) -> Self {
+        Self {
+            inner,
+            on_drop: Some(Box::new(on_drop)),
+        }
+    }
+}
+
+impl<T> Transport<RoleServer> for TrackedTransport<T>
+where
+    T: Transport<RoleServer>,
+{
+    type Error = T::Error;
+
+    fn name() -> Cow<'static, str> {
+        T::name()
+    }
+
+    fn send(
+        &mut self,
+        item: TxJsonRpcMessage<RoleServer>,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send + 'static {
+        self.inner.send(item)
+    }
+
+    fn receive(&mut self) -> impl Future<Output = Option<RxJsonRpcMessage<RoleServer>>> + Send {
+        self.inner.receive()
+    }
+
+    fn close(&mut self) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        self.inner.close()
+    }
+}
+
+impl<T> Drop for TrackedTransport<T> {
+    fn drop(&mut self) {
+        if let Some(on_drop) = self.on_drop.take() {
+            on_drop();
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use crate::config::ServerConfig;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// Maximum characters of the arguments kept in logs and `/mcp/slow-calls`
+const ARGS_PREVIEW_CHARS: usize = 200;
+
+/// A tool call that exceeded its slow-call threshold
+#[derive(Debug, Clone, Serialize)]
+pub struct SlowCall {
+    pub timestamp: DateTime<Utc>,
+    pub tool_name: String,
+    pub connection_id: Option<String>,
+    pub request_id: Option<String>,
+    pub duration_ms: u64,
+    pub threshold_ms: u64,
+    pub success: bool,
+    /// Arguments JSON, truncated to 200 characters
+    pub args_preview: String,
+}
+
+/// Detects slow tool calls and keeps the most recent ones in a ring buffer
+///
+/// Thresholds come from `ServerConfig`: `slow_call_threshold_ms` applies to every
+/// tool unless `slow_call_thresholds` has an entry for it. A threshold of 0
+/// disables detection.
+pub struct SlowCallDetector {
+    default_threshold_ms: u64,
+    tool_thresholds_ms: HashMap<String, u64>,
+    capacity: usize,
+    recent: parking_lot::Mutex<VecDeque<SlowCall>>,
+}
+
+impl SlowCallDetector {
+    pub fn new(config: &ServerConfig) -> Self {
+        Self {
+            default_threshold_ms: config.slow_call_threshold_ms,
+            tool_thresholds_ms: config
+                .slow_call_thresholds
+                .iter()
+                .map(|(tool, ms)| (tool.clone(), *ms))
+                .collect(),
+            capacity: config.slow_call_history,
+            recent: parking_lot::Mutex::new(VecDeque::with_capacity(config.slow_call_history)),
+        }
+    }
+
+    /// Threshold for `tool_name` in ms (None = detection disabled)
+    pub fn threshold_ms(&self, tool_name: &str) -> Option<u64> {
+        let threshold = self
+            .tool_thresholds_ms
+            .get(tool_name)
+            .copied()
+            .unwrap_or(self.default_threshold_ms);
+        (threshold > 0).then_some(threshold)
+    }
+
+    /// Check a finished call; logs and records it if slow
+    ///
+    /// Returns whether the call was slow.
+    pub fn check(
+        &self,
+        tool_name: &str,
+        connection_id: Option<&str>,
+        request_id: Option<&str>,
+        arguments: &serde_json::Value,
+        duration_ms: u64,
+        success: bool,
+    ) -> bool {
+        let Some(threshold_ms) = self.threshold_ms(tool_name) else {
+            return false;
+        };
+        if duration_ms < threshold_ms {
+            return false;
+        }
+
+        let args_preview = args_preview(arguments);
+        log::warn!(
+            "Slow tool call: {} took {}ms (threshold {}ms, connection {}) args: {}",
+            tool_name,
+            duration_ms,
+            threshold_ms,
+            connection_id.unwrap_or("-"),
+            args_preview
+        );
+
+        if self.capacity > 0 {
+            let mut recent = self.recent.lock();
+            if recent.len() >= self.capacity {
+                recent.pop_front();
+            }
+            recent.push_back(SlowCall {
+                timestamp: Utc::now(),
+                tool_name: tool_name.to_string(),
+                connection_id: connection_id.map(str::to_string),
+                request_id: request_id.map(str::to_string),
+                duration_ms,
+                threshold_ms,
+                success,
+                args_preview,
+            });
+        }
+
+        true
+    }
+
+    /// Most recent slow calls, newest first, optionally filtered by connection and tool
+    pub fn recent(&self, connection_id: Option<&str>, tool_name: Option<&str>, limit: usize) -> Vec<SlowCall> {
+        self.recent
+            .lock()
+            .iter()
+            .rev()
+            .filter(|call| connection_id.is_none_or(|id| call.connection_id.as_deref() == Some(id)))
+            .filter(|call| tool_name.is_none_or(|name| call.tool_name == name))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Parse `tool=ms` pairs separated by commas (as in `KODEGEN_SLOW_CALL_THRESHOLDS`)
+pub fn parse_slow_call_thresholds(s: &str) -> Result<BTreeMap<String, u64>, String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .map(parse_slow_call_threshold)
+        .collect()
+}
+
+/// Parse one `tool=ms` directive (as in `--slow-call-threshold`)
+pub fn parse_slow_call_threshold(directive: &str) -> Result<(String, u64), String> {
+    let (tool, ms) = directive
+        .split_once('=')
+        .ok_or_else(|| format!("invalid slow-call threshold '{}' (expected tool=ms)", directive))?;
+    let (tool, ms) = (tool.trim(), ms.trim());
+    if tool.is_empty() {
+        return Err(format!("invalid slow-call threshold '{}' (tool is empty)", directive));
+    }
+    let ms = ms
+        .parse()
+        .map_err(|_| format!("invalid slow-call threshold '{}' for tool {} (expected milliseconds)", ms, tool))?;
+    Ok((tool.to_string(), ms))
+}
+
+/// Arguments JSON truncated to `ARGS_PREVIEW_CHARS` characters
+fn args_preview(arguments: &serde_json::Value) -> String {
+    let json = arguments.to_string();
+    match json.char_indices().nth(ARGS_PREVIEW_CHARS) {
+        Some((end, _)) => format!("{}...", &json[..end]),
+        None => json,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn detector(default_ms: u64, overrides: &[(&str, u64)], history: usize) -> SlowCallDetector {
+        SlowCallDetector::new(&ServerConfig {
+            slow_call_threshold_ms: default_ms,
+            slow_call_thresholds: overrides.iter().map(|(tool, ms)| (tool.to_string(), *ms)).collect(),
+            slow_call_history: history,
+            ..ServerConfig::default()
+        })
+    }
+
+    fn check(detector: &SlowCallDetector, tool_name: &str, connection_id: &str, duration_ms: u64) -> bool {
+        detector.check(tool_name, Some(connection_id), None, &json!({}), duration_ms, true)
+    }
+
+    #[test]
+    fn per_tool_thresholds_override_the_default() {
+        let detector = detector(1000, &[("search", 5000), ("fast", 0)], 10);
+        assert_eq!(detector.threshold_ms("read"), Some(1000));
+        assert_eq!(detector.threshold_ms("search"), Some(5000));
+        assert_eq!(detector.threshold_ms("fast"), None);
+
+        assert!(!check(&detector, "read", "c1", 999));
+        assert!(check(&detector, "read", "c1", 1000), "threshold is inclusive");
+        assert!(!check(&detector, "search", "c1", 4000));
+        assert!(!check(&detector, "fast", "c1", u64::MAX));
+    }
+
+    #[test]
+    fn zero_default_disables_detection_except_for_overrides() {
+        let detector = detector(0, &[("search", 10)], 10);
+        assert!(!check(&detector, "read", "c1", 60_000));
+        assert!(check(&detector, "search", "c1", 10));
+    }
+
+    #[test]
+    fn history_keeps_the_newest_calls() {
+        let detector = detector(1, &[], 2);
+        for duration_ms in [10, 20, 30] {
+            check(&detector, "read", "c1", duration_ms);
+        }
+
+        let durations: Vec<_> = detector.recent(None, None, 10).iter().map(|call| call.duration_ms).collect();
+        assert_eq!(durations, [30, 20]);
+        assert_eq!(detector.recent(None, None, 1).len(), 1);
+    }
+
+    #[test]
+    fn zero_history_still_reports_slow_calls() {
+        let detector = detector(1, &[], 0);
+        assert!(check(&detector, "read", "c1", 10));
+        assert!(detector.recent(None, None, 10).is_empty());
+    }
+
+    #[test]
+    fn recent_filters_by_connection_and_tool() {
+        let detector = detector(1, &[], 10);
+        check(&detector, "read", "c1", 10);
+        check(&detector, "write", "c1", 10);
+        check(&detector, "read", "c2", 10);
+
+        assert_eq!(detector.recent(Some("c1"), None, 10).len(), 2);
+        assert_eq!(detector.recent(None, Some("read"), 10).len(), 2);
+        let calls = detector.recent(Some("c2"), Some("read"), 10);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].connection_id.as_deref(), Some("c2"));
+        assert_eq!(calls[0].threshold_ms, 1);
+    }
+
+    #[test]
+    fn records_outcome_and_truncated_arguments() {
+        let detector = detector(1, &[], 10);
+        let arguments = json!({ "text": "x".repeat(500) });
+        detector.check("write", None, Some("req-1"), &arguments, 5, false);
+
+        let call = &detector.recent(None, None, 1)[0];
+        assert!(!call.success);
+        assert_eq!(call.request_id.as_deref(), Some("req-1"));
+        assert_eq!(call.args_preview.chars().count(), ARGS_PREVIEW_CHARS + 3);
+        assert!(call.args_preview.ends_with("..."));
+    }
+
+    #[test]
+    fn argument_preview_does_not_split_characters() {
+        let preview = args_preview(&json!("é".repeat(300)));
+        assert_eq!(preview.chars().count(), ARGS_PREVIEW_CHARS + 3);
+        assert_eq!(args_preview(&json!({ "a": 1 })), r#"{"a":1}"#);
+    }
+
+    #[test]
+    fn parses_threshold_directives() {
+        let thresholds = parse_slow_call_thresholds("search=5000, read = 100,").unwrap();
+        assert_eq!(thresholds.get("search"), Some(&5000));
+        assert_eq!(thresholds.get("read"), Some(&100));
+
+        assert!(parse_slow_call_threshold("search").is_err());
+        assert!(parse_slow_call_threshold("=100").is_err());
+        assert!(parse_slow_call_threshold("search=soon").is_err());
+    }
+}
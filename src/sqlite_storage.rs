@@ -0,0 +1,503 @@
+use crate::storage::{HISTORY_DISK_ENTRIES, HistoryQuery, StatsSnapshot, Storage, StoredCall};
+use crate::time_buckets::{Granularity, ToolBucket, UsageBuckets};
+use crate::tool_history::{CallAnnotations, HistoryRecord};
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use kodegen_mcp_schema::tool::tool_history::ToolCallRecord;
+use rusqlite::types::Value;
+use rusqlite::{Connection, OpenFlags, params};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS connection_stats (
+    connection_id TEXT PRIMARY KEY,
+    last_used INTEGER NOT NULL,
+    stats TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS tool_latency (
+    tool_name TEXT PRIMARY KEY,
+    histogram TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS usage_buckets (
+    granularity TEXT NOT NULL,
+    start INTEGER NOT NULL,
+    tool_name TEXT NOT NULL,
+    calls INTEGER NOT NULL,
+    failures INTEGER NOT NULL,
+    latency TEXT NOT NULL,
+    PRIMARY KEY (granularity, start, tool_name)
+);
+CREATE INDEX IF NOT EXISTS usage_buckets_tool ON usage_buckets (tool_name, granularity, start);
+CREATE TABLE IF NOT EXISTS tool_calls (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    connection_id TEXT,
+    timestamp TEXT NOT NULL,
+    timestamp_ms INTEGER NOT NULL,
+    tool_name TEXT NOT NULL,
+    args_json TEXT NOT NULL,
+    output_json TEXT NOT NULL,
+    duration_ms INTEGER,
+    slow INTEGER NOT NULL DEFAULT 0,
+    request_id TEXT
+);
+CREATE INDEX IF NOT EXISTS tool_calls_connection ON tool_calls (connection_id, timestamp_ms);
+CREATE INDEX IF NOT EXISTS tool_calls_tool ON tool_calls (tool_name, timestamp_ms);
+CREATE INDEX IF NOT EXISTS tool_calls_time ON tool_calls (timestamp_ms);
+";
+
+const CALL_COLUMNS: &str =
+    "connection_id, timestamp, tool_name, args_json, output_json, duration_ms, slow, request_id";
+
+/// Stats rows as stored, keyed like their table's primary key
+///
+/// `save_stats` compares a snapshot against the rows it last wrote and only
+/// upserts rows that changed and deletes rows that are gone.
+#[derive(Default)]
+struct StatsRows {
+    /// connection_id -> (last_used, stats)
+    connections: HashMap<String, (i64, String)>,
+    /// tool_name -> histogram
+    latency: HashMap<String, String>,
+    /// (granularity, start, tool_name) -> (calls, failures, latency)
+    buckets: HashMap<(Granularity, i64, String), (i64, i64, String)>,
+}
+
+impl StatsRows {
+    fn from_snapshot(snapshot: &StatsSnapshot) -> Result<Self> {
+        let mut rows = Self::default();
+        for (connection_id, stats) in &snapshot.connections {
+            // Through `Value` so map fields serialize in sorted order and unchanged stats compare equal
+            let stats_json = serde_json::to_value(stats)?.to_string();
+            rows.connections.insert(connection_id.clone(), (stats.last_used, stats_json));
+        }
+        for (tool_name, histogram) in &snapshot.global_latency {
+            rows.latency.insert(tool_name.clone(), serde_json::to_string(histogram)?);
+        }
+        for (granularity, start, tool_name, bucket) in snapshot.buckets.iter() {
+            rows.buckets.insert(
+                (granularity, start, tool_name.to_string()),
+                (
+                    i64::try_from(bucket.calls).unwrap_or(i64::MAX),
+                    i64::try_from(bucket.failures).unwrap_or(i64::MAX),
+                    serde_json::to_string(&bucket.latency)?,
+                ),
+            );
+        }
+        Ok(rows)
+    }
+
+    fn read(connection: &Connection) -> Result<Self> {
+        let mut rows = Self::default();
+
+        let mut statement = connection.prepare("SELECT connection_id, last_used, stats FROM connection_stats")?;
+        let mut query = statement.query([])?;
+        while let Some(row) = query.next()? {
+            rows.connections.insert(row.get(0)?, (row.get(1)?, row.get(2)?));
+        }
+
+        let mut statement = connection.prepare("SELECT tool_name, histogram FROM tool_latency")?;
+        let mut query = statement.query([])?;
+        while let Some(row) = query.next()? {
+            rows.latency.insert(row.get(0)?, row.get(1)?);
+        }
+
+        let mut statement = connection
+            .prepare("SELECT granularity, start, tool_name, calls, failures, latency FROM usage_buckets")?;
+        let mut query = statement.query([])?;
+        while let Some(row) = query.next()? {
+            let granularity: String = row.get(0)?;
+            let Ok(granularity) = granularity.parse::<Granularity>() else {
+                continue;
+            };
+            rows.buckets
+                .insert((granularity, row.get(1)?, row.get(2)?), (row.get(3)?, row.get(4)?, row.get(5)?));
+        }
+        Ok(rows)
+    }
+}
+
+/// SQLite backend: stats, usage buckets and tool calls in one database
+///
+/// Tool calls are indexed by connection, tool and time, so history queries
+/// and trimming do not read the whole history.
+pub struct SqliteStorage {
+    path: PathBuf,
+    connection: parking_lot::Mutex<Connection>,
+    /// Stats rows as of the last save (None until read back on the first save)
+    saved_rows: parking_lot::Mutex<Option<StatsRows>>,
+}
+
+impl SqliteStorage {
+    /// Open (or create) the database at `path`
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create stats directory {}", parent.display()))?;
+        }
+        let connection =
+            Connection::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.pragma_update(None, "synchronous", "NORMAL")?;
+        connection.busy_timeout(std::time::Duration::from_secs(5))?;
+        connection
+            .execute_batch(SCHEMA)
+            .with_context(|| format!("Failed to create schema in {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            connection: parking_lot::Mutex::new(connection),
+            saved_rows: parking_lot::Mutex::new(None),
+        })
+    }
+
+    /// Open another instance's database for reading
+    pub fn open_read_only(path: &Path) -> Result<Self> {
+        let connection = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+        connection.busy_timeout(std::time::Duration::from_secs(5))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            connection: parking_lot::Mutex::new(connection),
+            saved_rows: parking_lot::Mutex::new(None),
+        })
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn stats_path(&self) -> &Path {
+        &self.path
+    }
+
+    fn load_stats(&self) -> Result<Option<StatsSnapshot>> {
+        let connection = self.connection.lock();
+        let mut snapshot = StatsSnapshot::default();
+
+        let mut statement = connection.prepare("SELECT connection_id, stats FROM connection_stats")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let stats: String = row.get(1)?;
+            snapshot.connections.insert(row.get(0)?, serde_json::from_str(&stats)?);
+        }
+
+        let mut statement = connection.prepare("SELECT tool_name, histogram FROM tool_latency")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let histogram: String = row.get(1)?;
+            snapshot.global_latency.insert(row.get(0)?, serde_json::from_str(&histogram)?);
+        }
+
+        let mut buckets = UsageBuckets::default();
+        let mut statement = connection
+            .prepare("SELECT granularity, start, tool_name, calls, failures, latency FROM usage_buckets")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let granularity: String = row.get(0)?;
+            let Ok(granularity) = granularity.parse::<Granularity>() else {
+                continue;
+            };
+            let latency: String = row.get(5)?;
+            let bucket = ToolBucket {
+                calls: row.get::<_, i64>(3)?.try_into().unwrap_or_default(),
+                failures: row.get::<_, i64>(4)?.try_into().unwrap_or_default(),
+                latency: serde_json::from_str(&latency)?,
+            };
+            buckets.insert(granularity, row.get(1)?, row.get(2)?, bucket);
+        }
+        snapshot.buckets = buckets;
+
+        let saved = !snapshot.connections.is_empty()
+            || !snapshot.global_latency.is_empty()
+            || snapshot.buckets.iter().next().is_some();
+        Ok(saved.then_some(snapshot))
+    }
+
+    fn save_stats(&self, snapshot: &StatsSnapshot) -> Result<()> {
+        let rows = StatsRows::from_snapshot(snapshot)?;
+        let mut saved_rows = self.saved_rows.lock();
+        let mut connection = self.connection.lock();
+        // Taken so a failed save reads the rows back next time
+        let saved = match saved_rows.take() {
+            Some(saved) => saved,
+            None => StatsRows::read(&connection)?,
+        };
+
+        let transaction = connection.transaction()?;
+        {
+            let mut upsert = transaction.prepare(
+                "INSERT INTO connection_stats (connection_id, last_used, stats) VALUES (?1, ?2, ?3) \
+                 ON CONFLICT (connection_id) DO UPDATE SET last_used = excluded.last_used, stats = excluded.stats",
+            )?;
+            for (connection_id, row @ (last_used, stats)) in &rows.connections {
+                if saved.connections.get(connection_id) != Some(row) {
+                    upsert.execute(params![connection_id, last_used, stats])?;
+                }
+            }
+            let mut delete = transaction.prepare("DELETE FROM connection_stats WHERE connection_id = ?1")?;
+            for connection_id in saved.connections.keys().filter(|id| !rows.connections.contains_key(*id)) {
+                delete.execute(params![connection_id])?;
+            }
+
+            let mut upsert = transaction.prepare(
+                "INSERT INTO tool_latency (tool_name, histogram) VALUES (?1, ?2) \
+                 ON CONFLICT (tool_name) DO UPDATE SET histogram = excluded.histogram",
+            )?;
+            for (tool_name, histogram) in &rows.latency {
+                if saved.latency.get(tool_name) != Some(histogram) {
+                    upsert.execute(params![tool_name, histogram])?;
+                }
+            }
+            let mut delete = transaction.prepare("DELETE FROM tool_latency WHERE tool_name = ?1")?;
+            for tool_name in saved.latency.keys().filter(|name| !rows.latency.contains_key(*name)) {
+                delete.execute(params![tool_name])?;
+            }
+
+            let mut upsert = transaction.prepare(
+                "INSERT INTO usage_buckets (granularity, start, tool_name, calls, failures, latency) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6) \
+                 ON CONFLICT (granularity, start, tool_name) DO UPDATE SET \
+                 calls = excluded.calls, failures = excluded.failures, latency = excluded.latency",
+            )?;
+            for (key @ (granularity, start, tool_name), row @ (calls, failures, latency)) in &rows.buckets {
+                if saved.buckets.get(key) != Some(row) {
+                    upsert.execute(params![granularity.as_str(), start, tool_name, calls, failures, latency])?;
+                }
+            }
+            // Buckets only disappear from the snapshot when pruned
+            let mut delete = transaction
+                .prepare("DELETE FROM usage_buckets WHERE granularity = ?1 AND start = ?2 AND tool_name = ?3")?;
+            for (granularity, start, tool_name) in saved.buckets.keys().filter(|key| !rows.buckets.contains_key(*key)) {
+                delete.execute(params![granularity.as_str(), start, tool_name])?;
+            }
+        }
+        transaction.commit()?;
+        *saved_rows = Some(rows);
+        Ok(())
+    }
+
+    fn load_history(&self, limit: usize) -> Result<Vec<StoredCall>> {
+        self.query_history(&HistoryQuery {
+            limit,
+            ..HistoryQuery::default()
+        })
+    }
+
+    fn append_history(&self, calls: &[StoredCall]) -> Result<()> {
+        if calls.is_empty() {
+            return Ok(());
+        }
+        let mut connection = self.connection.lock();
+        let transaction = connection.transaction()?;
+        {
+            let mut insert = transaction.prepare_cached(&format!(
+                "INSERT INTO tool_calls (timestamp_ms, {CALL_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"
+            ))?;
+            for call in calls {
+                let record = &call.call.record;
+                let timestamp_ms = DateTime::parse_from_rfc3339(&record.timestamp)
+                    .map(|timestamp| timestamp.timestamp_millis())
+                    .unwrap_or_default();
+                insert.execute(params![
+                    timestamp_ms,
+                    call.connection_id,
+                    record.timestamp,
+                    record.tool_name,
+                    record.args_json,
+                    record.output_json,
+                    record.duration_ms.map(|ms| i64::try_from(ms).unwrap_or(i64::MAX)),
+                    call.call.annotations.slow,
+                    call.call.annotations.request_id,
+                ])?;
+            }
+            // Ids are assigned in insertion order, so this keeps the newest calls
+            transaction.execute(
+                "DELETE FROM tool_calls WHERE id <= (SELECT MAX(id) FROM tool_calls) - ?1",
+                params![i64::try_from(HISTORY_DISK_ENTRIES).unwrap_or(i64::MAX)],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn query_history(&self, query: &HistoryQuery) -> Result<Vec<StoredCall>> {
+        // Only add the conditions in use, so SQLite can pick the matching index
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        if let Some(ref connection_id) = query.connection_id {
+            conditions.push("connection_id = ?");
+            values.push(Value::Text(connection_id.clone()));
+        }
+        if let Some(ref tool_name) = query.tool_name {
+            conditions.push("tool_name = ?");
+            values.push(Value::Text(tool_name.clone()));
+        }
+        if let Some(from) = query.from {
+            conditions.push("timestamp_ms >= ?");
+            values.push(Value::Integer(from.timestamp_millis()));
+        }
+        if let Some(to) = query.to {
+            conditions.push("timestamp_ms < ?");
+            values.push(Value::Integer(to.timestamp_millis()));
+        }
+        values.push(Value::Integer(i64::try_from(query.limit).unwrap_or(i64::MAX)));
+
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let sql = format!("SELECT {CALL_COLUMNS} FROM tool_calls {filter} ORDER BY timestamp_ms DESC, id DESC LIMIT ?");
+
+        let connection = self.connection.lock();
+        let mut statement = connection.prepare_cached(&sql)?;
+        let mut calls = statement
+            .query_map(rusqlite::params_from_iter(values), |row| {
+                let record = ToolCallRecord {
+                    timestamp: row.get(1)?,
+                    tool_name: row.get(2)?,
+                    args_json: row.get(3)?,
+                    output_json: row.get(4)?,
+                    duration_ms: row
+                        .get::<_, Option<i64>>(5)?
+                        .map(|ms| u64::try_from(ms).unwrap_or_default()),
+                };
+                let annotations = CallAnnotations {
+                    slow: row.get(6)?,
+                    request_id: row.get(7)?,
+                };
+                Ok(StoredCall {
+                    connection_id: row.get(0)?,
+                    call: HistoryRecord { record, annotations },
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        calls.reverse();
+        Ok(calls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usage_tracker::UsageStats;
+
+    /// A fresh database path, removed (with its WAL files) on drop
+    struct TempDb(PathBuf);
+
+    impl TempDb {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("kodegen-sqlite-{}-{}.sqlite", std::process::id(), name));
+            let db = Self(path);
+            db.remove();
+            db
+        }
+
+        fn remove(&self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut path = self.0.clone().into_os_string();
+                path.push(suffix);
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            self.remove();
+        }
+    }
+
+    fn call(index: usize) -> StoredCall {
+        let timestamp = DateTime::from_timestamp(1_700_000_000 + index as i64, 0).unwrap();
+        StoredCall {
+            connection_id: Some("conn-1".to_string()),
+            call: HistoryRecord {
+                record: ToolCallRecord {
+                    timestamp: timestamp.to_rfc3339(),
+                    tool_name: "fs_read_file".to_string(),
+                    args_json: format!("{{\"index\":{index}}}"),
+                    output_json: "{}".to_string(),
+                    duration_ms: Some(index as u64),
+                },
+                annotations: CallAnnotations::default(),
+            },
+        }
+    }
+
+    fn count(storage: &SqliteStorage, table: &str) -> i64 {
+        storage
+            .connection
+            .lock()
+            .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| row.get(0))
+            .unwrap()
+    }
+
+    fn snapshot(connection_ids: &[&str], bucket_starts: &[i64]) -> StatsSnapshot {
+        let mut snapshot = StatsSnapshot::default();
+        for connection_id in connection_ids {
+            snapshot.connections.insert(connection_id.to_string(), UsageStats::default());
+        }
+        for start in bucket_starts {
+            snapshot.buckets.record(*start, "fs_read_file", true, Some(3));
+        }
+        snapshot
+    }
+
+    #[test]
+    fn history_is_trimmed_to_the_newest_disk_entries() {
+        let db = TempDb::new("trim");
+        let storage = SqliteStorage::open(&db.0).unwrap();
+
+        let calls: Vec<_> = (0..HISTORY_DISK_ENTRIES + 10).map(call).collect();
+        let (first, rest) = calls.split_at(HISTORY_DISK_ENTRIES);
+        storage.append_history(first).unwrap();
+        storage.append_history(rest).unwrap();
+
+        assert_eq!(count(&storage, "tool_calls"), HISTORY_DISK_ENTRIES as i64);
+        let loaded = storage.load_history(usize::MAX).unwrap();
+        assert_eq!(loaded.len(), HISTORY_DISK_ENTRIES);
+        assert_eq!(loaded[0].call.record.args_json, calls[10].call.record.args_json);
+        assert_eq!(loaded.last().unwrap().call.record.duration_ms, Some((HISTORY_DISK_ENTRIES + 9) as u64));
+    }
+
+    #[test]
+    fn saves_remove_dropped_connections_and_pruned_buckets() {
+        let db = TempDb::new("save");
+        let day = 86_400;
+        {
+            let storage = SqliteStorage::open(&db.0).unwrap();
+            storage.save_stats(&snapshot(&["conn-1", "conn-2"], &[0, day])).unwrap();
+            assert_eq!(count(&storage, "connection_stats"), 2);
+            assert_eq!(count(&storage, "usage_buckets"), 4);
+
+            storage.save_stats(&snapshot(&["conn-1"], &[day])).unwrap();
+            assert_eq!(count(&storage, "connection_stats"), 1);
+            assert_eq!(count(&storage, "usage_buckets"), 2);
+        }
+
+        // A new process diffs against what is on disk, not an empty cache
+        let storage = SqliteStorage::open(&db.0).unwrap();
+        let mut changed = snapshot(&["conn-3"], &[day, day + 3600]);
+        changed.buckets.record(day, "fs_read_file", false, None);
+        storage.save_stats(&changed).unwrap();
+
+        let loaded = storage.load_stats().unwrap().unwrap();
+        assert_eq!(loaded.connections.keys().collect::<Vec<_>>(), ["conn-3"]);
+        let mut buckets: Vec<_> = loaded
+            .buckets
+            .iter()
+            .map(|(granularity, start, _, bucket)| (granularity, start, bucket.calls, bucket.failures))
+            .collect();
+        buckets.sort_by_key(|(granularity, start, ..)| (granularity.as_str(), *start));
+        assert_eq!(
+            buckets,
+            [
+                (Granularity::Day, day, 3, 1),
+                (Granularity::Hour, day, 2, 1),
+                (Granularity::Hour, day + 3600, 1, 0),
+            ]
+        );
+    }
+}
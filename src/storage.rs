@@ -0,0 +1,493 @@
+use crate::latency::LatencyHistogram;
+use crate::time_buckets::UsageBuckets;
+use crate::tool_history::HistoryRecord;
+use crate::usage_tracker::UsageStats;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use kodegen_config::KodegenConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Tool calls kept on disk per instance; older calls are dropped
+pub const HISTORY_DISK_ENTRIES: usize = 5000;
+
+/// JSON backend: appended history lines between rotation checks
+const ROTATION_CHECK_INTERVAL: usize = 100;
+
+/// Where `UsageTracker` and `ToolHistory` persist their data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageBackend {
+    /// `stats_<instance>.json` plus `tool-history_<instance>.jsonl`
+    #[default]
+    Json,
+    /// One `stats_<instance>.sqlite` database (requires the `sqlite` cargo feature)
+    Sqlite,
+}
+
+impl StorageBackend {
+    /// Whether this build can open the backend
+    pub fn is_available(self) -> bool {
+        match self {
+            Self::Json => true,
+            Self::Sqlite => cfg!(feature = "sqlite"),
+        }
+    }
+}
+
+impl FromStr for StorageBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "sqlite" => Ok(Self::Sqlite),
+            other => Err(format!("unknown storage backend '{}' (expected json or sqlite)", other)),
+        }
+    }
+}
+
+/// Usage statistics as persisted between runs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatsSnapshot {
+    pub connections: HashMap<String, UsageStats>,
+    #[serde(default)]
+    pub global_latency: HashMap<String, LatencyHistogram>,
+    #[serde(default)]
+    pub buckets: UsageBuckets,
+}
+
+/// A persisted tool call and the connection that made it
+///
+/// History lines written before connection ids were stored have none.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredCall {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection_id: Option<String>,
+    #[serde(flatten)]
+    pub call: HistoryRecord,
+}
+
+/// Filter for `Storage::query_history`
+#[derive(Debug, Clone, Default)]
+pub struct HistoryQuery {
+    pub connection_id: Option<String>,
+    pub tool_name: Option<String>,
+    /// Inclusive lower bound on the call timestamp
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive upper bound on the call timestamp
+    pub to: Option<DateTime<Utc>>,
+    /// Return at most this many of the newest matching calls
+    pub limit: usize,
+}
+
+impl HistoryQuery {
+    pub fn matches(&self, call: &StoredCall) -> bool {
+        let record = &call.call.record;
+        if self
+            .connection_id
+            .as_deref()
+            .is_some_and(|id| call.connection_id.as_deref() != Some(id))
+        {
+            return false;
+        }
+        if self.tool_name.as_deref().is_some_and(|name| record.tool_name != name) {
+            return false;
+        }
+        if self.from.is_none() && self.to.is_none() {
+            return true;
+        }
+        let Ok(timestamp) = DateTime::parse_from_rfc3339(&record.timestamp) else {
+            return false;
+        };
+        self.from.is_none_or(|from| timestamp >= from) && self.to.is_none_or(|to| timestamp < to)
+    }
+}
+
+/// Persistence shared by `UsageTracker` and `ToolHistory`
+///
+/// Methods block on I/O; the trackers call them from their background tasks
+/// or through `spawn_blocking`.
+pub trait Storage: Send + Sync {
+    /// Stats file or database; other instances' stats are looked up next to it
+    fn stats_path(&self) -> &Path;
+
+    /// Load saved stats (None if nothing was saved yet)
+    fn load_stats(&self) -> Result<Option<StatsSnapshot>>;
+
+    /// Replace saved stats with `snapshot`
+    fn save_stats(&self, snapshot: &StatsSnapshot) -> Result<()>;
+
+    /// The newest `limit` calls, oldest first
+    fn load_history(&self, limit: usize) -> Result<Vec<StoredCall>>;
+
+    /// Append calls, keeping at most `HISTORY_DISK_ENTRIES`
+    fn append_history(&self, calls: &[StoredCall]) -> Result<()>;
+
+    /// The newest `query.limit` calls matching `query`, oldest first
+    fn query_history(&self, query: &HistoryQuery) -> Result<Vec<StoredCall>>;
+}
+
+/// Open the `backend` storage of `instance_id` (`{category}-{instance}`)
+pub fn open(backend: StorageBackend, instance_id: &str) -> Result<Arc<dyn Storage>> {
+    match backend {
+        StorageBackend::Json => Ok(Arc::new(JsonStorage::new(instance_id))),
+        #[cfg(feature = "sqlite")]
+        StorageBackend::Sqlite => {
+            let path = stats_dir().join(format!("stats_{instance_id}.sqlite"));
+            let created = !path.exists();
+            let storage = crate::sqlite_storage::SqliteStorage::open(&path)?;
+            if created {
+                import_json(&JsonStorage::new(instance_id), &storage);
+            }
+            Ok(Arc::new(storage))
+        }
+        #[cfg(not(feature = "sqlite"))]
+        StorageBackend::Sqlite => anyhow::bail!("the sqlite storage backend requires the `sqlite` cargo feature"),
+    }
+}
+
+/// Copy what the JSON backend saved into a newly created database
+///
+/// Runs once, so switching an instance to `--storage sqlite` keeps its stats and
+/// history. The JSON files are left in place.
+#[cfg(feature = "sqlite")]
+fn import_json(json: &JsonStorage, storage: &dyn Storage) {
+    let stats = json
+        .load_stats()
+        .and_then(|snapshot| snapshot.map(|snapshot| storage.save_stats(&snapshot)).transpose());
+    if let Err(e) = stats {
+        log::warn!("Failed to import stats from {}: {:#}", json.stats_file.display(), e);
+    }
+
+    let history = json
+        .load_history(HISTORY_DISK_ENTRIES)
+        .and_then(|calls| storage.append_history(&calls).map(|()| calls.len()));
+    match history {
+        Ok(0) => {}
+        Ok(count) => log::info!("Imported {} tool calls from {}", count, json.history_file.display()),
+        Err(e) => log::warn!("Failed to import tool history from {}: {:#}", json.history_file.display(), e),
+    }
+}
+
+/// Instance id of a stats file named `stats_{category}-{instance}.{json,sqlite}`
+pub fn stats_file_instance<'a>(file_name: &'a str, category: &str) -> Option<&'a str> {
+    let rest = file_name.strip_prefix("stats_")?.strip_prefix(category)?.strip_prefix('-')?;
+    rest.strip_suffix(".json")
+        .or_else(|| rest.strip_suffix(".sqlite").filter(|_| cfg!(feature = "sqlite")))
+}
+
+/// Read the stats saved at `path` by any backend (chosen by file extension)
+pub fn read_stats_at(path: &Path) -> Result<StatsSnapshot> {
+    #[cfg(feature = "sqlite")]
+    if path.extension().is_some_and(|ext| ext == "sqlite") {
+        return crate::sqlite_storage::SqliteStorage::open_read_only(path)?
+            .load_stats()
+            .map(Option::unwrap_or_default);
+    }
+    let json = std::fs::read_to_string(path)?;
+    Ok(parse_stats_file(&json)?)
+}
+
+/// Directory holding every instance's stats
+fn stats_dir() -> PathBuf {
+    KodegenConfig::data_dir()
+        .map(|dir| dir.join("stats"))
+        .unwrap_or_default()
+}
+
+/// Parse a stats file written by this or an older version
+///
+/// The layouts are tried in turn rather than through an untagged enum, whose
+/// buffering turns the integer bucket keys into strings that no longer parse.
+fn parse_stats_file(json: &str) -> Result<StatsSnapshot, serde_json::Error> {
+    match serde_json::from_str::<StatsSnapshot>(json) {
+        Ok(snapshot) => Ok(snapshot),
+        // Bare `connection_id -> UsageStats` map written before global latency tracking
+        Err(e) => serde_json::from_str::<HashMap<String, UsageStats>>(json)
+            .map(|connections| StatsSnapshot {
+                connections,
+                ..StatsSnapshot::default()
+            })
+            .map_err(|_| e),
+    }
+}
+
+/// Default backend: a pretty JSON stats file and a JSONL history file
+///
+/// The stats file is rewritten on every save, and the history file is read in
+/// full every `ROTATION_CHECK_INTERVAL` appended calls to trim it.
+pub struct JsonStorage {
+    stats_file: PathBuf,
+    history_file: PathBuf,
+    /// Calls appended since the last rotation check
+    writes_since_check: AtomicUsize,
+}
+
+impl JsonStorage {
+    /// `<data dir>/stats/stats_{instance_id}.json` and `<log dir>/tool-history_{instance_id}.jsonl`
+    pub fn new(instance_id: &str) -> Self {
+        let history_dir = KodegenConfig::log_dir().unwrap_or_else(|_| PathBuf::from("logs"));
+        Self {
+            stats_file: stats_dir().join(format!("stats_{instance_id}.json")),
+            history_file: history_dir.join(format!("tool-history_{instance_id}.jsonl")),
+            writes_since_check: AtomicUsize::new(0),
+        }
+    }
+
+    fn read_history(&self) -> Result<Vec<StoredCall>> {
+        let content = match std::fs::read_to_string(&self.history_file) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", self.history_file.display())),
+        };
+        Ok(content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    /// Trim the history file to the last `HISTORY_DISK_ENTRIES` lines
+    ///
+    /// Writes the kept lines to a temp file, then atomically renames it over
+    /// the history file.
+    fn rotate_if_needed(&self) -> Result<()> {
+        let content = match std::fs::read_to_string(&self.history_file) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let line_count = content.lines().count();
+        if line_count <= HISTORY_DISK_ENTRIES {
+            return Ok(());
+        }
+
+        let temp_file = self.history_file.with_extension("jsonl.tmp");
+        {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&temp_file)?);
+            for line in content.lines().skip(line_count - HISTORY_DISK_ENTRIES) {
+                file.write_all(line.as_bytes())?;
+                file.write_all(b"\n")?;
+            }
+            file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        }
+        std::fs::rename(&temp_file, &self.history_file)?;
+        Ok(())
+    }
+}
+
+impl Storage for JsonStorage {
+    fn stats_path(&self) -> &Path {
+        &self.stats_file
+    }
+
+    fn load_stats(&self) -> Result<Option<StatsSnapshot>> {
+        match std::fs::read_to_string(&self.stats_file) {
+            Ok(json) => Ok(Some(parse_stats_file(&json)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save_stats(&self, snapshot: &StatsSnapshot) -> Result<()> {
+        let json = serde_json::to_string_pretty(snapshot).context("Failed to serialize stats")?;
+
+        if let Some(parent) = self.stats_file.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create stats directory {}", parent.display()))?;
+        }
+
+        // Atomic write: write to temp file, then rename
+        let temp_file = self.stats_file.with_extension("json.tmp");
+        std::fs::write(&temp_file, json)
+            .with_context(|| format!("Failed to write temp stats file {}", temp_file.display()))?;
+        if let Err(e) = std::fs::rename(&temp_file, &self.stats_file) {
+            let _ = std::fs::remove_file(&temp_file);
+            return Err(e).with_context(|| {
+                format!("Failed to rename {} to {}", temp_file.display(), self.stats_file.display())
+            });
+        }
+        Ok(())
+    }
+
+    fn load_history(&self, limit: usize) -> Result<Vec<StoredCall>> {
+        let mut calls = self.read_history()?;
+        calls.drain(..calls.len().saturating_sub(limit));
+        Ok(calls)
+    }
+
+    fn append_history(&self, calls: &[StoredCall]) -> Result<()> {
+        if calls.is_empty() {
+            return Ok(());
+        }
+        if let Some(parent) = self.history_file.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create history directory {}", parent.display()))?;
+        }
+
+        let mut file = std::io::BufWriter::new(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.history_file)?,
+        );
+        for call in calls {
+            if let Ok(json) = serde_json::to_string(call) {
+                file.write_all(json.as_bytes())?;
+                file.write_all(b"\n")?;
+            }
+        }
+        file.flush()?;
+        drop(file);
+
+        let written = self.writes_since_check.fetch_add(calls.len(), Ordering::Relaxed) + calls.len();
+        if written >= ROTATION_CHECK_INTERVAL {
+            self.writes_since_check.store(0, Ordering::Relaxed);
+            self.rotate_if_needed().context("Failed to rotate tool history")?;
+        }
+        Ok(())
+    }
+
+    fn query_history(&self, query: &HistoryQuery) -> Result<Vec<StoredCall>> {
+        let mut calls: Vec<StoredCall> = self
+            .read_history()?
+            .into_iter()
+            .filter(|call| query.matches(call))
+            .collect();
+        calls.drain(..calls.len().saturating_sub(query.limit));
+        Ok(calls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time_buckets::Granularity;
+    use crate::tool_history::CallAnnotations;
+    use kodegen_mcp_schema::tool::tool_history::ToolCallRecord;
+
+    fn call(connection_id: Option<&str>, tool_name: &str, timestamp: &str) -> StoredCall {
+        StoredCall {
+            connection_id: connection_id.map(str::to_string),
+            call: HistoryRecord {
+                record: ToolCallRecord {
+                    timestamp: timestamp.to_string(),
+                    tool_name: tool_name.to_string(),
+                    args_json: "{}".to_string(),
+                    output_json: "{}".to_string(),
+                    duration_ms: None,
+                },
+                annotations: CallAnnotations::default(),
+            },
+        }
+    }
+
+    fn at(rfc3339: &str) -> Option<DateTime<Utc>> {
+        Some(DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc))
+    }
+
+    #[test]
+    fn history_query_filters_by_connection_and_tool() {
+        let stored = call(Some("conn-1"), "fs_read_file", "2023-11-14T22:13:20Z");
+        let legacy = call(None, "fs_read_file", "2023-11-14T22:13:20Z");
+
+        assert!(HistoryQuery::default().matches(&stored));
+        assert!(HistoryQuery::default().matches(&legacy));
+
+        let by_connection = HistoryQuery {
+            connection_id: Some("conn-1".to_string()),
+            ..HistoryQuery::default()
+        };
+        assert!(by_connection.matches(&stored));
+        assert!(!by_connection.matches(&legacy));
+        assert!(!by_connection.matches(&call(Some("conn-2"), "fs_read_file", "2023-11-14T22:13:20Z")));
+
+        let by_tool = HistoryQuery {
+            tool_name: Some("fs_search".to_string()),
+            ..HistoryQuery::default()
+        };
+        assert!(!by_tool.matches(&stored));
+    }
+
+    #[test]
+    fn history_query_time_range_is_half_open() {
+        let query = HistoryQuery {
+            from: at("2023-11-14T22:00:00Z"),
+            to: at("2023-11-14T23:00:00Z"),
+            ..HistoryQuery::default()
+        };
+
+        assert!(query.matches(&call(None, "fs_read_file", "2023-11-14T22:00:00Z")));
+        // Offsets are compared as instants
+        assert!(query.matches(&call(None, "fs_read_file", "2023-11-14T23:59:59+01:00")));
+        assert!(!query.matches(&call(None, "fs_read_file", "2023-11-14T23:00:00Z")));
+        assert!(!query.matches(&call(None, "fs_read_file", "2023-11-14T21:59:59Z")));
+        // Unparseable timestamps only match queries without a time range
+        assert!(!query.matches(&call(None, "fs_read_file", "yesterday")));
+        assert!(HistoryQuery::default().matches(&call(None, "fs_read_file", "yesterday")));
+    }
+
+    #[test]
+    fn stats_file_with_buckets_round_trips() {
+        let stats = UsageStats {
+            total_tool_calls: 2,
+            tool_counts: HashMap::from([("fs_read_file".to_string(), 2)]),
+            ..UsageStats::default()
+        };
+        let mut snapshot = StatsSnapshot {
+            connections: HashMap::from([("conn-1".to_string(), stats)]),
+            ..StatsSnapshot::default()
+        };
+        snapshot.global_latency.entry("fs_read_file".to_string()).or_default().record(12);
+        snapshot.buckets.record(1_700_000_000, "fs_read_file", true, Some(12));
+        snapshot.buckets.record(1_700_000_000, "fs_read_file", false, None);
+
+        let json = serde_json::to_string_pretty(&snapshot).unwrap();
+        let parsed = parse_stats_file(&json).unwrap();
+
+        assert_eq!(parsed.connections["conn-1"].tool_counts["fs_read_file"], 2);
+        assert_eq!(parsed.global_latency["fs_read_file"].summary().count, 1);
+        let buckets: Vec<_> = parsed
+            .buckets
+            .iter()
+            .map(|(granularity, start, tool, bucket)| (granularity, start, tool.to_string(), bucket.calls, bucket.failures))
+            .collect();
+        assert_eq!(
+            buckets,
+            [
+                (Granularity::Hour, 1_699_999_200, "fs_read_file".to_string(), 2, 1),
+                (Granularity::Day, 1_699_920_000, "fs_read_file".to_string(), 2, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn legacy_bare_connection_map_is_read_as_connections() {
+        let json = serde_json::json!({
+            "conn-1": {
+                "total_tool_calls": 3,
+                "successful_calls": 3,
+                "failed_calls": 0,
+                "tool_counts": { "fs_search": 3 },
+                "filesystem_operations": 3,
+                "first_used": 1_700_000_000,
+                "last_used": 1_700_000_600,
+                "total_sessions": 1,
+            }
+        })
+        .to_string();
+
+        let parsed = parse_stats_file(&json).unwrap();
+
+        assert_eq!(parsed.connections["conn-1"].tool_counts["fs_search"], 3);
+        assert!(parsed.global_latency.is_empty());
+        assert_eq!(parsed.buckets.iter().count(), 0);
+        assert!(parse_stats_file("[]").is_err());
+    }
+}
@@ -0,0 +1,381 @@
+use axum::http::HeaderMap;
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::trace::{Span, SpanKind, Status, TraceContextExt, Tracer, TracerProvider};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use std::sync::Arc;
+
+/// OTLP/HTTP path for traces, appended when the endpoint is a bare collector URL
+const OTLP_TRACES_PATH: &str = "/v1/traces";
+
+/// Largest request body buffered to name the request span after its JSON-RPC method
+pub const MAX_TRACED_BODY_BYTES: usize = 4 * 1024 * 1024;
+
+/// OpenTelemetry tracing for JSON-RPC requests and tool calls
+///
+/// Spans are only created when an OTLP endpoint is configured; otherwise every
+/// method is a no-op. Incoming W3C `traceparent` headers are honored, so spans
+/// join the trace started by kodegend or the client.
+#[derive(Clone, Default)]
+pub struct Telemetry {
+    inner: Option<Arc<TelemetryInner>>,
+}
+
+struct TelemetryInner {
+    provider: SdkTracerProvider,
+    tracer: SdkTracer,
+    category: String,
+}
+
+impl Telemetry {
+    /// Telemetry that records nothing
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Export spans to an OTLP/HTTP collector (e.g. `http://localhost:4318`)
+    pub fn otlp(endpoint: &str, category: &str, instance_id: &str) -> anyhow::Result<Self> {
+        use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+
+        let endpoint = traces_endpoint(endpoint);
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(&endpoint)
+            .build()
+            .map_err(|e| anyhow::anyhow!("Failed to create OTLP exporter for {}: {}", endpoint, e))?;
+
+        let resource = Resource::builder()
+            .with_service_name(format!("kodegen-{category}"))
+            .with_attributes([
+                KeyValue::new("service.instance.id", instance_id.to_string()),
+                KeyValue::new("kodegen.category", category.to_string()),
+            ])
+            .build();
+
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(resource)
+            .build();
+
+        log::info!("Exporting OpenTelemetry traces to {}", endpoint);
+
+        Ok(Self::with_provider(provider, category))
+    }
+
+    /// Record spans through an already configured tracer provider
+    pub fn with_provider(provider: SdkTracerProvider, category: &str) -> Self {
+        let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+        Self {
+            inner: Some(Arc::new(TelemetryInner {
+                provider,
+                tracer,
+                category: category.to_string(),
+            })),
+        }
+    }
+
+    /// Whether spans are being recorded
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// Trace context carried by the `traceparent`/`tracestate` headers (empty if absent)
+    pub fn extract_context(headers: &HeaderMap) -> Context {
+        TraceContextPropagator::new().extract(&HeaderExtractor(headers))
+    }
+
+    /// Start the span for one JSON-RPC request; returns the context holding it
+    pub fn start_request_span(
+        &self,
+        parent: &Context,
+        http_method: &str,
+        rpc_method: Option<&str>,
+        request_id: Option<&str>,
+        session_id: Option<&str>,
+    ) -> Option<Context> {
+        let inner = self.inner.as_ref()?;
+
+        let name = rpc_method
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("mcp {http_method}"));
+
+        let mut attributes = vec![
+            KeyValue::new("rpc.system", "jsonrpc"),
+            KeyValue::new("http.request.method", http_method.to_string()),
+            KeyValue::new("kodegen.category", inner.category.clone()),
+        ];
+        if let Some(rpc_method) = rpc_method {
+            attributes.push(KeyValue::new("rpc.method", rpc_method.to_string()));
+        }
+        if let Some(request_id) = request_id {
+            attributes.push(KeyValue::new("rpc.jsonrpc.request_id", request_id.to_string()));
+        }
+        if let Some(session_id) = session_id {
+            attributes.push(KeyValue::new("mcp.session.id", session_id.to_string()));
+        }
+
+        let span = inner
+            .tracer
+            .span_builder(name)
+            .with_kind(SpanKind::Server)
+            .with_attributes(attributes)
+            .start_with_context(&inner.tracer, parent);
+
+        Some(parent.with_span(span))
+    }
+
+    /// Start the span for one tool call, as a child of `parent`
+    pub fn start_tool_span(&self, parent: &Context, tool_name: &str) -> Option<ToolSpan> {
+        let inner = self.inner.as_ref()?;
+
+        let span = inner
+            .tracer
+            .span_builder(format!("tool {tool_name}"))
+            .with_kind(SpanKind::Internal)
+            .with_attributes([
+                KeyValue::new("mcp.tool.name", tool_name.to_string()),
+                KeyValue::new("kodegen.category", inner.category.clone()),
+            ])
+            .start_with_context(&inner.tracer, parent);
+
+        Some(ToolSpan { span })
+    }
+
+    /// Flush buffered spans and stop exporting
+    pub async fn shutdown(&self) {
+        let Some(inner) = self.inner.clone() else {
+            return;
+        };
+
+        // Export is blocking I/O; keep it off the async workers
+        match tokio::task::spawn_blocking(move || inner.provider.shutdown()).await {
+            Ok(Ok(())) => log::debug!("OpenTelemetry exporter flushed"),
+            Ok(Err(e)) => log::warn!("Failed to flush OpenTelemetry spans: {}", e),
+            Err(e) => log::warn!("OpenTelemetry shutdown task failed: {}", e),
+        }
+    }
+}
+
+/// Span of an in-progress tool call
+pub struct ToolSpan {
+    span: opentelemetry_sdk::trace::Span,
+}
+
+impl ToolSpan {
+    /// Record the call's outcome and end the span
+    pub fn finish(
+        mut self,
+        connection_id: Option<&str>,
+        connection_id_source: Option<&str>,
+        duration_ms: u64,
+        error: Option<String>,
+    ) {
+        if let Some(connection_id) = connection_id {
+            self.span.set_attribute(KeyValue::new("kodegen.connection_id", connection_id.to_string()));
+        }
+        if let Some(source) = connection_id_source {
+            self.span.set_attribute(KeyValue::new("kodegen.connection_id.source", source.to_string()));
+        }
+        self.span.set_attribute(KeyValue::new("kodegen.duration_ms", duration_ms as i64));
+
+        match error {
+            Some(message) => {
+                self.span.set_attribute(KeyValue::new("error.type", "tool_error"));
+                self.span.set_status(Status::error(message));
+            }
+            None => self.span.set_status(Status::Ok),
+        }
+        self.span.end();
+    }
+}
+
+/// End the request span held by `cx`, marking HTTP errors
+pub fn end_request_span(cx: &Context, status: axum::http::StatusCode) {
+    let span = cx.span();
+    span.set_attribute(KeyValue::new("http.response.status_code", i64::from(status.as_u16())));
+    if status.is_client_error() || status.is_server_error() {
+        span.set_status(Status::error(status.to_string()));
+    }
+    span.end();
+}
+
+/// End the request span held by `cx` once `response` has been sent
+///
+/// Tool calls run while their SSE response streams, so for event streams the span
+/// stays open until the stream ends and tool spans nest inside it. Other responses
+/// end the span right away.
+pub fn end_request_span_after(cx: Context, response: axum::response::Response) -> axum::response::Response {
+    use futures::StreamExt;
+
+    let is_event_stream = response
+        .headers()
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/event-stream"));
+    if !is_event_stream {
+        end_request_span(&cx, response.status());
+        return response;
+    }
+
+    let guard = RequestSpanGuard {
+        cx,
+        status: response.status(),
+    };
+    let (parts, body) = response.into_parts();
+    let body = body.into_data_stream().map(move |chunk| {
+        let _ = &guard;
+        chunk
+    });
+    axum::response::Response::from_parts(parts, axum::body::Body::from_stream(body))
+}
+
+/// Ends a request span when the response stream is finished or dropped
+struct RequestSpanGuard {
+    cx: Context,
+    status: axum::http::StatusCode,
+}
+
+impl Drop for RequestSpanGuard {
+    fn drop(&mut self) {
+        end_request_span(&self.cx, self.status);
+    }
+}
+
+/// Buffer a request body of at most [`MAX_TRACED_BODY_BYTES`]
+///
+/// Returns the body to forward and, if it fit under the cap, its bytes. Larger
+/// bodies are forwarded unchanged, streaming on from where buffering stopped.
+pub async fn buffer_body(body: axum::body::Body) -> Result<(axum::body::Body, Option<axum::body::Bytes>), axum::Error> {
+    use futures::StreamExt;
+
+    let mut stream = body.into_data_stream();
+    let mut buffered = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        buffered.extend_from_slice(&chunk?);
+        if buffered.len() > MAX_TRACED_BODY_BYTES {
+            let head = futures::stream::once(std::future::ready(Ok(axum::body::Bytes::from(buffered))));
+            return Ok((axum::body::Body::from_stream(head.chain(stream)), None));
+        }
+    }
+
+    let bytes = axum::body::Bytes::from(buffered);
+    Ok((axum::body::Body::from(bytes.clone()), Some(bytes)))
+}
+
+/// Method and id of a JSON-RPC message (first entry of a batch)
+pub fn jsonrpc_method_and_id(body: &[u8]) -> (Option<String>, Option<String>) {
+    let Ok(value) = serde_json::from_slice::<serde_json::Value>(body) else {
+        return (None, None);
+    };
+    let message = match &value {
+        serde_json::Value::Array(batch) => batch.first(),
+        message => Some(message),
+    };
+    let Some(message) = message else {
+        return (None, None);
+    };
+
+    let method = message.get("method").and_then(|m| m.as_str()).map(|m| m.to_string());
+    let id = message.get("id").and_then(|id| match id {
+        serde_json::Value::String(id) => Some(id.clone()),
+        serde_json::Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    });
+    (method, id)
+}
+
+/// Read propagation headers from an HTTP header map
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+/// Append the OTLP traces path to a bare collector URL
+fn traces_endpoint(endpoint: &str) -> String {
+    let trimmed = endpoint.trim_end_matches('/');
+    let has_path = trimmed
+        .split_once("://")
+        .is_some_and(|(_, rest)| rest.contains('/'));
+
+    if has_path {
+        trimmed.to_string()
+    } else {
+        format!("{trimmed}{OTLP_TRACES_PATH}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry_sdk::trace::InMemorySpanExporter;
+
+    fn telemetry() -> (Telemetry, InMemorySpanExporter) {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        (Telemetry::with_provider(provider, "filesystem"), exporter)
+    }
+
+    #[test]
+    fn tool_spans_are_children_of_the_request_span() {
+        let (telemetry, exporter) = telemetry();
+
+        let cx = telemetry
+            .start_request_span(&Context::new(), "POST", Some("tools/call"), Some("7"), Some("session"))
+            .expect("telemetry is enabled");
+        let tool_span = telemetry.start_tool_span(&cx, "fs_read_file").expect("telemetry is enabled");
+        tool_span.finish(Some("conn-1"), Some("header"), 12, None);
+        end_request_span(&cx, axum::http::StatusCode::OK);
+
+        let spans = exporter.get_finished_spans().expect("spans were exported");
+        let [tool, request] = spans.as_slice() else {
+            panic!("expected 2 spans, got {}", spans.len());
+        };
+        assert_eq!(request.name, "tools/call");
+        assert_eq!(tool.name, "tool fs_read_file");
+        assert_eq!(tool.span_context.trace_id(), request.span_context.trace_id());
+        assert_eq!(tool.parent_span_id, request.span_context.span_id());
+        assert!(tool.attributes.contains(&KeyValue::new("kodegen.connection_id", "conn-1")));
+    }
+
+    #[tokio::test]
+    async fn event_stream_request_span_ends_with_the_stream() {
+        let (telemetry, exporter) = telemetry();
+        let cx = telemetry
+            .start_request_span(&Context::new(), "POST", Some("tools/call"), None, None)
+            .expect("telemetry is enabled");
+
+        let response = axum::response::Response::builder()
+            .header(axum::http::header::CONTENT_TYPE, "text/event-stream")
+            .body(axum::body::Body::from("data: {}\n\n"))
+            .unwrap();
+        let response = end_request_span_after(cx, response);
+        assert!(exporter.get_finished_spans().unwrap().is_empty());
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"data: {}\n\n");
+        assert_eq!(exporter.get_finished_spans().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn oversized_bodies_are_forwarded_without_buffering() {
+        let small = axum::body::Body::from(r#"{"jsonrpc":"2.0","method":"ping"}"#);
+        let (_, bytes) = buffer_body(small).await.unwrap();
+        assert_eq!(jsonrpc_method_and_id(&bytes.unwrap()).0.as_deref(), Some("ping"));
+
+        let large = vec![b' '; MAX_TRACED_BODY_BYTES + 1];
+        let (body, bytes) = buffer_body(axum::body::Body::from(large.clone())).await.unwrap();
+        assert!(bytes.is_none());
+        assert_eq!(axum::body::to_bytes(body, usize::MAX).await.unwrap().len(), large.len());
+    }
+}
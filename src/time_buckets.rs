@@ -0,0 +1,312 @@
+use crate::latency::{LatencyHistogram, LatencySummary};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+const HOUR_SECS: i64 = 60 * 60;
+const DAY_SECS: i64 = 24 * HOUR_SECS;
+
+/// Width of a usage bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Granularity {
+    Hour,
+    Day,
+}
+
+impl Granularity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Hour => "hour",
+            Self::Day => "day",
+        }
+    }
+
+    pub fn secs(self) -> i64 {
+        match self {
+            Self::Hour => HOUR_SECS,
+            Self::Day => DAY_SECS,
+        }
+    }
+
+    /// Start (unix seconds, UTC) of the bucket containing `timestamp`
+    pub fn bucket_start(self, timestamp: i64) -> i64 {
+        timestamp - timestamp.rem_euclid(self.secs())
+    }
+}
+
+impl FromStr for Granularity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "hour" | "hourly" => Ok(Self::Hour),
+            "day" | "daily" => Ok(Self::Day),
+            other => Err(format!("unknown granularity '{}' (expected hour or day)", other)),
+        }
+    }
+}
+
+/// How long buckets are kept, in buckets of each granularity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BucketRetention {
+    pub hours: u64,
+    pub days: u64,
+}
+
+impl Default for BucketRetention {
+    fn default() -> Self {
+        Self { hours: 7 * 24, days: 90 }
+    }
+}
+
+/// Calls, failures and latency of one tool within one bucket
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolBucket {
+    pub calls: u64,
+    pub failures: u64,
+    pub latency: LatencyHistogram,
+}
+
+/// Hourly and daily per-tool usage, keyed by bucket start (unix seconds)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageBuckets {
+    hourly: BTreeMap<i64, HashMap<String, ToolBucket>>,
+    daily: BTreeMap<i64, HashMap<String, ToolBucket>>,
+}
+
+/// One bucket as returned by `/mcp/stats?granularity=...`
+#[derive(Debug, Clone, Serialize)]
+pub struct BucketReport {
+    pub start: DateTime<Utc>,
+    pub calls: u64,
+    pub failures: u64,
+    pub tools: BTreeMap<String, ToolBucketReport>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolBucketReport {
+    pub calls: u64,
+    pub failures: u64,
+    pub latency: LatencySummary,
+}
+
+impl UsageBuckets {
+    /// Count a call in the hourly and daily buckets containing `timestamp`
+    pub fn record(&mut self, timestamp: i64, tool_name: &str, success: bool, duration_ms: Option<u64>) {
+        for (granularity, buckets) in [(Granularity::Hour, &mut self.hourly), (Granularity::Day, &mut self.daily)] {
+            let bucket = buckets
+                .entry(granularity.bucket_start(timestamp))
+                .or_default()
+                .entry(tool_name.to_string())
+                .or_default();
+            bucket.calls += 1;
+            if !success {
+                bucket.failures += 1;
+            }
+            if let Some(duration_ms) = duration_ms {
+                bucket.latency.record(duration_ms);
+            }
+        }
+    }
+
+    /// Every bucket as `(granularity, start, tool, bucket)`, for row-based storage
+    pub fn iter(&self) -> impl Iterator<Item = (Granularity, i64, &str, &ToolBucket)> {
+        let hourly = self.hourly.iter().map(|(start, tools)| (Granularity::Hour, *start, tools));
+        let daily = self.daily.iter().map(|(start, tools)| (Granularity::Day, *start, tools));
+        hourly.chain(daily).flat_map(|(granularity, start, tools)| {
+            tools
+                .iter()
+                .map(move |(tool, bucket)| (granularity, start, tool.as_str(), bucket))
+        })
+    }
+
+    /// Put back a bucket read from row-based storage
+    pub fn insert(&mut self, granularity: Granularity, start: i64, tool_name: String, bucket: ToolBucket) {
+        let buckets = match granularity {
+            Granularity::Hour => &mut self.hourly,
+            Granularity::Day => &mut self.daily,
+        };
+        buckets.entry(start).or_default().insert(tool_name, bucket);
+    }
+
+    /// Drop buckets older than the retention (the current bucket counts as one)
+    pub fn prune(&mut self, now: i64, retention: BucketRetention) {
+        let keep_from = |granularity: Granularity, count: u64| {
+            let count = i64::try_from(count).unwrap_or(i64::MAX);
+            granularity
+                .bucket_start(now)
+                .saturating_sub((count - 1).max(0).saturating_mul(granularity.secs()))
+        };
+        let hourly_from = keep_from(Granularity::Hour, retention.hours);
+        let daily_from = keep_from(Granularity::Day, retention.days);
+        self.hourly = self.hourly.split_off(&hourly_from);
+        self.daily = self.daily.split_off(&daily_from);
+        if retention.hours == 0 {
+            self.hourly.clear();
+        }
+        if retention.days == 0 {
+            self.daily.clear();
+        }
+    }
+
+    /// Buckets overlapping `[from, to)`, optionally for one tool, oldest first
+    pub fn query(
+        &self,
+        granularity: Granularity,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        tool_name: Option<&str>,
+    ) -> Vec<BucketReport> {
+        let buckets = match granularity {
+            Granularity::Hour => &self.hourly,
+            Granularity::Day => &self.daily,
+        };
+        if from >= to {
+            return Vec::new();
+        }
+
+        buckets
+            .range(granularity.bucket_start(from.timestamp())..to.timestamp())
+            .map(|(start, tools)| {
+                let tools: BTreeMap<String, ToolBucketReport> = tools
+                    .iter()
+                    .filter(|(tool, _)| tool_name.is_none_or(|name| name == tool.as_str()))
+                    .map(|(tool, bucket)| {
+                        let report = ToolBucketReport {
+                            calls: bucket.calls,
+                            failures: bucket.failures,
+                            latency: bucket.latency.summary(),
+                        };
+                        (tool.clone(), report)
+                    })
+                    .collect();
+                BucketReport {
+                    start: DateTime::from_timestamp(*start, 0).unwrap_or_default(),
+                    calls: tools.values().map(|tool| tool.calls).sum(),
+                    failures: tools.values().map(|tool| tool.failures).sum(),
+                    tools,
+                }
+            })
+            .filter(|report| tool_name.is_none() || !report.tools.is_empty())
+            .collect()
+    }
+}
+
+/// Parse a `from`/`to` query value: RFC 3339 or unix seconds
+pub fn parse_timestamp(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(secs) = s.parse::<i64>() {
+        return DateTime::from_timestamp(secs, 0).ok_or_else(|| format!("timestamp out of range: {}", s));
+    }
+    DateTime::parse_from_rfc3339(s)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|_| format!("invalid timestamp '{}' (expected RFC 3339 or unix seconds)", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2023-11-14T22:13:20Z
+    const NOW: i64 = 1_700_000_000;
+
+    fn at(timestamp: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(timestamp, 0).unwrap()
+    }
+
+    fn starts(reports: &[BucketReport]) -> Vec<i64> {
+        reports.iter().map(|report| report.start.timestamp()).collect()
+    }
+
+    #[test]
+    fn buckets_start_on_utc_boundaries() {
+        assert_eq!(Granularity::Hour.bucket_start(NOW), 1_699_999_200);
+        assert_eq!(Granularity::Day.bucket_start(NOW), 1_699_920_000);
+        assert_eq!(Granularity::Hour.bucket_start(-1), -HOUR_SECS);
+    }
+
+    #[test]
+    fn records_calls_in_hourly_and_daily_buckets() {
+        let mut buckets = UsageBuckets::default();
+        buckets.record(NOW, "fs_read_file", true, Some(10));
+        buckets.record(NOW + 60, "fs_read_file", false, None);
+        buckets.record(NOW, "fs_search", true, Some(5));
+
+        for granularity in [Granularity::Hour, Granularity::Day] {
+            let start = granularity.bucket_start(NOW);
+            let reports = buckets.query(granularity, at(start), at(start + granularity.secs()), None);
+            let [report] = reports.as_slice() else {
+                panic!("expected one {} bucket, got {}", granularity.as_str(), reports.len());
+            };
+            assert_eq!((report.calls, report.failures), (3, 1));
+
+            let read = &report.tools["fs_read_file"];
+            assert_eq!((read.calls, read.failures, read.latency.count), (2, 1, 1));
+            assert_eq!(report.tools["fs_search"].latency.max_ms, 5);
+        }
+    }
+
+    #[test]
+    fn query_returns_overlapping_buckets_oldest_first() {
+        let mut buckets = UsageBuckets::default();
+        let hour = Granularity::Hour.bucket_start(NOW);
+        buckets.record(NOW, "fs_read_file", true, None);
+        buckets.record(NOW - 2 * HOUR_SECS, "fs_search", true, None);
+        buckets.record(NOW - 5 * HOUR_SECS, "fs_read_file", true, None);
+
+        // `from` inside a bucket still includes that bucket; `to` is exclusive
+        let reports = buckets.query(Granularity::Hour, at(NOW - 3 * HOUR_SECS + 1), at(NOW + HOUR_SECS), None);
+        assert_eq!(starts(&reports), [hour - 2 * HOUR_SECS, hour]);
+        assert!(buckets.query(Granularity::Hour, at(NOW - 2 * HOUR_SECS), at(hour), None).len() == 1);
+
+        // Filtering by tool drops buckets where it was not called
+        let reports = buckets.query(Granularity::Hour, at(NOW - 6 * HOUR_SECS), at(NOW + 1), Some("fs_read_file"));
+        assert_eq!(starts(&reports), [hour - 5 * HOUR_SECS, hour]);
+        assert!(reports.iter().all(|report| report.tools.len() == 1));
+
+        assert!(buckets.query(Granularity::Hour, at(NOW), at(NOW), None).is_empty());
+    }
+
+    #[test]
+    fn prune_keeps_the_retained_number_of_buckets() {
+        let mut buckets = UsageBuckets::default();
+        for hours_ago in 0..4 {
+            buckets.record(NOW - hours_ago * HOUR_SECS, "fs_read_file", true, None);
+        }
+        buckets.record(NOW - 3 * DAY_SECS, "fs_read_file", true, None);
+
+        buckets.prune(NOW, BucketRetention { hours: 2, days: 2 });
+
+        let hour = Granularity::Hour.bucket_start(NOW);
+        let day = Granularity::Day.bucket_start(NOW);
+        let hourly = buckets.query(Granularity::Hour, at(0), at(NOW + 1), None);
+        assert_eq!(starts(&hourly), [hour - HOUR_SECS, hour]);
+        let daily = buckets.query(Granularity::Day, at(0), at(NOW + 1), None);
+        assert_eq!(starts(&daily), [day]);
+
+        buckets.prune(NOW, BucketRetention { hours: 0, days: 0 });
+        assert_eq!(buckets.iter().count(), 0);
+    }
+
+    #[test]
+    fn prune_with_huge_retention_keeps_everything() {
+        let mut buckets = UsageBuckets::default();
+        buckets.record(NOW, "fs_read_file", true, None);
+        buckets.record(0, "fs_read_file", true, None);
+
+        buckets.prune(NOW, BucketRetention { hours: u64::MAX, days: u64::MAX });
+
+        assert_eq!(buckets.iter().count(), 4);
+    }
+
+    #[test]
+    fn parses_rfc3339_and_unix_timestamps() {
+        assert_eq!(parse_timestamp("1700000000").unwrap(), at(NOW));
+        assert_eq!(parse_timestamp("2023-11-14T23:13:20+01:00").unwrap(), at(NOW));
+        assert!(parse_timestamp("yesterday").is_err());
+        assert!(parse_timestamp(&i64::MAX.to_string()).is_err());
+    }
+}
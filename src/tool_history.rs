@@ -1,24 +1,46 @@
+use crate::storage::{HistoryQuery, JsonStorage, Storage, StoredCall};
 use chrono::{DateTime, Utc};
 use dashmap::DashMap;
-use kodegen_config::KodegenConfig;
 use kodegen_mcp_schema::tool::tool_history::ToolCallRecord;
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::io::Write;
-use std::path::PathBuf;
 use std::sync::Arc;
 use termcolor::{BufferWriter, ColorChoice};
-use tokio::fs::OpenOptions;
-use tokio::io::AsyncWriteExt;
 
 const MAX_HISTORY_ENTRIES: usize = 1000;
-const MAX_DISK_ENTRIES: usize = 5000;
-const ROTATION_CHECK_INTERVAL: usize = 100;
+
+/// Connection id for loaded calls that were stored without one
+const LEGACY_CONNECTION_ID: &str = "__legacy__";
+
+/// Server-side annotations stored alongside a `ToolCallRecord`
+///
+/// Serialized flat next to the record's own fields, so history files stay
+/// readable as plain `ToolCallRecord` lines.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CallAnnotations {
+    /// The call exceeded its slow-call threshold
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub slow: bool,
+    /// Request id of the call (from `X-Request-Id` or generated)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// Tool call record together with its annotations, as stored in history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    #[serde(flatten)]
+    pub record: ToolCallRecord,
+    #[serde(flatten)]
+    pub annotations: CallAnnotations,
+}
 
 /// Update event for background processor
 enum HistoryUpdate {
     AddCall {
         connection_id: String,
-        record: ToolCallRecord,
+        record: HistoryRecord,
     },
     RemoveConnection(String), // connection_id
 }
@@ -26,51 +48,38 @@ enum HistoryUpdate {
 /// Tool call history manager with per-connection in-memory cache and disk persistence
 #[derive(Clone)]
 pub struct ToolHistory {
-    /// Per-connection entries (connection_id -> VecDeque<ToolCallRecord>)
-    entries_by_connection: Arc<DashMap<String, VecDeque<ToolCallRecord>>>,
+    /// Per-connection entries (connection_id -> VecDeque<HistoryRecord>)
+    entries_by_connection: Arc<DashMap<String, VecDeque<HistoryRecord>>>,
 
-    /// Path to JSONL history file
-    history_file: PathBuf,
+    /// Where calls are persisted (shared with `UsageTracker`)
+    storage: Arc<dyn Storage>,
 
     /// Write queue for async batching (per-connection)
-    write_queue: Arc<DashMap<String, Vec<ToolCallRecord>>>,
+    write_queue: Arc<DashMap<String, Vec<HistoryRecord>>>,
 
     /// Fire-and-forget channel for recording calls
     update_sender: tokio::sync::mpsc::UnboundedSender<HistoryUpdate>,
-
-    /// Counter for rotation check
-    writes_since_check: Arc<tokio::sync::RwLock<usize>>,
 }
 
 impl ToolHistory {
     /// Create new history manager and start background writer
     pub async fn new(instance_id: String) -> Self {
-        // Determine history file location
-        let history_dir = KodegenConfig::log_dir()
-            .unwrap_or_else(|_| PathBuf::from("logs"));
-
-        // Create directory if needed (async)
-        if let Err(e) = tokio::fs::create_dir_all(&history_dir).await {
-            let bufwtr = BufferWriter::stderr(ColorChoice::Auto);
-            let mut buffer = bufwtr.buffer();
-            let _ = writeln!(&mut buffer, "Failed to create history directory: {e}");
-            let _ = bufwtr.print(&buffer);
-        }
-
-        let history_file = history_dir.join(format!("tool-history_{instance_id}.jsonl"));
+        Self::with_storage(Arc::new(JsonStorage::new(&instance_id))).await
+    }
 
+    /// Create history manager persisting to `storage` (shared with `UsageTracker`)
+    pub async fn with_storage(storage: Arc<dyn Storage>) -> Self {
         // Create unbounded channel for fire-and-forget recording
         let (update_sender, update_receiver) = tokio::sync::mpsc::unbounded_channel();
 
         let history = Self {
             entries_by_connection: Arc::new(DashMap::new()),
-            history_file: history_file.clone(),
+            storage,
             write_queue: Arc::new(DashMap::new()),
             update_sender,
-            writes_since_check: Arc::new(tokio::sync::RwLock::new(0)),
         };
 
-        // Load existing history from disk (stored globally but distributed per-connection on load)
+        // Load existing history from disk, restoring each call to its connection
         history.load_from_disk().await;
 
         // Start background processor
@@ -87,6 +96,19 @@ impl ToolHistory {
         arguments: serde_json::Value,
         output: serde_json::Value,
         duration_ms: Option<u64>,
+    ) {
+        self.track_annotated_call(connection_id, tool_name, arguments, output, duration_ms, CallAnnotations::default());
+    }
+
+    /// Add an annotated tool call to history (fire-and-forget, never blocks)
+    pub fn track_annotated_call(
+        &self,
+        connection_id: &str,
+        tool_name: String,
+        arguments: serde_json::Value,
+        output: serde_json::Value,
+        duration_ms: Option<u64>,
+        annotations: CallAnnotations,
     ) {
         // Serialize Value → String immediately (single allocation per field)
         let args_json = serde_json::to_string(&arguments)
@@ -106,12 +128,19 @@ impl ToolHistory {
         // If send fails (channel closed), silently ignore - history is best-effort
         let _ = self.update_sender.send(HistoryUpdate::AddCall {
             connection_id: connection_id.to_string(),
-            record,
+            record: HistoryRecord { record, annotations },
         });
     }
 
     /// Get history for a specific connection
     pub fn get_history_for_connection(&self, connection_id: &str) -> Option<Vec<ToolCallRecord>> {
+        self.entries_by_connection
+            .get(connection_id)
+            .map(|entry| entry.value().iter().map(|entry| entry.record.clone()).collect())
+    }
+
+    /// Get history for a specific connection, including annotations
+    pub fn get_annotated_history_for_connection(&self, connection_id: &str) -> Option<Vec<HistoryRecord>> {
         self.entries_by_connection
             .get(connection_id)
             .map(|entry| entry.value().iter().cloned().collect())
@@ -138,6 +167,7 @@ impl ToolHistory {
         // Filter entries
         let filtered: Vec<_> = entries
             .iter()
+            .map(|entry| &entry.record)
             .filter(|record| {
                 // Filter by tool name
                 if let Some(name) = tool_name
@@ -178,6 +208,19 @@ impl ToolHistory {
         filtered[start..end].to_vec()
     }
 
+    /// Number of records waiting to be written to disk
+    pub fn write_queue_depth(&self) -> usize {
+        self.write_queue.iter().map(|entry| entry.len()).sum()
+    }
+
+    /// Query persisted calls by connection, tool and time (beyond the in-memory window)
+    ///
+    /// Calls reach storage within about a second of being tracked.
+    pub async fn query(&self, query: HistoryQuery) -> anyhow::Result<Vec<StoredCall>> {
+        let storage = Arc::clone(&self.storage);
+        tokio::task::spawn_blocking(move || storage.query_history(&query)).await?
+    }
+
     /// Remove connection history (called when connection is deleted)
     pub fn remove_connection(&self, connection_id: &str) {
         let _ = self
@@ -185,43 +228,24 @@ impl ToolHistory {
             .send(HistoryUpdate::RemoveConnection(connection_id.to_string()));
     }
 
-    /// Load history from disk (JSONL format) - stored globally but for backward compatibility
+    /// Load the most recent calls from storage into the per-connection cache
     async fn load_from_disk(&self) {
-        if !tokio::fs::try_exists(&self.history_file)
-            .await
-            .unwrap_or(false)
-        {
-            return;
-        }
-
-        match tokio::fs::read_to_string(&self.history_file).await {
-            Ok(content) => {
-                let mut entries = VecDeque::new();
-
-                // Parse each line as JSON
-                for line in content.lines() {
-                    if let Ok(record) = serde_json::from_str::<ToolCallRecord>(line) {
-                        entries.push_back(record);
-                    }
-                }
-
-                // Keep only last 1000 entries
-                while entries.len() > MAX_HISTORY_ENTRIES {
-                    entries.pop_front();
-                }
-
-                // For backward compatibility, store in a "__legacy__" connection_id
-                // In practice, this data won't be visible to any specific connection
-                if !entries.is_empty() {
-                    self.entries_by_connection.insert("__legacy__".to_string(), entries);
+        let storage = Arc::clone(&self.storage);
+        let loaded = tokio::task::spawn_blocking(move || storage.load_history(MAX_HISTORY_ENTRIES)).await;
+
+        match loaded {
+            Ok(Ok(calls)) => {
+                // Calls stored without a connection id are not visible to any specific connection
+                for call in calls {
+                    let connection_id = call.connection_id.unwrap_or_else(|| LEGACY_CONNECTION_ID.to_string());
+                    self.entries_by_connection
+                        .entry(connection_id)
+                        .or_default()
+                        .push_back(call.call);
                 }
             }
-            Err(e) => {
-                let bufwtr = BufferWriter::stderr(ColorChoice::Auto);
-                let mut buffer = bufwtr.buffer();
-                let _ = writeln!(&mut buffer, "Failed to load tool history: {e}");
-                let _ = bufwtr.print(&buffer);
-            }
+            Ok(Err(e)) => report_error(&format!("Failed to load tool history: {e:#}")),
+            Err(e) => report_error(&format!("Failed to load tool history: {e}")),
         }
     }
 
@@ -232,8 +256,7 @@ impl ToolHistory {
     ) {
         let entries_by_connection = Arc::clone(&self.entries_by_connection);
         let write_queue = Arc::clone(&self.write_queue);
-        let writes_since_check = Arc::clone(&self.writes_since_check);
-        let history_file = self.history_file.clone();
+        let storage = Arc::clone(&self.storage);
 
         tokio::spawn(async move {
             // Disk flush interval (1 second)
@@ -279,88 +302,28 @@ impl ToolHistory {
 
                     // Periodic disk flush
                     _ = flush_interval.tick() => {
-                        // Collect all records from all connection queues
-                        let mut all_records = Vec::new();
-
-                        for mut entry in write_queue.iter_mut() {
-                            let records = std::mem::take(entry.value_mut());
-                            all_records.extend(records);
-                        }
-
+                        let all_records = Self::drain_write_queue(&write_queue);
                         if all_records.is_empty() {
                             continue;
                         }
 
-                        // Append to file (JSONL format)
-                        match OpenOptions::new()
-                            .create(true)
-                            .append(true)
-                            .open(&history_file)
-                            .await
-                        {
-                            Ok(mut file) => {
-                                for record in &all_records {
-                                    if let Ok(json) = serde_json::to_string(record) {
-                                        let line = format!("{json}\n");
-                                        let _ = file.write_all(line.as_bytes()).await;
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                let bufwtr = BufferWriter::stderr(ColorChoice::Auto);
-                                let mut buffer = bufwtr.buffer();
-                                let _ = writeln!(&mut buffer, "Failed to write tool history: {e}");
-                                let _ = bufwtr.print(&buffer);
-                                continue;
-                            }
-                        }
-
-                        // Check if rotation is needed
-                        let should_rotate = {
-                            let mut check_counter = writes_since_check.write().await;
-                            *check_counter += all_records.len();
-
-                            if *check_counter >= ROTATION_CHECK_INTERVAL {
-                                *check_counter = 0;
-                                true
-                            } else {
-                                false
-                            }
-                        };
-
-                        if should_rotate {
-                            // Perform rotation check
-                            if let Err(e) = Self::rotate_if_needed(&history_file).await {
-                                let bufwtr = BufferWriter::stderr(ColorChoice::Auto);
-                                let mut buffer = bufwtr.buffer();
-                                let _ = writeln!(&mut buffer, "Failed to rotate tool history: {e}");
-                                let _ = bufwtr.print(&buffer);
-                            }
+                        let storage = Arc::clone(&storage);
+                        match tokio::task::spawn_blocking(move || storage.append_history(&all_records)).await {
+                            Ok(Ok(())) => {}
+                            Ok(Err(e)) => report_error(&format!("Failed to write tool history: {e:#}")),
+                            Err(e) => report_error(&format!("Failed to write tool history: {e}")),
                         }
                     }
 
                     // Channel closed (shutdown)
                     else => {
                         // Flush any remaining records before exiting
-                        let mut all_records = Vec::new();
-
-                        for entry in write_queue.iter() {
-                            all_records.extend(entry.value().clone());
-                        }
-
-                        if !all_records.is_empty()
-                            && let Ok(mut file) = OpenOptions::new()
-                                .create(true)
-                                .append(true)
-                                .open(&history_file)
-                                .await
+                        let all_records = Self::drain_write_queue(&write_queue);
+                        let storage = Arc::clone(&storage);
+                        if let Ok(Err(e)) =
+                            tokio::task::spawn_blocking(move || storage.append_history(&all_records)).await
                         {
-                            for record in &all_records {
-                                if let Ok(json) = serde_json::to_string(record) {
-                                    let line = format!("{json}\n");
-                                    let _ = file.write_all(line.as_bytes()).await;
-                                }
-                            }
+                            report_error(&format!("Failed to write tool history: {e:#}"));
                         }
 
                         break;
@@ -370,47 +333,25 @@ impl ToolHistory {
         });
     }
 
-    /// Check if file needs rotation and rotate if necessary
-    ///
-    /// This is called periodically by the background writer when the write counter
-    /// reaches `ROTATION_CHECK_INTERVAL`. If the file has more than `MAX_DISK_ENTRIES`
-    /// lines, it keeps only the last `MAX_DISK_ENTRIES` lines and atomically replaces
-    /// the file using a temp file + rename strategy.
-    async fn rotate_if_needed(history_file: &PathBuf) -> Result<(), std::io::Error> {
-        // Read current file
-        let content = match tokio::fs::read_to_string(history_file).await {
-            Ok(c) => c,
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
-            Err(e) => return Err(e),
-        };
-
-        // Count lines
-        let line_count = content.lines().count();
-
-        // Only rotate if exceeds limit
-        if line_count <= MAX_DISK_ENTRIES {
-            return Ok(());
-        }
-
-        // Keep only the last MAX_DISK_ENTRIES lines
-        let keep_from = line_count.saturating_sub(MAX_DISK_ENTRIES);
-        let kept_lines: Vec<&str> = content.lines().skip(keep_from).collect();
-
-        // Write to temporary file (atomic operation step 1)
-        let temp_file = history_file.with_extension("jsonl.tmp");
-        {
-            let mut file = tokio::fs::File::create(&temp_file).await?;
-            for line in kept_lines {
-                file.write_all(line.as_bytes()).await?;
-                file.write_all(b"\n").await?;
-            }
-            file.sync_all().await?;
+    /// Take every queued record, tagged with its connection id
+    fn drain_write_queue(write_queue: &DashMap<String, Vec<HistoryRecord>>) -> Vec<StoredCall> {
+        let mut all_records = Vec::new();
+        for mut entry in write_queue.iter_mut() {
+            let connection_id = entry.key().clone();
+            let records = std::mem::take(entry.value_mut());
+            all_records.extend(records.into_iter().map(|call| StoredCall {
+                connection_id: Some(connection_id.clone()),
+                call,
+            }));
         }
-
-        // Atomic rename (atomic operation step 2)
-        // On Unix systems (including macOS), this is an atomic filesystem operation
-        tokio::fs::rename(&temp_file, history_file).await?;
-
-        Ok(())
+        all_records
     }
 }
+
+/// Print a history error to stderr (history is best-effort and never fails a call)
+fn report_error(message: &str) {
+    let bufwtr = BufferWriter::stderr(ColorChoice::Auto);
+    let mut buffer = bufwtr.buffer();
+    let _ = writeln!(&mut buffer, "{message}");
+    let _ = bufwtr.print(&buffer);
+}
@@ -1,3 +1,7 @@
+use crate::latency::LatencyHistogram;
+use crate::storage::{self, JsonStorage, StatsSnapshot, Storage};
+use crate::time_buckets::{BucketReport, BucketRetention, Granularity, UsageBuckets};
+use chrono::{DateTime, Utc};
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -9,10 +13,18 @@ enum StatsUpdate {
     Success {
         connection_id: String,
         tool_name: String,
+        duration_ms: Option<u64>,
     },
     Failure {
         connection_id: String,
         tool_name: String,
+        duration_ms: Option<u64>,
+    },
+    /// Duration of a call without a connection id (global latency only)
+    Latency {
+        tool_name: String,
+        success: bool,
+        duration_ms: u64,
     },
     RemoveConnection(String), // connection_id
     SaveToDisk, // Periodic flush to disk
@@ -27,14 +39,10 @@ const SAVE_INTERVAL_SECS: u64 = 5 * 60;
 
 /// Statistics tracked for tool usage
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "StoredUsageStats")]
 pub struct UsageStats {
-    // Tool category counters
-    pub filesystem_operations: u64,
-    pub terminal_operations: u64,
-    pub edit_operations: u64,
-    pub search_operations: u64,
-    pub config_operations: u64,
-    pub process_operations: u64,
+    // Calls per tool category (`ToolMetadata` category name, e.g. "filesystem")
+    pub category_counts: HashMap<String, u64>,
 
     // Overall counters
     pub total_tool_calls: u64,
@@ -44,6 +52,10 @@ pub struct UsageStats {
     // Tool-specific counters
     pub tool_counts: HashMap<String, u64>,
 
+    // Tool-specific call durations (absent in stats files written before latency tracking)
+    #[serde(default)]
+    pub tool_latency: HashMap<String, LatencyHistogram>,
+
     // Timing information
     pub first_used: i64, // Unix timestamp
     pub last_used: i64,  // Unix timestamp
@@ -54,16 +66,12 @@ impl Default for UsageStats {
     fn default() -> Self {
         let now = chrono::Utc::now().timestamp();
         Self {
-            filesystem_operations: 0,
-            terminal_operations: 0,
-            edit_operations: 0,
-            search_operations: 0,
-            config_operations: 0,
-            process_operations: 0,
+            category_counts: HashMap::new(),
             total_tool_calls: 0,
             successful_calls: 0,
             failed_calls: 0,
             tool_counts: HashMap::new(),
+            tool_latency: HashMap::new(),
             first_used: now,
             last_used: now,
             total_sessions: 1,
@@ -71,12 +79,133 @@ impl Default for UsageStats {
     }
 }
 
+impl UsageStats {
+    /// Add another connection's or instance's stats to these
+    pub fn merge(&mut self, other: &UsageStats) {
+        for (category, count) in &other.category_counts {
+            *self.category_counts.entry(category.clone()).or_insert(0) += count;
+        }
+        self.total_tool_calls += other.total_tool_calls;
+        self.successful_calls += other.successful_calls;
+        self.failed_calls += other.failed_calls;
+        for (tool, count) in &other.tool_counts {
+            *self.tool_counts.entry(tool.clone()).or_insert(0) += count;
+        }
+        for (tool, histogram) in &other.tool_latency {
+            self.tool_latency.entry(tool.clone()).or_default().merge(histogram);
+        }
+        self.first_used = self.first_used.min(other.first_used);
+        self.last_used = self.last_used.max(other.last_used);
+        self.total_sessions += other.total_sessions;
+    }
+
+    /// Sum of several stats (None if there are none)
+    pub fn merged<'a>(stats: impl IntoIterator<Item = &'a UsageStats>) -> Option<UsageStats> {
+        let mut stats = stats.into_iter();
+        let mut total = stats.next()?.clone();
+        for other in stats {
+            total.merge(other);
+        }
+        Some(total)
+    }
+}
+
+/// Totals of one server instance, from its stats file (or memory for this instance)
+#[derive(Debug, Clone, Serialize)]
+pub struct InstanceStats {
+    pub instance_id: String,
+    /// This is the running instance (stats are live, not from its last save)
+    pub live: bool,
+    pub connections: usize,
+    /// Sum over the instance's connections (None if it had none)
+    pub totals: Option<UsageStats>,
+}
+
+/// Usage of a category across every instance with a stats file
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryHistory {
+    /// Oldest instance first
+    pub instances: Vec<InstanceStats>,
+    /// Sum over all instances (None if no instance had connections)
+    pub totals: Option<UsageStats>,
+    /// Per-tool call durations across all instances
+    pub global_latency: HashMap<String, LatencyHistogram>,
+}
+
+/// `UsageStats` as written by current or older versions
+///
+/// Before `category_counts`, stats files had one fixed counter per category.
+/// They are folded into the map on load; `config_operations` also counted
+/// introspection and prompt tools, which stay under "config".
+#[derive(Deserialize)]
+struct StoredUsageStats {
+    #[serde(default)]
+    category_counts: HashMap<String, u64>,
+    #[serde(default)]
+    filesystem_operations: u64,
+    #[serde(default)]
+    terminal_operations: u64,
+    #[serde(default)]
+    edit_operations: u64,
+    #[serde(default)]
+    search_operations: u64,
+    #[serde(default)]
+    config_operations: u64,
+    #[serde(default)]
+    process_operations: u64,
+    total_tool_calls: u64,
+    successful_calls: u64,
+    failed_calls: u64,
+    tool_counts: HashMap<String, u64>,
+    #[serde(default)]
+    tool_latency: HashMap<String, LatencyHistogram>,
+    first_used: i64,
+    last_used: i64,
+    total_sessions: u64,
+}
+
+impl From<StoredUsageStats> for UsageStats {
+    fn from(stored: StoredUsageStats) -> Self {
+        let mut category_counts = stored.category_counts;
+        let legacy = [
+            (kodegen_config::CATEGORY_FILESYSTEM.name, stored.filesystem_operations),
+            (kodegen_config::CATEGORY_TERMINAL.name, stored.terminal_operations),
+            (kodegen_config::CATEGORY_CONFIG.name, stored.config_operations),
+            (kodegen_config::CATEGORY_PROCESS.name, stored.process_operations),
+            ("edit", stored.edit_operations),
+            ("search", stored.search_operations),
+        ];
+        for (category, count) in legacy {
+            if count > 0 {
+                *category_counts.entry(category.to_string()).or_insert(0) += count;
+            }
+        }
+
+        Self {
+            category_counts,
+            total_tool_calls: stored.total_tool_calls,
+            successful_calls: stored.successful_calls,
+            failed_calls: stored.failed_calls,
+            tool_counts: stored.tool_counts,
+            tool_latency: stored.tool_latency,
+            first_used: stored.first_used,
+            last_used: stored.last_used,
+            total_sessions: stored.total_sessions,
+        }
+    }
+}
+
 /// Usage tracker that manages per-connection statistics for all tool calls
 #[derive(Clone)]
 pub struct UsageTracker {
     /// Per-connection stats storage (connection_id -> UsageStats)
     stats_by_connection: Arc<DashMap<String, UsageStats>>,
-    stats_file: PathBuf,
+    /// Per-tool call durations across all connections, kept when connections are removed
+    global_latency: Arc<DashMap<String, LatencyHistogram>>,
+    /// Hourly and daily per-tool usage across all connections
+    buckets: Arc<parking_lot::Mutex<UsageBuckets>>,
+    bucket_retention: BucketRetention,
+    storage: Arc<dyn Storage>,
     session_start: std::time::Instant,
     /// Fire-and-forget channel for stat updates
     update_sender: tokio::sync::mpsc::UnboundedSender<StatsUpdate>,
@@ -86,23 +215,41 @@ impl UsageTracker {
     /// Create new `UsageTracker` with instance-specific stats file in ~/.kodegen/stats_{`instance_id}.json`
     #[must_use]
     pub fn new(instance_id: String) -> Self {
-        let stats_file = Self::get_stats_file_path(&instance_id);
+        Self::with_bucket_retention(instance_id, BucketRetention::default())
+    }
 
+    /// Create new `UsageTracker` keeping hourly/daily usage buckets for `bucket_retention`
+    #[must_use]
+    pub fn with_bucket_retention(instance_id: String, bucket_retention: BucketRetention) -> Self {
+        Self::with_storage(Arc::new(JsonStorage::new(&instance_id)), bucket_retention)
+    }
+
+    /// Create new `UsageTracker` persisting to `storage` (shared with `ToolHistory`)
+    #[must_use]
+    pub fn with_storage(storage: Arc<dyn Storage>, bucket_retention: BucketRetention) -> Self {
         // Load existing stats from disk (if available)
-        let stats_by_connection = Self::load_from_disk(&stats_file);
+        let StatsSnapshot {
+            connections,
+            global_latency,
+            mut buckets,
+        } = Self::load_from_disk(storage.as_ref());
+        buckets.prune(Utc::now().timestamp(), bucket_retention);
 
         // Create unbounded channel for fire-and-forget updates
         let (update_sender, update_receiver) = tokio::sync::mpsc::unbounded_channel();
 
         let tracker = Self {
-            stats_by_connection: Arc::new(stats_by_connection),
-            stats_file: stats_file.clone(),
+            stats_by_connection: Arc::new(connections.into_iter().collect()),
+            global_latency: Arc::new(global_latency.into_iter().collect()),
+            buckets: Arc::new(parking_lot::Mutex::new(buckets)),
+            bucket_retention,
+            storage,
             session_start: std::time::Instant::now(),
             update_sender: update_sender.clone(),
         };
 
         // Start background processor
-        tracker.start_background_processor(update_receiver, stats_file);
+        tracker.start_background_processor(update_receiver);
 
         // Start periodic save timer
         tracker.start_periodic_save_timer();
@@ -116,17 +263,10 @@ impl UsageTracker {
         self.session_start.elapsed()
     }
 
-    /// Get the path to the stats file on disk
+    /// Get the path to the stats file (or database) on disk
     #[must_use]
     pub fn stats_file_path(&self) -> &std::path::Path {
-        &self.stats_file
-    }
-
-    /// Get stats file path using kodegen_config (directory creation happens async)
-    fn get_stats_file_path(instance_id: &str) -> PathBuf {
-        kodegen_config::KodegenConfig::data_dir()
-            .map(|dir| dir.join("stats").join(format!("stats_{instance_id}.json")))
-            .unwrap_or_else(|_| PathBuf::from(format!("stats_{instance_id}.json")))
+        self.storage.stats_path()
     }
 
     /// Check if this is a new session (30+ min since last activity)
@@ -147,6 +287,7 @@ impl UsageTracker {
         let _ = self.update_sender.send(StatsUpdate::Success {
             connection_id: connection_id.to_string(),
             tool_name: tool_name.to_string(),
+            duration_ms: None,
         });
     }
 
@@ -155,15 +296,145 @@ impl UsageTracker {
         let _ = self.update_sender.send(StatsUpdate::Failure {
             connection_id: connection_id.to_string(),
             tool_name: tool_name.to_string(),
+            duration_ms: None,
         });
     }
 
+    /// Track a completed tool call and its duration (fire-and-forget, never blocks)
+    ///
+    /// Calls without a connection id only count towards global latency.
+    pub fn track_call(&self, connection_id: Option<&str>, tool_name: &str, success: bool, duration_ms: u64) {
+        let tool_name = tool_name.to_string();
+        let update = match connection_id {
+            Some(connection_id) if success => StatsUpdate::Success {
+                connection_id: connection_id.to_string(),
+                tool_name,
+                duration_ms: Some(duration_ms),
+            },
+            Some(connection_id) => StatsUpdate::Failure {
+                connection_id: connection_id.to_string(),
+                tool_name,
+                duration_ms: Some(duration_ms),
+            },
+            None => StatsUpdate::Latency {
+                tool_name,
+                success,
+                duration_ms,
+            },
+        };
+        let _ = self.update_sender.send(update);
+    }
+
     /// Get stats for a specific connection
     #[must_use]
     pub fn get_stats_for_connection(&self, connection_id: &str) -> Option<UsageStats> {
         self.stats_by_connection.get(connection_id).map(|entry| entry.value().clone())
     }
 
+    /// Get stats for every tracked connection (connection_id -> UsageStats)
+    #[must_use]
+    pub fn get_all_stats(&self) -> HashMap<String, UsageStats> {
+        self.stats_by_connection
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
+    }
+
+    /// Get stats summed over every tracked connection (None if there are none)
+    #[must_use]
+    pub fn get_totals(&self) -> Option<UsageStats> {
+        let stats: Vec<UsageStats> = self.stats_by_connection.iter().map(|entry| entry.value().clone()).collect();
+        UsageStats::merged(&stats)
+    }
+
+    /// Merge the stats files of every instance of `category` into one history
+    ///
+    /// Reads `stats_{category}-*.json` (and `.sqlite` with the `sqlite` feature)
+    /// from the stats directory (blocking I/O).
+    /// The running instance contributes its in-memory stats instead of its file,
+    /// which may be up to 5 minutes old. Unreadable files are skipped.
+    #[must_use]
+    pub fn category_history(&self, category: &str) -> CategoryHistory {
+        let own_path = self.storage.stats_path();
+        let mut files: Vec<(String, PathBuf)> = own_path
+            .parent()
+            .and_then(|dir| std::fs::read_dir(dir).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                let instance_id = storage::stats_file_instance(&name, category)?.to_string();
+                Some((instance_id, entry.path()))
+            })
+            .collect();
+        if !files.iter().any(|(_, path)| path == own_path) {
+            let own = own_path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            let instance_id = storage::stats_file_instance(own, category).unwrap_or(own).to_string();
+            files.push((instance_id, own_path.to_path_buf()));
+        }
+        // Instance ids start with their start time, so this is oldest first
+        files.sort();
+
+        let mut instances = Vec::with_capacity(files.len());
+        let mut global_latency: HashMap<String, LatencyHistogram> = HashMap::new();
+        for (instance_id, path) in files {
+            let live = path == own_path;
+            let file = if live {
+                StatsSnapshot {
+                    connections: self.get_all_stats(),
+                    global_latency: self.get_global_latency(),
+                    buckets: UsageBuckets::default(),
+                }
+            } else {
+                match storage::read_stats_at(&path) {
+                    Ok(file) => file,
+                    Err(e) => {
+                        log::warn!("Skipping stats file {}: {}", path.display(), e);
+                        continue;
+                    }
+                }
+            };
+
+            for (tool, histogram) in &file.global_latency {
+                global_latency.entry(tool.clone()).or_default().merge(histogram);
+            }
+            instances.push(InstanceStats {
+                instance_id,
+                live,
+                connections: file.connections.len(),
+                totals: UsageStats::merged(file.connections.values()),
+            });
+        }
+
+        CategoryHistory {
+            totals: UsageStats::merged(instances.iter().filter_map(|instance| instance.totals.as_ref())),
+            instances,
+            global_latency,
+        }
+    }
+
+    /// Get per-tool call durations across all connections
+    #[must_use]
+    pub fn get_global_latency(&self) -> HashMap<String, LatencyHistogram> {
+        self.global_latency
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
+    }
+
+    /// Hourly or daily usage buckets overlapping `[from, to)`, optionally for one tool
+    #[must_use]
+    pub fn get_buckets(
+        &self,
+        granularity: Granularity,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        tool_name: Option<&str>,
+    ) -> Vec<BucketReport> {
+        self.buckets.lock().query(granularity, from, to, tool_name)
+    }
+
     /// Remove connection stats (called when connection is deleted)
     pub fn remove_connection(&self, connection_id: &str) {
         let _ = self
@@ -181,69 +452,61 @@ impl UsageTracker {
         let _ = self.update_sender.send(StatsUpdate::Shutdown);
     }
 
-    /// Load stats from disk (atomic read with error recovery)
-    fn load_from_disk(stats_file: &PathBuf) -> DashMap<String, UsageStats> {
-        match std::fs::read_to_string(stats_file) {
-            Ok(json) => match serde_json::from_str::<HashMap<String, UsageStats>>(&json) {
-                Ok(map) => {
-                    log::info!("Loaded {} connection stats from {}", map.len(), stats_file.display());
-                    map.into_iter().collect()
-                }
-                Err(e) => {
-                    log::warn!("Failed to parse stats file {}: {} - starting fresh", stats_file.display(), e);
-                    DashMap::new()
-                }
-            },
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                log::debug!("No existing stats file at {} - starting fresh", stats_file.display());
-                DashMap::new()
+    /// Load stats from storage (starting fresh on errors)
+    fn load_from_disk(storage: &dyn Storage) -> StatsSnapshot {
+        let stats_file = storage.stats_path();
+        match storage.load_stats() {
+            Ok(Some(snapshot)) => {
+                log::info!("Loaded {} connection stats from {}", snapshot.connections.len(), stats_file.display());
+                snapshot
+            }
+            Ok(None) => {
+                log::debug!("No existing stats at {} - starting fresh", stats_file.display());
+                StatsSnapshot::default()
             }
             Err(e) => {
-                log::warn!("Failed to read stats file {}: {} - starting fresh", stats_file.display(), e);
-                DashMap::new()
+                log::warn!("Failed to load stats from {}: {:#} - starting fresh", stats_file.display(), e);
+                StatsSnapshot::default()
             }
         }
     }
 
-    /// Save stats to disk (atomic write with temp file)
-    fn save_to_disk(stats_by_connection: &DashMap<String, UsageStats>, stats_file: &PathBuf) {
-        // Convert DashMap to HashMap for serialization
-        let snapshot: HashMap<String, UsageStats> = stats_by_connection
-            .iter()
-            .map(|entry| (entry.key().clone(), entry.value().clone()))
-            .collect();
-
-        // Serialize to JSON
-        let json = match serde_json::to_string_pretty(&snapshot) {
-            Ok(j) => j,
-            Err(e) => {
-                log::error!("Failed to serialize stats: {}", e);
-                return;
-            }
+    /// Save stats to storage (the write runs on the blocking pool)
+    async fn save_to_disk(
+        stats_by_connection: &DashMap<String, UsageStats>,
+        global_latency: &DashMap<String, LatencyHistogram>,
+        buckets: &parking_lot::Mutex<UsageBuckets>,
+        bucket_retention: BucketRetention,
+        storage: &Arc<dyn Storage>,
+    ) {
+        // Drop expired buckets before writing them out
+        let buckets = {
+            let mut buckets = buckets.lock();
+            buckets.prune(Utc::now().timestamp(), bucket_retention);
+            buckets.clone()
         };
 
-        // Ensure parent directory exists
-        if let Some(parent) = stats_file.parent()
-            && let Err(e) = std::fs::create_dir_all(parent) {
-                log::error!("Failed to create stats directory {}: {}", parent.display(), e);
-                return;
-            }
-
-        // Atomic write: write to temp file, then rename
-        let temp_file = stats_file.with_extension("json.tmp");
-
-        if let Err(e) = std::fs::write(&temp_file, json) {
-            log::error!("Failed to write temp stats file {}: {}", temp_file.display(), e);
-            return;
-        }
+        // Convert DashMaps to HashMaps for serialization
+        let snapshot = StatsSnapshot {
+            connections: stats_by_connection
+                .iter()
+                .map(|entry| (entry.key().clone(), entry.value().clone()))
+                .collect(),
+            global_latency: global_latency
+                .iter()
+                .map(|entry| (entry.key().clone(), entry.value().clone()))
+                .collect(),
+            buckets,
+        };
 
-        if let Err(e) = std::fs::rename(&temp_file, stats_file) {
-            log::error!("Failed to rename {} to {}: {}", temp_file.display(), stats_file.display(), e);
-            let _ = std::fs::remove_file(&temp_file); // Clean up temp file
-            return;
+        let connection_count = snapshot.connections.len();
+        let writer = Arc::clone(storage);
+        let stats_file = storage.stats_path();
+        match tokio::task::spawn_blocking(move || writer.save_stats(&snapshot)).await {
+            Ok(Ok(())) => log::debug!("Saved {} connection stats to {}", connection_count, stats_file.display()),
+            Ok(Err(e)) => log::error!("Failed to save stats to {}: {:#}", stats_file.display(), e),
+            Err(e) => log::error!("Failed to save stats to {}: {}", stats_file.display(), e),
         }
-
-        log::debug!("Saved {} connection stats to {}", snapshot.len(), stats_file.display());
     }
 
     /// Start periodic save timer (saves every 5 minutes)
@@ -263,9 +526,12 @@ impl UsageTracker {
     fn start_background_processor(
         &self,
         mut update_receiver: tokio::sync::mpsc::UnboundedReceiver<StatsUpdate>,
-        stats_file: PathBuf,
     ) {
         let stats_by_connection = Arc::clone(&self.stats_by_connection);
+        let global_latency = Arc::clone(&self.global_latency);
+        let buckets = Arc::clone(&self.buckets);
+        let bucket_retention = self.bucket_retention;
+        let storage = Arc::clone(&self.storage);
 
         tokio::spawn(async move {
             loop {
@@ -274,6 +540,7 @@ impl UsageTracker {
                         StatsUpdate::Success {
                             connection_id,
                             tool_name,
+                            duration_ms,
                         } => {
                             // Get or create stats for this connection
                             let mut stats = stats_by_connection
@@ -295,30 +562,24 @@ impl UsageTracker {
                             // Update tool-specific counter
                             *stats.tool_counts.entry(tool_name.clone()).or_insert(0) += 1;
 
+                            // Update tool-specific latency, per connection and globally
+                            if let Some(duration_ms) = duration_ms {
+                                stats.tool_latency.entry(tool_name.clone()).or_default().record(duration_ms);
+                                global_latency.entry(tool_name.clone()).or_default().record(duration_ms);
+                            }
+
                             // Update category counter
                             if let Some(category) = Self::get_category(&tool_name) {
-                                match category {
-                                    name if name == kodegen_config::CATEGORY_FILESYSTEM.name => {
-                                        stats.filesystem_operations += 1
-                                    }
-                                    name if name == kodegen_config::CATEGORY_TERMINAL.name => {
-                                        stats.terminal_operations += 1
-                                    }
-                                    name if name == kodegen_config::CATEGORY_INTROSPECTION.name
-                                        || name == kodegen_config::CATEGORY_CONFIG.name
-                                        || name == kodegen_config::CATEGORY_PROMPT.name => {
-                                        stats.config_operations += 1
-                                    }
-                                    name if name == kodegen_config::CATEGORY_PROCESS.name => {
-                                        stats.process_operations += 1
-                                    }
-                                    _ => {}
-                                }
+                                *stats.category_counts.entry(category.to_string()).or_insert(0) += 1;
                             }
+                            drop(stats);
+
+                            buckets.lock().record(now, &tool_name, true, duration_ms);
                         }
                         StatsUpdate::Failure {
                             connection_id,
                             tool_name,
+                            duration_ms,
                         } => {
                             // Get or create stats for this connection
                             let mut stats = stats_by_connection
@@ -340,26 +601,29 @@ impl UsageTracker {
                             // Update tool-specific counter
                             *stats.tool_counts.entry(tool_name.clone()).or_insert(0) += 1;
 
+                            // Update tool-specific latency, per connection and globally
+                            if let Some(duration_ms) = duration_ms {
+                                stats.tool_latency.entry(tool_name.clone()).or_default().record(duration_ms);
+                                global_latency.entry(tool_name.clone()).or_default().record(duration_ms);
+                            }
+
                             // Update category counter
                             if let Some(category) = Self::get_category(&tool_name) {
-                                match category {
-                                    name if name == kodegen_config::CATEGORY_FILESYSTEM.name => {
-                                        stats.filesystem_operations += 1
-                                    }
-                                    name if name == kodegen_config::CATEGORY_TERMINAL.name => {
-                                        stats.terminal_operations += 1
-                                    }
-                                    name if name == kodegen_config::CATEGORY_INTROSPECTION.name
-                                        || name == kodegen_config::CATEGORY_CONFIG.name
-                                        || name == kodegen_config::CATEGORY_PROMPT.name => {
-                                        stats.config_operations += 1
-                                    }
-                                    name if name == kodegen_config::CATEGORY_PROCESS.name => {
-                                        stats.process_operations += 1
-                                    }
-                                    _ => {}
-                                }
+                                *stats.category_counts.entry(category.to_string()).or_insert(0) += 1;
                             }
+                            drop(stats);
+
+                            buckets.lock().record(now, &tool_name, false, duration_ms);
+                        }
+                        StatsUpdate::Latency {
+                            tool_name,
+                            success,
+                            duration_ms,
+                        } => {
+                            buckets
+                                .lock()
+                                .record(Utc::now().timestamp(), &tool_name, success, Some(duration_ms));
+                            global_latency.entry(tool_name).or_default().record(duration_ms);
                         }
                         StatsUpdate::RemoveConnection(connection_id) => {
                             // Remove stats for this connection
@@ -367,19 +631,40 @@ impl UsageTracker {
                         }
                         StatsUpdate::SaveToDisk => {
                             // Periodic flush to disk
-                            Self::save_to_disk(&stats_by_connection, &stats_file);
+                            Self::save_to_disk(
+                                &stats_by_connection,
+                                &global_latency,
+                                &buckets,
+                                bucket_retention,
+                                &storage,
+                            )
+                            .await;
                         }
                         StatsUpdate::Shutdown => {
                             // Final flush and shutdown
                             log::info!("UsageTracker shutting down - saving stats to disk");
-                            Self::save_to_disk(&stats_by_connection, &stats_file);
+                            Self::save_to_disk(
+                                &stats_by_connection,
+                                &global_latency,
+                                &buckets,
+                                bucket_retention,
+                                &storage,
+                            )
+                            .await;
                             break; // Exit the background processor
                         }
                     },
                     // Channel closed (server shutdown)
                     None => {
                         log::info!("UsageTracker channel closed - final save to disk");
-                        Self::save_to_disk(&stats_by_connection, &stats_file);
+                        Self::save_to_disk(
+                            &stats_by_connection,
+                            &global_latency,
+                            &buckets,
+                            bucket_retention,
+                            &storage,
+                        )
+                        .await;
                         break;
                     }
                 }
@@ -388,3 +673,79 @@ impl UsageTracker {
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counters() -> serde_json::Value {
+        serde_json::json!({
+            "total_tool_calls": 12,
+            "successful_calls": 11,
+            "failed_calls": 1,
+            "tool_counts": { "fs_read_file": 12 },
+            "first_used": 1_700_000_000,
+            "last_used": 1_700_000_600,
+            "total_sessions": 2,
+        })
+    }
+
+    #[test]
+    fn folds_legacy_category_counters_into_the_map() {
+        let mut legacy = counters();
+        let fields = legacy.as_object_mut().unwrap();
+        fields.insert("filesystem_operations".into(), 5.into());
+        fields.insert("terminal_operations".into(), 3.into());
+        fields.insert("config_operations".into(), 2.into());
+        fields.insert("search_operations".into(), 2.into());
+        fields.insert("edit_operations".into(), 0.into());
+
+        let stats: UsageStats = serde_json::from_value(legacy).unwrap();
+
+        assert_eq!(
+            stats.category_counts,
+            HashMap::from([
+                (kodegen_config::CATEGORY_FILESYSTEM.name.to_string(), 5),
+                (kodegen_config::CATEGORY_TERMINAL.name.to_string(), 3),
+                (kodegen_config::CATEGORY_CONFIG.name.to_string(), 2),
+                ("search".to_string(), 2),
+            ])
+        );
+        assert_eq!(stats.total_tool_calls, 12);
+        assert_eq!(stats.tool_counts["fs_read_file"], 12);
+        assert!(stats.tool_latency.is_empty());
+    }
+
+    #[test]
+    fn adds_legacy_counters_to_existing_category_counts() {
+        let mut mixed = counters();
+        let fields = mixed.as_object_mut().unwrap();
+        fields.insert(
+            "category_counts".into(),
+            serde_json::json!({ kodegen_config::CATEGORY_FILESYSTEM.name: 4, "git": 1 }),
+        );
+        fields.insert("filesystem_operations".into(), 5.into());
+
+        let stats: UsageStats = serde_json::from_value(mixed).unwrap();
+
+        assert_eq!(stats.category_counts[kodegen_config::CATEGORY_FILESYSTEM.name], 9);
+        assert_eq!(stats.category_counts["git"], 1);
+    }
+
+    #[test]
+    fn current_format_round_trips_without_legacy_fields() {
+        let mut stats = UsageStats::default();
+        stats.category_counts.insert("git".to_string(), 3);
+        stats.tool_counts.insert("git_status".to_string(), 3);
+        stats.tool_latency.entry("git_status".to_string()).or_default().record(40);
+        stats.total_tool_calls = 3;
+
+        let json = serde_json::to_value(&stats).unwrap();
+        assert!(json.get("filesystem_operations").is_none());
+
+        let restored: UsageStats = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.category_counts, stats.category_counts);
+        assert_eq!(restored.tool_latency, stats.tool_latency);
+        assert_eq!(restored.total_tool_calls, 3);
+    }
+}